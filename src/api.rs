@@ -0,0 +1,510 @@
+use crate::audit::AuditLog;
+use crate::cleaner::{
+    clean_package_cache, CleanOptions, CleanReport, Deleter, KeepCount, ListOrder, OutputFormat, RetentionPolicy, SizeScope,
+    TimeBasis, DEFAULT_TEMP_PATTERNS,
+};
+use crate::dedupe::DedupeMode;
+use crate::duration::TimestampZone;
+use crate::filter::{PackageFilter, VersionGlobs};
+use crate::group::GroupBy;
+use crate::sidecar::VersionSource;
+use crate::sizecache::SizeCache;
+use crate::template::SummaryTemplate;
+use crate::tree::TreeStyle;
+use crate::volume::clean_by_volume;
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Cleans one or more package cache roots, for embedding in other tools:
+///
+/// ```no_run
+/// use cleanpkgcache::{Cleaner, CleanerOptions};
+///
+/// let options = CleanerOptions::builder()
+///     .path(r"C:\PkgCache\VC17LTCG")
+///     .keep(3)
+///     .dry_run(true)
+///     .exclude("VC17*")
+///     .build()?;
+/// for report in Cleaner::new(options).run()? {
+///     println!("{}: {} versions, {} bytes", report.path.display(), report.versions_deleted, report.bytes_freed);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// No summary is printed: each deletion goes to the [`log`] crate at info
+/// level (debug with [`CleanerOptionsBuilder::verbose`]), and the totals come
+/// back as [`CleanReport`]s. A progress bar is drawn when stdout is a
+/// terminal unless [`CleanerOptionsBuilder::quiet`] is set.
+#[derive(Debug)]
+pub struct Cleaner {
+    options: CleanerOptions,
+}
+
+impl Cleaner {
+    pub fn new(options: CleanerOptions) -> Self {
+        Cleaner { options }
+    }
+
+    pub fn options(&self) -> &CleanerOptions {
+        &self.options
+    }
+
+    /// Cleans every root in turn and returns one report per root, stopping
+    /// at the first root that fails. With [`CleanerOptionsBuilder::jobs_per_volume`],
+    /// roots on different volumes are cleaned at the same time instead.
+    pub fn run(&self) -> Result<Vec<CleanReport>> {
+        let options = &self.options.clean;
+        match options.jobs_per_volume {
+            Some(jobs) => clean_by_volume(&self.options.paths, options, jobs, |_| {})
+                .into_iter()
+                .map(|(_, report)| report)
+                .collect(),
+            None => self.options.paths.iter().map(|path| clean_package_cache(path, options)).collect(),
+        }
+    }
+}
+
+/// Settings for a [`Cleaner`], created with [`CleanerOptions::builder`].
+#[derive(Clone, Debug)]
+pub struct CleanerOptions {
+    paths: Vec<PathBuf>,
+    clean: CleanOptions,
+}
+
+impl CleanerOptions {
+    /// A builder starting from the command line's defaults, see
+    /// [`CleanOptions::default`].
+    pub fn builder() -> CleanerOptionsBuilder {
+        CleanerOptionsBuilder::default()
+    }
+
+    /// The cache roots that get cleaned.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The settings applied to each root, for the lower-level functions in
+    /// [`crate::cleaner`].
+    pub fn clean_options(&self) -> &CleanOptions {
+        &self.clean
+    }
+}
+
+/// Builds [`CleanerOptions`]. Every setter corresponds to the command-line
+/// flag of the same name.
+#[derive(Debug, Default)]
+pub struct CleanerOptionsBuilder {
+    paths: Vec<PathBuf>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    pins: Vec<String>,
+    clean_temp: bool,
+    temp_patterns: Vec<String>,
+    group_by: Option<String>,
+    policy: Option<RetentionPolicy>,
+    clean: CleanOptions,
+}
+
+impl CleanerOptionsBuilder {
+    /// Adds a cache root to clean.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Adds several cache roots to clean.
+    pub fn paths<P: Into<PathBuf>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Number of newest versions kept per package, at least 1.
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.clean.keep = KeepCount::Count(keep);
+        self
+    }
+
+    /// Keeps this percentage of each package's versions instead of a fixed
+    /// count, rounded up and at least one version.
+    pub fn keep_percent(mut self, percent: f64) -> Self {
+        self.clean.keep = KeepCount::Percent(percent);
+        self
+    }
+
+    /// Picks the retention rule explicitly, which then needs its companion
+    /// setting ([`Self::delete_older_than`] for age, [`Self::max_total_size`]
+    /// for size) and nothing from the other policies. Without it, a size
+    /// budget selects [`RetentionPolicy::Size`] and anything else
+    /// [`RetentionPolicy::Count`], where a cutoff only spares newer versions.
+    pub fn policy(mut self, policy: RetentionPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// A keep count for one package directory name, overriding [`Self::keep`].
+    pub fn keep_override(mut self, package: impl Into<String>, keep: usize) -> Self {
+        self.clean.keep_overrides.insert(package.into(), keep);
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.clean.dry_run = dry_run;
+        self
+    }
+
+    /// Logs every version considered at debug level.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.clean.verbose = verbose;
+        self
+    }
+
+    /// Hides the progress bar.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.clean.quiet = quiet;
+        self
+    }
+
+    /// Collects kept-version sizes for the JSON report (with
+    /// [`OutputFormat::Json`]) and hides the progress bar.
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.clean.format = format;
+        self
+    }
+
+    /// Per-package count lines instead of each path, see `--summary-only`.
+    pub fn summary_only(mut self, summary_only: bool) -> Self {
+        self.clean.summary_only = summary_only;
+        self
+    }
+
+    /// Worker threads used for deleting; 1 deletes sequentially.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.clean.jobs = jobs;
+        self
+    }
+
+    /// Cleans roots on different volumes at the same time, each with `jobs`
+    /// worker threads, instead of one root after the other.
+    pub fn jobs_per_volume(mut self, jobs: usize) -> Self {
+        self.clean.jobs_per_volume = Some(jobs);
+        self
+    }
+
+    /// Only clean packages matching this glob (repeatable).
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Never clean packages matching this glob (repeatable, wins over includes).
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Always keeps version directories whose name matches this glob
+    /// (repeatable), without counting them towards [`Self::keep`].
+    pub fn pin(mut self, pattern: impl Into<String>) -> Self {
+        self.pins.push(pattern.into());
+        self
+    }
+
+    /// Deletes version directories left by interrupted installs, whatever
+    /// the keep count, see [`Self::temp_pattern`].
+    pub fn clean_temp(mut self, clean_temp: bool) -> Self {
+        self.clean_temp = clean_temp;
+        self
+    }
+
+    /// A glob for [`Self::clean_temp`] (repeatable), replacing
+    /// [`DEFAULT_TEMP_PATTERNS`].
+    pub fn temp_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.temp_patterns.push(pattern.into());
+        self
+    }
+
+    /// Asks on the terminal before each deletion.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.clean.interactive = interactive;
+        self
+    }
+
+    /// Moves versions to the trash instead of deleting them.
+    pub fn trash(mut self, trash: bool) -> Self {
+        self.clean.trash = trash;
+        self
+    }
+
+    /// Renames versions to `<name>.to-delete` instead of deleting them.
+    pub fn stage(mut self, stage: bool) -> Self {
+        self.clean.stage = stage;
+        self
+    }
+
+    /// Prints no summary, and logs in-use skips and retries as warnings.
+    pub fn report_only_errors(mut self, report_only_errors: bool) -> Self {
+        self.clean.report_only_errors = report_only_errors;
+        self
+    }
+
+    /// Collects deletion failures into the report instead of stopping.
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.clean.keep_going = keep_going;
+        self
+    }
+
+    /// Retries for deletions that fail on a briefly locked file.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.clean.retries = retries;
+        self
+    }
+
+    /// Removes package directories left empty.
+    pub fn prune_empty(mut self, prune_empty: bool) -> Self {
+        self.clean.prune_empty = prune_empty;
+        self
+    }
+
+    /// Directory levels below the root that make up a package, at least 1.
+    pub fn package_depth(mut self, package_depth: usize) -> Self {
+        self.clean.package_depth = package_depth;
+        self
+    }
+
+    /// Treats the root's directories as versions, grouped into packages by
+    /// this regex's capture group, see [`GroupBy`].
+    pub fn group_by(mut self, pattern: impl Into<String>) -> Self {
+        self.group_by = Some(pattern.into());
+        self
+    }
+
+    /// Never deletes versions younger than this.
+    pub fn min_age(mut self, min_age: Duration) -> Self {
+        self.clean.min_age = min_age;
+        self
+    }
+
+    /// Keeps at least this many versions of each package, whatever the
+    /// keep count, age or size budget allow.
+    pub fn min_versions(mut self, min_versions: usize) -> Self {
+        self.clean.min_versions = min_versions;
+        self
+    }
+
+    /// Only deletes versions from before this time.
+    pub fn delete_older_than(mut self, cutoff: SystemTime) -> Self {
+        self.clean.delete_older_than = Some(cutoff);
+        self
+    }
+
+    /// Marker files that flag a version as in use, replacing the defaults.
+    pub fn lock_file_names<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.clean.lock_file_names = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Runs `command` with the version path before each deletion; a non-zero
+    /// exit keeps that version.
+    pub fn pre_delete_hook(mut self, command: impl Into<String>) -> Self {
+        self.clean.pre_delete_hook = Some(command.into());
+        self
+    }
+
+    /// Order of the verbose version listing.
+    pub fn sort(mut self, list_order: ListOrder) -> Self {
+        self.clean.list_order = list_order;
+        self
+    }
+
+    /// Shows at most `limit` versions per package in the verbose listing.
+    pub fn verbose_limit(mut self, limit: usize) -> Self {
+        self.clean.verbose_limit = Some(limit);
+        self
+    }
+
+    /// In a dry run, lists the versions each package keeps.
+    pub fn show_kept(mut self, show_kept: bool) -> Self {
+        self.clean.show_kept = show_kept;
+        self
+    }
+
+    /// Prints why each version is kept or deleted; only meant for dry runs.
+    pub fn explain(mut self, explain: bool) -> Self {
+        self.clean.explain = explain;
+        self
+    }
+
+    /// Measures the free space of each root's volume before and after cleaning.
+    pub fn free_space(mut self, free_space: bool) -> Self {
+        self.clean.free_space = free_space;
+        self
+    }
+
+    /// Logs only warnings for packages with nothing to delete, even when verbose.
+    pub fn affected_only(mut self, affected_only: bool) -> Self {
+        self.clean.affected_only = affected_only;
+        self
+    }
+
+    /// Prints the cache as a tree of kept and deleted versions, drawn in `style`.
+    pub fn tree(mut self, style: TreeStyle) -> Self {
+        self.clean.tree = Some(style);
+        self
+    }
+
+    pub fn time_basis(mut self, time_basis: TimeBasis) -> Self {
+        self.clean.time_basis = time_basis;
+        self
+    }
+
+    /// Time zone of the timestamps in the verbose listing.
+    pub fn timestamps(mut self, zone: TimestampZone) -> Self {
+        self.clean.timestamps = zone;
+        self
+    }
+
+    /// Reads each version's real version or date from a sidecar file
+    /// inside it, falling back to its name and timestamp.
+    pub fn version_from(mut self, source: VersionSource) -> Self {
+        self.clean.version_from = Some(source);
+        self
+    }
+
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.clean.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    pub fn include_files(mut self, include_files: bool) -> Self {
+        self.clean.include_files = include_files;
+        self
+    }
+
+    /// Keeps versions up to a size budget in bytes instead of a count.
+    pub fn max_total_size(mut self, bytes: u64) -> Self {
+        self.clean.max_total_size = Some(bytes);
+        self
+    }
+
+    pub fn size_scope(mut self, size_scope: SizeScope) -> Self {
+        self.clean.size_scope = size_scope;
+        self
+    }
+
+    pub fn allow_empty_packages(mut self, allow_empty_packages: bool) -> Self {
+        self.clean.allow_empty_packages = allow_empty_packages;
+        self
+    }
+
+    /// Deletes versions identical to a newer one, see [`DedupeMode`].
+    pub fn dedupe(mut self, mode: DedupeMode) -> Self {
+        self.clean.dedupe = Some(mode);
+        self
+    }
+
+    /// Records every removed version in this log.
+    pub fn audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.clean.audit = Some(audit);
+        self
+    }
+
+    /// Reuses sizes measured by earlier runs. Call [`SizeCache::save`] afterwards.
+    pub fn size_cache(mut self, size_cache: Arc<SizeCache>) -> Self {
+        self.clean.size_cache = Some(size_cache);
+        self
+    }
+
+    /// Prints `template` filled in from each cache root's report instead of
+    /// the summary block.
+    pub fn summary_format(mut self, template: SummaryTemplate) -> Self {
+        self.clean.summary_format = Some(template);
+        self
+    }
+
+    /// Replaces how version directories are removed, [`fs::remove_dir_all`]
+    /// by default. Mostly useful to simulate failures in tests.
+    ///
+    /// [`fs::remove_dir_all`]: std::fs::remove_dir_all
+    pub fn deleter(mut self, deleter: Deleter) -> Self {
+        self.clean.deleter = deleter;
+        self
+    }
+
+    /// Checks the settings and compiles the glob and `group_by` patterns.
+    pub fn build(self) -> Result<CleanerOptions> {
+        let CleanerOptionsBuilder {
+            paths,
+            include,
+            exclude,
+            pins,
+            clean_temp,
+            mut temp_patterns,
+            group_by,
+            policy,
+            mut clean,
+        } = self;
+        match clean.keep {
+            KeepCount::Count(0) => {
+                anyhow::bail!("keep must be at least 1 (keeping 0 would delete every version of a package)")
+            }
+            KeepCount::Percent(percent) if !(percent > 0.0 && percent <= 100.0) => {
+                anyhow::bail!("keep percentage must be above 0% and at most 100%")
+            }
+            _ => {}
+        }
+        if clean.keep_overrides.values().any(|&keep| keep == 0) {
+            anyhow::bail!("keep overrides must be at least 1");
+        }
+        if clean.jobs == 0 || clean.jobs_per_volume == Some(0) {
+            anyhow::bail!("jobs must be at least 1");
+        }
+        if clean.jobs_per_volume.is_some() && clean.interactive {
+            anyhow::bail!("jobs per volume cannot be combined with interactive mode");
+        }
+        if clean.stage && clean.trash {
+            anyhow::bail!("staging cannot be combined with moving to the trash");
+        }
+        if clean.package_depth == 0 {
+            anyhow::bail!("package depth must be at least 1");
+        }
+        clean.filter = PackageFilter::new(&include, &exclude)?;
+        clean.pins = VersionGlobs::new(&pins)?;
+        if clean_temp {
+            if temp_patterns.is_empty() {
+                temp_patterns = DEFAULT_TEMP_PATTERNS.iter().map(|pattern| pattern.to_string()).collect();
+            }
+            clean.temp_dirs = Some(VersionGlobs::new(&temp_patterns)?);
+        }
+        clean.group_by = group_by.as_deref().map(GroupBy::new).transpose()?;
+        clean.policy = match policy {
+            Some(policy) => {
+                check_policy(policy, &clean)?;
+                policy
+            }
+            None if clean.max_total_size.is_some() => RetentionPolicy::Size,
+            None => RetentionPolicy::Count,
+        };
+
+        Ok(CleanerOptions { paths, clean })
+    }
+}
+
+/// Checks that an explicitly chosen policy has its companion setting and
+/// none belonging to another policy.
+fn check_policy(policy: RetentionPolicy, clean: &CleanOptions) -> Result<()> {
+    let by_age = clean.delete_older_than.is_some();
+    let by_size = clean.max_total_size.is_some();
+    match policy {
+        RetentionPolicy::Count if by_age || by_size => {
+            anyhow::bail!("policy(Count) cannot be combined with delete_older_than or max_total_size")
+        }
+        RetentionPolicy::Age if !by_age => anyhow::bail!("policy(Age) needs delete_older_than"),
+        RetentionPolicy::Age if by_size => anyhow::bail!("policy(Age) cannot be combined with max_total_size"),
+        RetentionPolicy::Size if !by_size => anyhow::bail!("policy(Size) needs max_total_size"),
+        RetentionPolicy::Size if by_age => anyhow::bail!("policy(Size) cannot be combined with delete_older_than"),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use log::warn;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Append-only record of every directory a run removed (or would remove),
+/// written with `--log-file`.
+///
+/// Each run starts with a `#` header line holding the start time and the
+/// arguments, followed by one tab-separated line per directory:
+/// `timestamp  action  size_bytes  path`.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Opens `path` for appending and writes the run header.
+    pub fn open(path: &Path, dry_run: bool) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+
+        let arguments: Vec<String> = std::env::args().collect();
+        writeln!(
+            file,
+            "# {} cleanpkgcache {}{}: {}",
+            humantime::format_rfc3339_seconds(SystemTime::now()),
+            env!("CARGO_PKG_VERSION"),
+            if dry_run { " (dry run, entries are hypothetical)" } else { "" },
+            arguments.join(" ")
+        )
+        .with_context(|| format!("Failed to write log file: {}", path.display()))?;
+
+        Ok(AuditLog {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Appends one entry. `action` is e.g. `deleted` or `would-delete`.
+    /// Failures are reported as warnings so they never change what gets deleted.
+    pub fn record(&self, action: &str, bytes: Option<u64>, path: &Path) {
+        let size = bytes.map_or_else(|| "-".to_string(), |bytes| bytes.to_string());
+        let line = format!(
+            "{}\t{}\t{}\t{}\n",
+            humantime::format_rfc3339_seconds(SystemTime::now()),
+            action,
+            size,
+            path.display()
+        );
+
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            warn!("failed to write log file {}: {}", self.path.display(), err);
+        }
+    }
+}
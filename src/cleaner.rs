@@ -0,0 +1,2473 @@
+use crate::audit::AuditLog;
+use crate::color;
+use crate::dedupe::{fingerprint, DedupeMode};
+use crate::duration::{format_timestamp, parse_date, TimestampZone};
+use crate::filter::{PackageFilter, VersionGlobs};
+use crate::group::GroupBy;
+use crate::hook::run_pre_delete_hook;
+use crate::longpath::long_path;
+use crate::permission::{denied_at, is_permission_denied};
+use crate::plan;
+use crate::sidecar::{read_field, VersionSource};
+use crate::size::{dir_size, format_bytes};
+use crate::sizecache::SizeCache;
+use crate::stage::{is_staged, stage};
+use crate::stop_requested;
+use crate::template::SummaryTemplate;
+use crate::tree::{print_tree, TreeStyle};
+use crate::volume::free_space;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{debug, info, warn, Level};
+use rayon::prelude::*;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, SystemTime};
+
+/// Removes a version directory and everything in it. [`CleanOptions::deleter`]
+/// defaults to [`fs::remove_dir_all`]; embedders and tests can swap it,
+/// e.g. to simulate failures.
+pub type Deleter = fn(&Path) -> io::Result<()>;
+
+/// Which rule decides the versions a package keeps, for `--policy`. The
+/// newest version is kept under every policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionPolicy {
+    /// Keep the newest --keep versions
+    #[default]
+    Count,
+    /// Keep the newest version and every version newer than --delete-older-than
+    Age,
+    /// Keep the newest versions that fit in --max-total-size
+    Size,
+}
+
+/// How `--max-total-size` is applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeScope {
+    /// Each package may use up to the budget
+    #[default]
+    Package,
+    /// All packages together share the budget
+    Global,
+}
+
+/// How many of its newest versions a package keeps, from `--keep`: a fixed
+/// count like `3`, or a share of its versions like `25%`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeepCount {
+    Count(usize),
+    /// Percentage of the versions, rounded up and at least one.
+    Percent(f64),
+}
+
+impl KeepCount {
+    /// The number of versions to keep out of `versions`.
+    pub fn resolve(self, versions: usize) -> usize {
+        match self {
+            KeepCount::Count(count) => count,
+            KeepCount::Percent(percent) => ((percent * versions as f64 / 100.0).ceil() as usize).max(1),
+        }
+    }
+}
+
+impl fmt::Display for KeepCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeepCount::Count(count) => write!(f, "{}", count),
+            KeepCount::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// A count as a number and a percentage as a string like `"25%"`.
+impl Serialize for KeepCount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            KeepCount::Count(count) => serializer.serialize_u64(*count as u64),
+            KeepCount::Percent(_) => serializer.collect_str(self),
+        }
+    }
+}
+
+/// Parses a `--keep` value: a number of versions, or a percentage of them
+/// with a trailing `%`.
+pub fn parse_keep(value: &str) -> Result<KeepCount, String> {
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent: f64 = percent
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a valid percentage of versions", value))?;
+        if !(percent > 0.0 && percent <= 100.0) {
+            return Err(format!("'{}' must be a percentage above 0% and at most 100%", value));
+        }
+        return Ok(KeepCount::Percent(percent));
+    }
+
+    let keep: usize = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid number of versions", value))?;
+    if keep == 0 {
+        return Err("keep count must be at least 1 (keeping 0 would delete every version of a package)".to_string());
+    }
+    Ok(KeepCount::Count(keep))
+}
+
+/// The rule that decided whether a version is kept or deleted, shown by
+/// `--explain`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// Among the newest `N` versions of `--keep`.
+    WithinLatest(usize),
+    /// The newest version, which `--policy age` always keeps.
+    Newest,
+    /// Among the newest versions that fit in `--max-total-size`.
+    WithinSizeBudget,
+    /// The newest version, kept although it doesn't fit in the size budget.
+    NewestOverSizeBudget,
+    /// Matches a `--pin` pattern.
+    Pinned,
+    /// Younger than `--min-age`.
+    TooRecent,
+    /// Newer than `--delete-older-than`.
+    NewerThanCutoff,
+    /// Among the newest versions `--min-versions` keeps.
+    MinVersions(usize),
+    /// Holds a `--lock-file-name` marker file.
+    InUse,
+    /// Vetoed by a failing `--pre-delete-hook`.
+    SkippedByHook,
+    /// Declined at an `--interactive` prompt.
+    Declined,
+    /// Older than the newest `N` versions of `--keep`.
+    BeyondLatest(usize),
+    /// Older than `--delete-older-than` under `--policy age`.
+    OlderThanCutoff,
+    /// Doesn't fit in `--max-total-size` after the newer versions.
+    OverSizeBudget,
+    /// A copy of a newer version, with `--dedupe`.
+    Duplicate,
+    /// Leftover of an interrupted install, with `--clean-temp`.
+    Temp,
+}
+
+impl Reason {
+    pub fn is_kept(self) -> bool {
+        !matches!(
+            self,
+            Reason::BeyondLatest(_) | Reason::OlderThanCutoff | Reason::OverSizeBudget | Reason::Duplicate | Reason::Temp
+        )
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.is_kept() { "kept: " } else { "deleted: " })?;
+        match self {
+            Reason::WithinLatest(keep) => write!(f, "within latest {}", keep),
+            Reason::Newest => f.write_str("newest version"),
+            Reason::WithinSizeBudget => f.write_str("within size budget"),
+            Reason::NewestOverSizeBudget => f.write_str("newest version, despite size budget"),
+            Reason::Pinned => f.write_str("pinned"),
+            Reason::TooRecent => f.write_str("newer than min-age"),
+            Reason::NewerThanCutoff => f.write_str("newer than delete-older-than"),
+            Reason::MinVersions(floor) => write!(f, "within min-versions {}", floor),
+            Reason::InUse => f.write_str("in use"),
+            Reason::SkippedByHook => f.write_str("skipped by hook"),
+            Reason::Declined => f.write_str("declined at the prompt"),
+            Reason::BeyondLatest(keep) => write!(f, "beyond latest {}", keep),
+            Reason::OlderThanCutoff => f.write_str("older than delete-older-than"),
+            Reason::OverSizeBudget => f.write_str("over size budget"),
+            Reason::Duplicate => f.write_str("duplicate of a newer version"),
+            Reason::Temp => f.write_str("matches a temp pattern"),
+        }
+    }
+}
+
+/// The same text as printed, e.g. `"kept: within latest 2"`.
+impl Serialize for Reason {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Order of the verbose version listing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListOrder {
+    /// Newest version first
+    #[default]
+    Newest,
+    /// Oldest version first
+    Oldest,
+}
+
+/// Which timestamp of a version directory is used for ordering and `--min-age`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimeBasis {
+    /// Last access time
+    Atime,
+    /// Last modification time
+    #[default]
+    Mtime,
+    /// Status change time (creation time on Windows)
+    Ctime,
+}
+
+impl TimeBasis {
+    fn name(self) -> &'static str {
+        match self {
+            TimeBasis::Atime => "atime",
+            TimeBasis::Mtime => "mtime",
+            TimeBasis::Ctime => "ctime",
+        }
+    }
+
+    fn read(self, metadata: &fs::Metadata) -> io::Result<SystemTime> {
+        match self {
+            TimeBasis::Atime => metadata.accessed(),
+            TimeBasis::Mtime => metadata.modified(),
+            #[cfg(unix)]
+            TimeBasis::Ctime => {
+                use std::os::unix::fs::MetadataExt;
+                let since_epoch = Duration::new(metadata.ctime().max(0) as u64, metadata.ctime_nsec() as u32);
+                Ok(SystemTime::UNIX_EPOCH + since_epoch)
+            }
+            #[cfg(not(unix))]
+            TimeBasis::Ctime => metadata.created(),
+        }
+    }
+}
+
+/// How results are reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable progress and summary
+    #[default]
+    Human,
+    /// A single JSON report on stdout
+    Json,
+}
+
+/// Settings that control how a package cache is cleaned.
+#[derive(Clone, Debug)]
+pub struct CleanOptions {
+    pub keep: KeepCount,
+    /// Which of `keep`, `delete_older_than` and `max_total_size` drives retention.
+    pub policy: RetentionPolicy,
+    pub dry_run: bool,
+    pub verbose: bool,
+    pub quiet: bool,
+    /// Print a per-package count line in the summary instead of each path.
+    pub summary_only: bool,
+    pub jobs: usize,
+    pub format: OutputFormat,
+    pub filter: PackageFilter,
+    /// Version directory names that are always kept, outside the keep count.
+    pub pins: VersionGlobs,
+    /// Version directory names left by interrupted installs, deleted outside
+    /// the keep count, with `--clean-temp`.
+    pub temp_dirs: Option<VersionGlobs>,
+    pub interactive: bool,
+    pub trash: bool,
+    /// Rename versions to `<name>.to-delete` instead of deleting them, with
+    /// `--stage`; see [`crate::stage::purge_staged`] for the second phase.
+    pub stage: bool,
+    pub keep_going: bool,
+    /// How many times a deletion that failed transiently is tried again.
+    pub retries: u32,
+    /// Remove package directories that end up (or already were) empty.
+    pub prune_empty: bool,
+    /// Directory levels below the root that make up a package, 1 for `package/version`.
+    pub package_depth: usize,
+    /// Treat the root's directories as versions grouped into packages by
+    /// this pattern, instead of `package/version` folders.
+    pub group_by: Option<GroupBy>,
+    /// Versions younger than this are never deleted.
+    pub min_age: Duration,
+    /// Fewest versions each package keeps whatever the other rules say,
+    /// with `--min-versions`; 0 for no floor.
+    pub min_versions: usize,
+    /// Versions newer than this are kept even outside the keep count.
+    pub delete_older_than: Option<SystemTime>,
+    /// Marker files that flag a version directory as in use.
+    pub lock_file_names: Vec<String>,
+    /// Order of the verbose version listing.
+    pub list_order: ListOrder,
+    /// Most versions shown in the verbose listing of one package; unlimited when `None`.
+    pub verbose_limit: Option<usize>,
+    /// In a dry run, list the versions each package keeps, newest first.
+    pub show_kept: bool,
+    /// In a dry run, print why each version is kept or deleted, with
+    /// `--explain`; the JSON report carries the same reasons.
+    pub explain: bool,
+    /// Read the free space of each root's volume before cleaning and, in
+    /// real runs, after, with `--free-space`.
+    pub free_space: bool,
+    /// Log nothing but warnings for packages with nothing to delete, even
+    /// when verbose, with `--affected-only`.
+    pub affected_only: bool,
+    /// Print the cache as a tree of kept and deleted versions before the
+    /// summary, drawn in this style, with `--tree`.
+    pub tree: Option<TreeStyle>,
+    /// Command run before each deletion, with `--pre-delete-hook`; a
+    /// non-zero exit keeps the version. Not run in dry runs.
+    pub pre_delete_hook: Option<String>,
+    /// Which timestamp `min_age` and ordering use.
+    pub time_basis: TimeBasis,
+    /// Time zone of the timestamps in the verbose listing.
+    pub timestamps: TimestampZone,
+    /// Sidecar file whose field replaces the version parsed from the
+    /// directory name, or the timestamp when it holds a date.
+    pub version_from: Option<VersionSource>,
+    /// Delete the contents of symlinked version directories instead of just the link.
+    pub follow_symlinks: bool,
+    /// Count regular files at the version level, e.g. `pkg-1.0.zip`, as versions.
+    pub include_files: bool,
+    /// Size budget that replaces the keep count when set.
+    pub max_total_size: Option<u64>,
+    pub size_scope: SizeScope,
+    /// Allow the size budget to remove a package's newest version too.
+    pub allow_empty_packages: bool,
+    /// Per-package keep counts from the config file, keyed by directory name.
+    pub keep_overrides: HashMap<String, usize>,
+    /// Where removed versions are recorded, with `--log-file`.
+    pub audit: Option<Arc<AuditLog>>,
+    /// Sizes remembered from earlier runs, with `--size-cache`.
+    pub size_cache: Option<Arc<SizeCache>>,
+    /// Delete versions identical to a newer one of the same package, whatever the keep count.
+    pub dedupe: Option<DedupeMode>,
+    /// Log no per-package lines and show no progress bar, see [`preview_deletions`].
+    pub silent: bool,
+    /// Print no summary, and log in-use skips and retries as warnings, so a
+    /// run with nothing to report prints nothing.
+    pub report_only_errors: bool,
+    /// Removes version directories; not used for the trash, symlinks or version files.
+    pub deleter: Deleter,
+    /// One-line summary printed instead of the summary block, with `--summary-format`.
+    pub summary_format: Option<SummaryTemplate>,
+    /// Clean roots on different volumes at the same time with this many
+    /// jobs each, see [`crate::volume::clean_by_volume`].
+    pub jobs_per_volume: Option<usize>,
+}
+
+/// Names of leftover directories deleted by `--clean-temp` when no
+/// `--temp-pattern` is given.
+pub const DEFAULT_TEMP_PATTERNS: &[&str] = &["*.tmp", "*~", ".partial*"];
+
+/// Number of versions kept per package when nothing else is configured.
+pub const DEFAULT_KEEP: usize = 2;
+
+/// Deleting is I/O bound, so more threads than this mostly thrash the disk.
+const MAX_DEFAULT_JOBS: usize = 8;
+
+/// Default for `jobs`: the available parallelism, capped at [`MAX_DEFAULT_JOBS`].
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, |cpus| cpus.get())
+        .min(MAX_DEFAULT_JOBS)
+}
+
+/// The command line's defaults: keep the newest [`DEFAULT_KEEP`] versions
+/// of every package, skip versions with a `.lock` or `in_use.marker` file,
+/// and actually delete.
+impl Default for CleanOptions {
+    fn default() -> Self {
+        CleanOptions {
+            keep: KeepCount::Count(DEFAULT_KEEP),
+            policy: RetentionPolicy::Count,
+            dry_run: false,
+            verbose: false,
+            quiet: false,
+            summary_only: false,
+            jobs: default_jobs(),
+            format: OutputFormat::Human,
+            filter: PackageFilter::default(),
+            pins: VersionGlobs::default(),
+            temp_dirs: None,
+            interactive: false,
+            trash: false,
+            stage: false,
+            keep_going: false,
+            retries: 2,
+            prune_empty: false,
+            package_depth: 1,
+            group_by: None,
+            min_age: Duration::ZERO,
+            min_versions: 0,
+            delete_older_than: None,
+            lock_file_names: vec![".lock".to_string(), "in_use.marker".to_string()],
+            list_order: ListOrder::Newest,
+            verbose_limit: None,
+            show_kept: false,
+            explain: false,
+            free_space: false,
+            affected_only: false,
+            tree: None,
+            pre_delete_hook: None,
+            time_basis: TimeBasis::Mtime,
+            timestamps: TimestampZone::Utc,
+            version_from: None,
+            follow_symlinks: false,
+            include_files: false,
+            max_total_size: None,
+            size_scope: SizeScope::Package,
+            allow_empty_packages: false,
+            keep_overrides: HashMap::new(),
+            audit: None,
+            size_cache: None,
+            dedupe: None,
+            silent: false,
+            report_only_errors: false,
+            deleter: |path| fs::remove_dir_all(path),
+            summary_format: None,
+            jobs_per_volume: None,
+        }
+    }
+}
+
+impl CleanOptions {
+    /// Number of versions to keep for `package_name` out of `versions`,
+    /// honoring overrides.
+    fn keep_for(&self, package_name: &str, versions: usize) -> usize {
+        // Matched like package directories are grouped, so on Windows an
+        // override applies whichever spelling of the package was seen first
+        let key = package_key(package_name);
+        self.keep_overrides
+            .iter()
+            .filter(|(name, _)| package_key(name) == key)
+            .map(|(_, &keep)| keep)
+            .max()
+            .unwrap_or_else(|| self.keep.resolve(versions))
+    }
+
+    /// Versions of a package that count towards its keep count: not pinned
+    /// and not temp leftovers.
+    fn counted_versions(&self, versions: &[PackageVersion]) -> usize {
+        versions
+            .iter()
+            .filter(|version| !self.pins.is_match(&version.name) && !self.is_temp(&version.name))
+            .count()
+    }
+
+    /// Whether the version directory `name` is leftover garbage for `--clean-temp`.
+    fn is_temp(&self, name: &str) -> bool {
+        self.temp_dirs.as_ref().is_some_and(|temp_dirs| temp_dirs.is_match(name))
+    }
+
+    /// Level for lines worth seeing even with `report_only_errors`, which
+    /// otherwise would be info.
+    fn notable_level(&self) -> Level {
+        if self.report_only_errors {
+            Level::Warn
+        } else {
+            Level::Info
+        }
+    }
+
+    /// Whether `path` contains one of the lock marker files. This is only a
+    /// best-effort check, nothing stops a build from starting afterwards.
+    fn in_use(&self, path: &Path) -> bool {
+        self.lock_file_names.iter().any(|name| long_path(&path.join(name)).exists())
+    }
+
+    /// Runs the pre-delete hook, if any, for a version about to be deleted.
+    /// Returns why the hook vetoed the deletion.
+    fn hook_veto(&self, path: &Path) -> Option<String> {
+        let command = self.pre_delete_hook.as_deref().filter(|_| !self.dry_run)?;
+        debug!("  Running pre-delete hook for {}", path.display());
+        run_pre_delete_hook(command, path).err()
+    }
+
+    /// Whether `path` can be a version: a directory, or with `include_files`
+    /// also a regular file, that isn't staged for deletion.
+    fn is_version_entry(&self, path: &Path) -> bool {
+        if path.file_name().is_some_and(is_staged) {
+            return false;
+        }
+        let path = long_path(path);
+        path.is_dir() || (self.include_files && path.is_file())
+    }
+
+    /// What happens to a removed version, for the summaries.
+    fn removal(&self) -> &'static str {
+        if self.trash {
+            "moved to trash"
+        } else if self.stage {
+            "staged for deletion"
+        } else {
+            "deleted"
+        }
+    }
+
+    /// The summary line for `bytes` removed. Staged versions still take up
+    /// their space until they are purged.
+    fn freed_line(&self, bytes: u64) -> String {
+        match (self.dry_run, self.stage) {
+            (true, true) => format!("  Would stage {}", format_bytes(bytes)),
+            (true, false) => format!("  Would free {}", format_bytes(bytes)),
+            (false, true) => format!("  Staged {} (freed by --purge-staged)", format_bytes(bytes)),
+            (false, false) => format!("  Freed {}", format_bytes(bytes)),
+        }
+    }
+
+    /// Measures a version directory, through the size cache when there is one.
+    fn dir_size(&self, path: &Path) -> Result<u64> {
+        match &self.size_cache {
+            Some(cache) => cache.dir_size(path),
+            None => dir_size(path),
+        }
+    }
+
+    /// Records a removed (or, in a dry run, removable) version in the `--log-file`.
+    fn audit(&self, version: &PackageVersion, bytes: u64) {
+        let Some(audit) = &self.audit else {
+            return;
+        };
+        let action = match (self.dry_run, self.trash, self.stage) {
+            (true, true, _) => "would-trash",
+            (true, false, true) => "would-stage",
+            (true, false, false) => "would-delete",
+            (false, true, _) => "trashed",
+            (false, false, true) => "staged",
+            (false, false, false) => "deleted",
+        };
+        audit.record(action, Some(bytes), &version.path);
+    }
+}
+
+/// Like [`clean_package_cache`], then prints the summary with
+/// [`print_clean_report`] when using the human format. This is what the
+/// command line does for each cache root.
+pub fn clean_and_print(cache_path: &Path, options: &CleanOptions) -> Result<CleanReport> {
+    let report = clean_package_cache(cache_path, options)?;
+    if options.format == OutputFormat::Human && !options.report_only_errors {
+        print_clean_report(&report, options);
+    }
+    Ok(report)
+}
+
+/// Works out how many versions a run with `options` would delete under
+/// `roots`, and how many bytes that frees, with a dry run that prints
+/// nothing. Roots that aren't directories are left out.
+pub fn preview_deletions(roots: &[PathBuf], options: &CleanOptions) -> Result<(usize, u64)> {
+    let preview = CleanOptions {
+        dry_run: true,
+        interactive: false,
+        audit: None,
+        silent: true,
+        ..options.clone()
+    };
+
+    let mut versions = 0;
+    let mut bytes = 0;
+    for root in roots.iter().filter(|root| root.is_dir()) {
+        let report = clean_package_cache(root, &preview)?;
+        versions += report.versions_deleted;
+        bytes += report.bytes_freed;
+    }
+    Ok((versions, bytes))
+}
+
+/// Cleans one package cache root: every subdirectory is a package and every
+/// directory below that a version. Older versions beyond what `options`
+/// keeps are deleted (or only reported in a dry run).
+///
+/// Progress and per-version lines go to the log; the totals are only
+/// returned, see [`print_clean_report`] for the human summary.
+pub fn clean_package_cache(cache_path: &Path, options: &CleanOptions) -> Result<CleanReport> {
+    let free_space_before = options.free_space.then(|| read_free_space(cache_path)).flatten();
+    let ScannedCache { package_dirs, mut packages, mut errors } = scan_cache(cache_path, options)?;
+
+    // A size budget replaces the keep count with one computed per package
+    let size_budget = options
+        .max_total_size
+        .map(|limit| apply_size_budget(&mut packages, limit, options));
+    let keep_for = |package_name: &str, versions: &[PackageVersion]| {
+        // By age only the newest is kept outright, the cutoff decides the rest
+        if options.policy == RetentionPolicy::Age {
+            return 1;
+        }
+        size_budget
+            .as_ref()
+            .and_then(|(keeps, _)| keeps.get(package_name).copied())
+            .unwrap_or_else(|| options.keep_for(package_name, options.counted_versions(versions)))
+    };
+
+    // Cache health, reported even when nothing ends up deleted
+    let packages_with_excess_versions = packages
+        .iter()
+        .filter(|(name, versions)| options.counted_versions(versions) > keep_for(name, versions))
+        .count();
+    let largest_package = packages
+        .iter()
+        .max_by(|a, b| a.1.len().cmp(&b.1.len()).then_with(|| b.0.cmp(a.0)))
+        .map(|(name, versions)| LargestPackage {
+            name: name.clone(),
+            versions: versions.len(),
+        });
+
+    // Second pass: clean each package
+    let mut total_deleted = 0;
+    let mut total_kept = 0;
+    let mut total_bytes: u64 = 0;
+    let mut duplicates_deleted = 0;
+    let mut bytes_freed_by_dedupe = 0;
+    let mut versions_pinned = 0;
+    let mut temp_dirs_removed = 0;
+    let mut versions_partially_deleted = 0;
+    let mut versions_failed = 0;
+    let mut versions_permission_denied = 0;
+    let mut versions_skipped_by_hook = 0;
+    let mut bytes_freed_by_temp = 0;
+    let packages_count = packages.len();
+    let progress = progress_bar(packages_count, options);
+    let emit = |lines: &[(Level, String)]| {
+        if options.silent || lines.is_empty() {
+            return;
+        }
+        match &progress {
+            Some(bar) => bar.suspend(|| emit_lines(lines)),
+            None => emit_lines(lines),
+        }
+    };
+    let report = |outcome: &PackageOutcome| {
+        emit(&outcome.lines);
+        if let Some(bar) = &progress {
+            bar.inc(1);
+        }
+    };
+    // Real deletions are shown the moment they finish; a dry run is quick
+    // and keeps its lines for the ordered output below
+    let stream: Option<&Stream<'_>> = (!options.dry_run).then_some(&emit);
+
+    // Prompting only makes sense one version at a time
+    let sequential = options.jobs <= 1 || options.interactive;
+    let outcomes: Vec<PackageOutcome> = if sequential {
+        let mut prompter = options.interactive.then(Prompter::new);
+        if prompter.as_ref().is_some_and(|prompter| !prompter.enabled) {
+            warn!("stdin is not a terminal, --interactive will keep every version");
+        }
+
+        let mut outcomes = Vec::new();
+        for (package_name, versions) in packages {
+            if let Some(bar) = &progress {
+                bar.set_message(package_name.clone());
+            }
+            let keep = keep_for(&package_name, &versions);
+            let mut outcome = clean_package(&package_name, versions, keep, options, false, prompter.as_mut(), stream);
+            report(&outcome);
+            // Sequential runs stop at the first failure unless --keep-going is set
+            if !outcome.errors.is_empty() && !options.keep_going {
+                if let Some(bar) = &progress {
+                    bar.finish_and_clear();
+                }
+                return Err(outcome.errors.remove(0));
+            }
+            let aborted = outcome.aborted;
+            outcomes.push(outcome);
+            if aborted {
+                info!("  Aborted, no further versions will be deleted");
+                break;
+            }
+            if stop_requested() {
+                info!("  Interrupted, no further versions will be deleted");
+                break;
+            }
+        }
+        outcomes
+    } else {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.jobs)
+            .build()
+            .context("Failed to create deletion thread pool")?;
+        pool.install(|| {
+            packages
+                .into_par_iter()
+                .map(|(package_name, versions)| {
+                    if let Some(bar) = &progress {
+                        bar.set_message(package_name.clone());
+                    }
+                    let keep = keep_for(&package_name, &versions);
+                    let outcome = clean_package(&package_name, versions, keep, options, true, None, stream);
+                    if options.dry_run {
+                        if let Some(bar) = &progress {
+                            bar.inc(1);
+                        }
+                    } else {
+                        report(&outcome);
+                    }
+                    outcome
+                })
+                .collect()
+        })
+    };
+
+    if let Some(bar) = &progress {
+        bar.finish_and_clear();
+    }
+    // Nothing is deleted, so a dry run can wait and print packages in order
+    // for output that diffs cleanly between runs
+    if options.dry_run && !sequential && !options.silent {
+        for outcome in &outcomes {
+            emit_lines(&outcome.lines);
+        }
+    }
+
+    let aborted = outcomes.iter().any(|outcome| outcome.aborted);
+    let mut package_reports = Vec::new();
+    for mut outcome in outcomes {
+        outcome.report.versions_kept = outcome.kept;
+        total_kept += outcome.kept;
+        total_deleted += outcome.deleted;
+        total_bytes += outcome.bytes;
+        duplicates_deleted += outcome.duplicates;
+        bytes_freed_by_dedupe += outcome.duplicate_bytes;
+        versions_pinned += outcome.pinned;
+        versions_partially_deleted += outcome.report.partially_deleted.len();
+        versions_failed += outcome.failed;
+        versions_permission_denied += outcome.permission_denied;
+        versions_skipped_by_hook += outcome.skipped_by_hook;
+        temp_dirs_removed += outcome.temp_dirs;
+        bytes_freed_by_temp += outcome.temp_bytes;
+        errors.extend(outcome.errors);
+        package_reports.push(outcome.report);
+    }
+    // Fewer than packages_count when the run was aborted or interrupted
+    let packages_processed = package_reports.len();
+
+    let size_budget = size_budget.map(|(_, report)| report);
+    let deleted_paths: Vec<PathBuf> = package_reports
+        .iter()
+        .flat_map(|package| package.deleted.iter().map(|version| version.path.clone()))
+        .collect();
+
+    let mut pruned_packages = Vec::new();
+    if options.prune_empty && !stop_requested() {
+        for (_, path) in &package_dirs {
+            if prune_if_empty(path, &deleted_paths, options) {
+                pruned_packages.push(path.clone());
+            }
+        }
+    }
+    let free_space_after = (options.free_space && !options.dry_run).then(|| read_free_space(cache_path)).flatten();
+
+    Ok(CleanReport {
+        path: cache_path.to_path_buf(),
+        packages: package_reports,
+        deleted_paths,
+        pruned_packages,
+        packages_processed,
+        versions_kept: total_kept,
+        versions_deleted: total_deleted,
+        bytes_freed: total_bytes,
+        duplicates_deleted,
+        bytes_freed_by_dedupe,
+        versions_pinned,
+        versions_partially_deleted,
+        versions_failed,
+        versions_permission_denied,
+        versions_skipped_by_hook,
+        aborted,
+        temp_dirs_removed,
+        bytes_freed_by_temp,
+        packages_with_excess_versions,
+        largest_package,
+        size_budget,
+        free_space_before,
+        free_space_after,
+        errors: errors.iter().map(|err| format!("{:#}", err)).collect(),
+    })
+}
+
+/// Free space on the volume of `cache_path`, or `None` with a warning when
+/// it can't be read, e.g. on some network shares.
+fn read_free_space(cache_path: &Path) -> Option<u64> {
+    match free_space(cache_path) {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            warn!("could not read the free space of {}: {}", cache_path.display(), err);
+            None
+        }
+    }
+}
+
+/// Every package of a cache root with its versions, newest first. Packages
+/// are ordered by name so that output and reports are the same on every run.
+struct ScannedCache {
+    /// Package directories by name; several can share a package on Windows.
+    /// Empty with `--group-by`, where packages have no directory of their own.
+    package_dirs: Vec<(String, PathBuf)>,
+    packages: BTreeMap<String, Vec<PackageVersion>>,
+    /// Packages skipped with `keep_going` because they couldn't be read.
+    errors: Vec<anyhow::Error>,
+}
+
+/// Finds the packages of a cache root and their versions.
+fn scan_cache(cache_path: &Path, options: &CleanOptions) -> Result<ScannedCache> {
+    // The root's ignore file adds to --exclude for this root only
+    let filter = options.filter.with_ignore_file(cache_path)?;
+
+    // First pass: collect all package directories, then scan their versions
+    // in parallel since listing directories is slow on network drives
+    let mut package_dirs = Vec::new();
+    let scanned: Vec<(String, Result<Vec<PackageVersion>>)> = match &options.group_by {
+        Some(group_by) => scan_grouped(cache_path, group_by, &filter, options)?,
+        None => {
+            find_package_dirs(cache_path, None, options.package_depth, &filter, &mut package_dirs)?;
+            // Directory listings come in no particular order
+            package_dirs.sort();
+            package_dirs
+                .par_iter()
+                .map(|(package_name, path)| {
+                    let package_name = package_name.clone();
+                    let versions = scan_package(path, options);
+                    (package_name, versions)
+                })
+                .collect()
+        }
+    };
+
+    // Packages are keyed by their first-seen spelling, so entries that only
+    // differ in case on Windows end up in the same bucket
+    let mut packages: BTreeMap<String, Vec<PackageVersion>> = BTreeMap::new();
+    let mut display_names: HashMap<String, String> = HashMap::new();
+    let mut errors = Vec::new();
+    for (package_name, versions) in scanned {
+        match versions {
+            Ok(versions) if versions.is_empty() => {}
+            Ok(versions) => {
+                let display_name = display_names
+                    .entry(package_key(&package_name))
+                    .or_insert(package_name)
+                    .clone();
+                packages.entry(display_name).or_default().extend(versions);
+            }
+            Err(err) if options.keep_going => {
+                warn!("skipping package {} ({:#})", package_name, err);
+                errors.push(err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    for versions in packages.values_mut() {
+        sort_versions(versions);
+    }
+
+    Ok(ScannedCache { package_dirs, packages, errors })
+}
+
+/// Lists every package of a cache root with the size and timestamp of each
+/// version, without deciding what to keep or deleting anything. Backs
+/// `--list`; see [`print_inventory`] for the human form.
+pub fn list_package_cache(cache_path: &Path, options: &CleanOptions) -> Result<InventoryReport> {
+    let ScannedCache { mut packages, errors, .. } = scan_cache(cache_path, options)?;
+
+    packages
+        .par_iter_mut()
+        .flat_map(|(_, versions)| versions.par_iter_mut())
+        .filter(|version| version.size.is_none())
+        .for_each(|version| version.size = Some(options.dir_size(&version.path).unwrap_or(0)));
+
+    let mut inventory: Vec<InventoryPackage> = packages
+        .into_iter()
+        .map(|(name, mut versions)| {
+            if options.list_order == ListOrder::Oldest {
+                versions.reverse();
+            }
+            let versions: Vec<InventoryVersion> = versions
+                .iter()
+                .map(|version| InventoryVersion {
+                    name: version.name.clone(),
+                    path: version.path.clone(),
+                    size_bytes: version.size.unwrap_or(0),
+                    timestamp: humantime::format_rfc3339_seconds(version.timestamp).to_string(),
+                })
+                .collect();
+            InventoryPackage {
+                name,
+                total_bytes: versions.iter().map(|version| version.size_bytes).sum(),
+                versions,
+            }
+        })
+        .collect();
+    inventory.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(InventoryReport {
+        path: cache_path.to_path_buf(),
+        total_bytes: inventory.iter().map(|package| package.total_bytes).sum(),
+        packages: inventory,
+        errors: errors.iter().map(|err| format!("{:#}", err)).collect(),
+    })
+}
+
+/// Prints an [`InventoryReport`]: each package with its versions, then the totals.
+pub fn print_inventory(report: &InventoryReport, options: &CleanOptions) {
+    println!("Package cache at: {}", report.path.display());
+    for package in &report.packages {
+        let count = package.versions.len();
+        println!("\n{} ({} version{}, {})", color::bold(&package.name), count, if count == 1 { "" } else { "s" },
+            format_bytes(package.total_bytes));
+        for version in &package.versions {
+            println!("  {:<20} {:>10}  {} {}", version.name, format_bytes(version.size_bytes),
+                options.time_basis.name(), version.timestamp);
+        }
+    }
+
+    let versions: usize = report.packages.iter().map(|package| package.versions.len()).sum();
+    println!("\nInventory:");
+    println!("  Packages: {}", report.packages.len());
+    println!("  Versions: {}", versions);
+    println!("  Total size: {}", format_bytes(report.total_bytes));
+}
+
+/// Prints a summary block of processed packages, kept and deleted versions
+/// and freed space under `title`.
+pub fn print_totals(title: &str, packages: usize, kept: usize, deleted: usize, bytes: u64, options: &CleanOptions) {
+    println!("\n{}:", title);
+    println!("  Packages processed: {}", packages);
+    println!("  Versions kept: {}", kept);
+    let action = options.removal();
+    if options.dry_run {
+        println!("  Versions that would be {}: {}", action, deleted);
+    } else {
+        println!("  Versions {}: {}", action, deleted);
+    }
+    println!("{}", options.freed_line(bytes));
+}
+
+/// Prints the human summary of a [`CleanReport`], preceded by the
+/// per-package counts with `--summary-only`.
+pub fn print_clean_report(report: &CleanReport, options: &CleanOptions) {
+    if let Some(style) = options.tree {
+        print_tree(report, style);
+    }
+    if options.summary_only {
+        print_package_counts(&report.packages, options.verbose);
+    }
+    if let Some(template) = &options.summary_format {
+        println!("{}", template.render(report, options.dry_run));
+        return;
+    }
+
+    let title = match (&report.size_budget, options.delete_older_than) {
+        (Some(budget), _) => format!("Summary (size budget {} per {})", format_bytes(budget.limit),
+            if budget.scope == SizeScope::Global { "cache" } else { "package" }),
+        (None, Some(cutoff)) if options.policy == RetentionPolicy::Age => format!(
+            "Summary (keeping the latest and anything since {})",
+            humantime::format_rfc3339_seconds(cutoff)
+        ),
+        _ => format!("Summary (keeping latest {})", options.keep),
+    };
+    print_totals(&title, report.packages_processed, report.versions_kept, report.versions_deleted,
+        report.bytes_freed, options);
+    if options.free_space {
+        print_free_space(report, options.dry_run);
+    }
+    if options.dedupe.is_some() {
+        if options.dry_run {
+            println!("  Duplicates that would be deleted: {} ({})", report.duplicates_deleted,
+                format_bytes(report.bytes_freed_by_dedupe));
+        } else {
+            println!("  Duplicates deleted: {} ({})", report.duplicates_deleted,
+                format_bytes(report.bytes_freed_by_dedupe));
+        }
+    }
+    if report.versions_partially_deleted > 0 {
+        let freed: u64 = report.packages.iter()
+            .flat_map(|package| &package.partially_deleted)
+            .map(|version| version.size_bytes)
+            .sum();
+        println!("  Partially deleted (some files remain): {} ({} freed)", report.versions_partially_deleted,
+            format_bytes(freed));
+    }
+    if report.versions_failed > 0 {
+        let action = if options.dry_run { "Would fail to delete" } else { "Failed to delete" };
+        println!("  {}: {}", action, report.versions_failed);
+    }
+    if report.versions_permission_denied > 0 {
+        println!("  Not deletable (permission denied): {}", report.versions_permission_denied);
+    }
+    if options.pre_delete_hook.is_some() && !options.dry_run {
+        println!("  Skipped by hook: {}", report.versions_skipped_by_hook);
+    }
+    if options.temp_dirs.is_some() {
+        if options.dry_run {
+            println!("  Temp directories that would be removed: {} ({})", report.temp_dirs_removed,
+                format_bytes(report.bytes_freed_by_temp));
+        } else {
+            println!("  Temp directories removed: {} ({})", report.temp_dirs_removed,
+                format_bytes(report.bytes_freed_by_temp));
+        }
+    }
+    if !options.pins.is_empty() {
+        println!("  Pinned versions kept: {}", report.versions_pinned);
+    }
+    if options.prune_empty {
+        if options.dry_run {
+            println!("  Empty package directories that would be removed: {}", report.pruned_packages.len());
+        } else {
+            println!("  Empty package directories removed: {}", report.pruned_packages.len());
+        }
+    }
+    println!("  Packages with excess versions: {}", report.packages_with_excess_versions);
+    if let Some(largest) = &report.largest_package {
+        println!("  Largest package: {} ({} versions)", largest.name, largest.versions);
+    }
+    if let Some(budget) = &report.size_budget {
+        println!("  Total size: {}", format_bytes(budget.total));
+        println!("  Over budget by: {}", format_bytes(budget.over_budget));
+        println!("  Trimmed by size budget: {}", format_bytes(budget.trimmed));
+    }
+}
+
+/// Prints the free space measured around the run, which differs from the
+/// summed sizes when versions share hardlinks, are sparse or go to the trash.
+fn print_free_space(report: &CleanReport, dry_run: bool) {
+    let Some(before) = report.free_space_before else {
+        println!("  Free space: unknown (could not be read)");
+        return;
+    };
+    println!("  Free space before: {}", format_bytes(before));
+    if dry_run {
+        println!("  Estimated free space after: {}", format_bytes(before.saturating_add(report.bytes_freed)));
+        return;
+    }
+    match report.free_space_after {
+        // Other programs writing to the disk meanwhile can make it shrink
+        Some(after) if after >= before => {
+            println!("  Free space after: {} ({} gained on disk)", format_bytes(after), format_bytes(after - before));
+        }
+        Some(after) => {
+            println!("  Free space after: {} ({} less, other programs were writing)", format_bytes(after),
+                format_bytes(before - after));
+        }
+        None => println!("  Free space after: unknown (could not be read)"),
+    }
+}
+
+/// Prints a `name: before -> after (delete n)` line per package, sorted by
+/// name. Packages with nothing to delete are left out unless `verbose`.
+fn print_package_counts(packages: &[PackageReport], verbose: bool) {
+    let mut packages: Vec<&PackageReport> = packages
+        .iter()
+        .filter(|package| verbose || !package.deleted.is_empty())
+        .collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    println!();
+    for package in packages {
+        let kept = package.versions_kept;
+        let deleted = package.deleted.len();
+        println!("{}: {} -> {} (delete {})", package.name, kept + deleted, kept, deleted);
+    }
+}
+
+/// Key that identifies a package directory. Windows file systems are case
+/// insensitive, so names are compared ignoring case there.
+fn package_key(name: &str) -> String {
+    if cfg!(windows) {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Collects the package directories `depth` levels below `dir` into
+/// `package_dirs`, named by their path below the root joined with `/`
+/// (e.g. `zlib/x64` for a depth of 2). `prefix` is the name of `dir` itself,
+/// `None` for the root. Every level is matched against `filter`, so
+/// excluding `zlib` also skips `zlib/x64`.
+fn find_package_dirs(
+    dir: &Path,
+    prefix: Option<&str>,
+    depth: usize,
+    filter: &PackageFilter,
+    package_dirs: &mut Vec<(String, PathBuf)>,
+) -> Result<()> {
+    for entry in fs::read_dir(long_path(dir))
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+    {
+        let entry = entry?;
+        // Joined onto the original root so long-path prefixes stay out of output
+        let path = dir.join(entry.file_name());
+
+        if !long_path(&path).is_dir() {
+            continue;
+        }
+
+        let name = path.file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+
+        if name.is_empty() {
+            continue;
+        }
+
+        let package_name = match prefix {
+            Some(prefix) => format!("{}/{}", prefix, name),
+            None => name.to_string(),
+        };
+
+        if let Some(reason) = filter.skip_reason(&package_name) {
+            debug!("  Skipping package {} ({})", package_name, reason);
+            continue;
+        }
+
+        if depth > 1 {
+            find_package_dirs(&path, Some(&package_name), depth - 1, filter, package_dirs)?;
+        } else {
+            package_dirs.push((package_name, path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the version directories at the top of a flat cache root for
+/// `--group-by`, each paired with the package its name belongs to.
+/// Directories that don't match the pattern are left alone.
+fn scan_grouped(
+    cache_path: &Path,
+    group_by: &GroupBy,
+    filter: &PackageFilter,
+    options: &CleanOptions,
+) -> Result<Vec<(String, Result<Vec<PackageVersion>>)>> {
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(long_path(cache_path))
+        .with_context(|| format!("Failed to read directory: {}", cache_path.display()))?
+    {
+        let entry = entry?;
+        let path = cache_path.join(entry.file_name());
+
+        if !options.is_version_entry(&path) {
+            continue;
+        }
+
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        let Some((package_name, version_text)) = group_by.split(name) else {
+            debug!("  Skipping {} (doesn't match --group-by)", name);
+            continue;
+        };
+
+        if let Some(reason) = filter.skip_reason(package_name) {
+            debug!("  Skipping {} ({})", name, reason);
+            continue;
+        }
+
+        candidates.push((package_name.to_string(), version_text, name.to_string(), path.clone()));
+    }
+    // Directory listings come in no particular order
+    candidates.sort_by(|a, b| a.2.cmp(&b.2));
+
+    Ok(candidates
+        .into_par_iter()
+        .map(|(package_name, version_text, name, path)| {
+            let version = PackageVersion::new(name, path, options).map(|mut version| {
+                version.version = parse_version_name(&version_text, version.is_file);
+                vec![version]
+            });
+            (package_name, version)
+        })
+        .collect())
+}
+
+/// Removes the package directory `path` if it is empty, for `--prune-empty`.
+/// In a dry run it only counts as empty when everything left in it is among
+/// `deleted`, the versions the run would have removed. Returns whether the
+/// directory was (or would be) removed.
+fn prune_if_empty(path: &Path, deleted: &[PathBuf], options: &CleanOptions) -> bool {
+    // A symlinked package directory is left alone, like its target
+    if !fs::symlink_metadata(long_path(path)).is_ok_and(|metadata| metadata.is_dir()) {
+        return false;
+    }
+    let Ok(mut entries) = fs::read_dir(long_path(path)) else {
+        return false;
+    };
+    let empty = if options.dry_run {
+        entries.all(|entry| entry.is_ok_and(|entry| deleted.contains(&path.join(entry.file_name()))))
+    } else {
+        entries.next().is_none()
+    };
+    if !empty {
+        return false;
+    }
+
+    if options.dry_run {
+        info!("  Would remove empty package directory: {}", path.display());
+        return true;
+    }
+
+    // remove_dir, not remove_dir_all: anything created since still stops it
+    match fs::remove_dir(long_path(path)) {
+        Ok(()) => {
+            info!("  Removing empty package directory: {}", path.display());
+            true
+        }
+        Err(err) => {
+            warn!("could not remove empty package directory {}: {}", path.display(), err);
+            false
+        }
+    }
+}
+
+/// Collects the version directories of one package, and with
+/// `include_files` its version files.
+fn scan_package(path: &Path, options: &CleanOptions) -> Result<Vec<PackageVersion>> {
+    let mut versions = Vec::new();
+
+    for version_entry in fs::read_dir(long_path(path))
+        .with_context(|| format!("Failed to read package directory: {}", path.display()))?
+    {
+        let version_entry = version_entry
+            .with_context(|| format!("Failed to read package directory: {}", path.display()))?;
+        let version_path = path.join(version_entry.file_name());
+
+        if !options.is_version_entry(&version_path) {
+            continue;
+        }
+
+        let version_name = version_path.file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        if version_name.is_empty() {
+            continue;
+        }
+
+        versions.push(PackageVersion::new(version_name, version_path, options)?);
+    }
+
+    Ok(versions)
+}
+
+/// Sorts versions newest first: by parsed version number when the name is
+/// parseable, otherwise by modification time. Parseable names rank above
+/// unparseable ones so a stray folder can't push out a real version.
+///
+/// Versions that tie on both (e.g. unnumbered folders copied in bulk) are
+/// ordered by name, later names first, so the same one is kept on every run
+/// whatever order the directory listing comes in.
+fn sort_versions(versions: &mut [PackageVersion]) {
+    versions.sort_by(|a, b| {
+        b.version.is_some()
+            .cmp(&a.version.is_some())
+            .then_with(|| b.version.cmp(&a.version))
+            .then_with(|| b.timestamp.cmp(&a.timestamp))
+            .then_with(|| b.name.cmp(&a.name))
+    });
+}
+
+/// Whether only the name tie-break of [`sort_versions`] tells `a` and `b` apart.
+fn is_tied(a: &PackageVersion, b: &PackageVersion) -> bool {
+    a.version == b.version && a.timestamp == b.timestamp
+}
+
+/// Works out how many of the newest versions of each package fit within a
+/// size budget, caching every version's size on the way.
+///
+/// With [`SizeScope::Package`] each package gets the full budget. With
+/// [`SizeScope::Global`] all packages share it: every package's newest
+/// version is counted first, then every package's second newest, and so on.
+/// Versions are kept until the first one that would exceed the budget.
+///
+/// A keep count of 0 means the budget can't even fit the newest version.
+/// [`clean_package`] still keeps that one unless `allow_empty_packages` is
+/// set, and the trimmed total here accounts for it.
+fn apply_size_budget(
+    packages: &mut BTreeMap<String, Vec<PackageVersion>>,
+    limit: u64,
+    options: &CleanOptions,
+) -> (HashMap<String, usize>, SizeBudgetReport) {
+    let scope = options.size_scope;
+    let allow_empty = options.allow_empty_packages;
+    packages
+        .par_iter_mut()
+        .flat_map(|(_, versions)| versions.par_iter_mut())
+        .filter(|version| version.size.is_none())
+        .for_each(|version| version.size = Some(options.dir_size(&version.path).unwrap_or(0)));
+    let size_of = |version: &PackageVersion| version.size.unwrap_or(0);
+
+    let mut keeps = HashMap::new();
+    let mut report = SizeBudgetReport {
+        limit,
+        scope,
+        ..SizeBudgetReport::default()
+    };
+
+    match scope {
+        SizeScope::Package => {
+            for (name, versions) in packages.iter() {
+                let total: u64 = versions.iter().map(size_of).sum();
+                let mut used = 0;
+                let keep = versions
+                    .iter()
+                    .take_while(|version| {
+                        used += size_of(version);
+                        used <= limit
+                    })
+                    .count();
+                let kept: u64 = versions.iter().take(keep.max(usize::from(!allow_empty))).map(size_of).sum();
+
+                report.total += total;
+                report.over_budget += total.saturating_sub(limit);
+                report.trimmed += total - kept;
+                keeps.insert(name.clone(), keep);
+            }
+        }
+        SizeScope::Global => {
+            // Rank versions across packages: all newest versions first, then all
+            // second newest, ties broken by modification time
+            let mut ranked: Vec<(usize, &String, &PackageVersion)> = packages
+                .iter()
+                .flat_map(|(name, versions)| {
+                    versions.iter().enumerate().map(move |(rank, version)| (rank, name, version))
+                })
+                .collect();
+            ranked.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| b.2.timestamp.cmp(&a.2.timestamp)));
+
+            let total: u64 = ranked.iter().map(|(_, _, version)| size_of(version)).sum();
+            let mut used = 0;
+            for name in packages.keys() {
+                keeps.insert(name.clone(), 0);
+            }
+            for (_, name, version) in ranked {
+                used += size_of(version);
+                if used > limit {
+                    break;
+                }
+                *keeps.entry(name.clone()).or_insert(0) += 1;
+            }
+            let kept: u64 = packages
+                .iter()
+                .map(|(name, versions)| {
+                    let keep = keeps[name].max(usize::from(!allow_empty));
+                    versions.iter().take(keep).map(size_of).sum::<u64>()
+                })
+                .sum();
+
+            report.total = total;
+            report.over_budget = total.saturating_sub(limit);
+            report.trimmed = total - kept;
+        }
+    }
+
+    (keeps, report)
+}
+
+/// Applies the retention rule to a single package and deletes (or reports)
+/// the versions beyond the newest `keep`. `versions` must already be sorted
+/// newest first.
+///
+/// Output is collected into `lines` instead of printed directly so parallel
+/// workers don't interleave. With a `stream`, the lines gathered before the
+/// first deletion are passed to it, and then each deletion line as soon as
+/// that deletion is done, so long runs show progress as it happens. When
+/// `parallel` is set the deletions run on the current rayon pool and every
+/// error is collected; otherwise processing stops at the first error unless
+/// `keep_going` is set. A `prompter` asks before each deletion and is only
+/// used on the sequential path.
+fn clean_package(
+    package_name: &str,
+    mut versions: Vec<PackageVersion>,
+    keep: usize,
+    options: &CleanOptions,
+    parallel: bool,
+    prompter: Option<&mut Prompter>,
+    stream: Option<&Stream<'_>>,
+) -> PackageOutcome {
+    let mut outcome = PackageOutcome {
+        report: PackageReport {
+            name: package_name.to_string(),
+            ..PackageReport::default()
+        },
+        ..PackageOutcome::default()
+    };
+
+    if options.verbose {
+        outcome.debug(format!("\nPackage: {}", color::bold(package_name)));
+
+        // The footprint line below needs every size, so measure each version
+        // once up front; deletion and the JSON report reuse these sizes
+        for version in versions.iter_mut().filter(|version| version.size.is_none()) {
+            version.size = Some(match options.dir_size(&version.path) {
+                Ok(size) => size,
+                Err(err) => {
+                    outcome.debug(format!("  Could not measure {}: {:#}", version.path.display(), err));
+                    0
+                }
+            });
+        }
+
+        outcome.debug(format!("  Found {} versions:", versions.len()));
+        // Numbers are retention ranks (1 = newest) whichever way the list runs
+        let mut listing: Vec<(usize, &PackageVersion)> = versions.iter().enumerate().collect();
+        if options.list_order == ListOrder::Oldest {
+            listing.reverse();
+        }
+        let limit = options.verbose_limit.unwrap_or(usize::MAX);
+        let hidden = listing.len().saturating_sub(limit);
+        for (i, version) in listing.into_iter().take(limit) {
+            outcome.debug(format!("    {}: {} ({}: {})",
+                i + 1,
+                version.name,
+                options.time_basis.name(),
+                format_timestamp(version.timestamp, options.timestamps)
+            ));
+        }
+        if hidden > 0 {
+            outcome.debug(format!("    ... and {} more", hidden));
+        }
+    }
+
+    // Retention order of every version, for listing the keep set newest first
+    let ranks: Vec<PathBuf> = if options.show_kept || options.explain {
+        versions.iter().map(|version| version.path.clone()).collect()
+    } else {
+        Vec::new()
+    };
+
+    // Pinned versions are kept outright and leave the keep count to the rest
+    let (pinned, versions): (Vec<PackageVersion>, Vec<PackageVersion>) =
+        versions.into_iter().partition(|version| options.pins.is_match(&version.name));
+    for version in &pinned {
+        outcome.info(format!("  {} {} (pinned)", color::kept("Keeping:"), version.name));
+        outcome.record_kept(version, Reason::Pinned, options);
+        outcome.pinned += 1;
+    }
+
+    // Leftovers of interrupted installs aren't versions, so they go whatever the keep count
+    let (temp, versions): (Vec<PackageVersion>, Vec<PackageVersion>) =
+        versions.into_iter().partition(|version| options.is_temp(&version.name));
+    for version in &temp {
+        outcome.info(format!("  Temp: {} (matches a temp pattern)", version.name));
+    }
+
+    // A size budget too small for even the newest version keeps it anyway,
+    // so the package doesn't disappear entirely
+    let over_budget = keep == 0 && !options.allow_empty_packages;
+    let keep = if over_budget {
+        if let Some(newest) = versions.first() {
+            outcome.info(format!("  {} {} (kept newest despite size budget)", color::kept("Keeping:"), newest.name));
+        }
+        1
+    } else {
+        keep
+    };
+
+    // Copies of a newer version go first and don't count towards `keep`
+    let (distinct, duplicates) = match options.dedupe {
+        Some(mode) => find_duplicates(&versions, mode, &mut outcome),
+        None => (versions.iter().collect(), Vec::new()),
+    };
+
+    // Keep the latest `keep` versions, delete the rest
+    if options.verbose {
+        outcome.debug(format!("  Keeping latest {}", keep));
+    }
+    // Point it out when the last kept and first deleted versions only differ by name
+    if let (Some(last_kept), Some(first_deleted)) = (keep.checked_sub(1).and_then(|i| distinct.get(i)), distinct.get(keep)) {
+        if is_tied(last_kept, first_deleted) {
+            outcome.warn(format!(
+                "{}: {} and {} have the same version and {}, keeping {} because its name sorts later; \
+                 give versions numeric names or try another --time-basis to choose deliberately",
+                package_name,
+                last_kept.name,
+                first_deleted.name,
+                options.time_basis.name(),
+                last_kept.name
+            ));
+        }
+    }
+    let to_keep = distinct.iter().take(keep);
+    let mut to_delete: Vec<&PackageVersion> = temp.iter()
+        .chain(duplicates.iter().copied())
+        .chain(distinct.iter().skip(keep).copied())
+        .collect();
+    let is_duplicate = |version: &PackageVersion| duplicates.iter().any(|duplicate| duplicate.path == version.path);
+    let is_temp = |version: &PackageVersion| temp.iter().any(|temp| temp.path == version.path);
+    // What decided the versions within and beyond the keep count
+    let (kept_reason, deleted_reason) = if over_budget {
+        (Reason::NewestOverSizeBudget, Reason::OverSizeBudget)
+    } else if options.max_total_size.is_some() {
+        (Reason::WithinSizeBudget, Reason::OverSizeBudget)
+    } else if options.policy == RetentionPolicy::Age {
+        (Reason::Newest, Reason::OlderThanCutoff)
+    } else {
+        (Reason::WithinLatest(keep), Reason::BeyondLatest(keep))
+    };
+    let delete_reason = |version: &PackageVersion| {
+        if is_temp(version) {
+            Reason::Temp
+        } else if is_duplicate(version) {
+            Reason::Duplicate
+        } else {
+            deleted_reason
+        }
+    };
+
+    for version in to_keep {
+        if options.verbose {
+            outcome.debug(format!("  {} {}", color::kept("Keeping:"), version.name));
+        }
+        outcome.record_kept(version, kept_reason, options);
+    }
+
+    // Never delete anything younger than --min-age, even outside the keep count
+    if !options.min_age.is_zero() {
+        let now = SystemTime::now();
+        to_delete.retain(|version| {
+            let age = now.duration_since(version.timestamp).unwrap_or(Duration::ZERO);
+            if age >= options.min_age {
+                return true;
+            }
+
+            outcome.info(format!("  {} {} (too recent)", color::kept("Keeping:"), version.name));
+            outcome.record_kept(version, Reason::TooRecent, options);
+            false
+        });
+    }
+
+    // With --delete-older-than, only versions from before the cutoff go
+    if let Some(cutoff) = options.delete_older_than {
+        to_delete.retain(|version| {
+            if version.timestamp < cutoff || is_duplicate(version) || is_temp(version) {
+                return true;
+            }
+
+            outcome.info(format!("  {} {} (newer than --delete-older-than)", color::kept("Keeping:"), version.name));
+            outcome.record_kept(version, Reason::NewerThanCutoff, options);
+            false
+        });
+    }
+
+    // Leave versions alone while a build has them locked
+    to_delete.retain(|version| {
+        if !options.in_use(&version.path) {
+            return true;
+        }
+
+        outcome.lines.push((options.notable_level(), format!("  Skipped: {} (in use)", version.path.display())));
+        outcome.record_kept(version, Reason::InUse, options);
+        false
+    });
+
+    // Hooks run one at a time, before any parallel deletion starts
+    if !options.interactive {
+        to_delete.retain(|version| !outcome.vetoed_by_hook(version, options));
+    }
+
+    // However the rules above decided, the newest candidates are spared
+    // until the package keeps --min-versions; temp leftovers and duplicates
+    // aren't versions of their own and still go
+    let mut missing = options.min_versions.saturating_sub(outcome.kept);
+    to_delete.retain(|version| {
+        if missing == 0 || is_temp(version) || is_duplicate(version) {
+            return true;
+        }
+
+        missing -= 1;
+        outcome.info(format!("  {} {} (within --min-versions)", color::kept("Keeping:"), version.name));
+        outcome.record_kept(version, Reason::MinVersions(options.min_versions), options);
+        false
+    });
+
+    if options.explain {
+        let mut explained = std::mem::take(&mut outcome.reasons);
+        explained.extend(to_delete.iter().map(|version| (version.path.clone(), version.name.clone(), delete_reason(version))));
+        explained.sort_by_key(|(path, _, _)| ranks.iter().position(|ranked| ranked == path));
+        for (_, name, reason) in explained {
+            outcome.info(format!("  {} {} - {}", package_name, name, reason));
+        }
+    }
+
+    // Whether there is anything to delete is only known now, so hold back the lines until the end
+    let unaffected = options.affected_only && to_delete.is_empty();
+    if let Some(stream) = stream.filter(|_| !unaffected) {
+        stream(&outcome.lines);
+        outcome.lines.clear();
+    }
+    // An empty line has already been streamed
+    let streamed = |line: String| match stream {
+        Some(stream) => {
+            stream(&[(Level::Info, line)]);
+            String::new()
+        }
+        None => line,
+    };
+
+    let results: Vec<(&PackageVersion, String, Result<u64>)> = if parallel {
+        to_delete
+            .par_iter()
+            .filter(|_| !stop_requested())
+            .map(|version| {
+                let (line, result) = delete_version(version, options);
+                (*version, streamed(line), result)
+            })
+            .collect()
+    } else if let Some(prompter) = prompter {
+        let mut results = Vec::new();
+        for version in to_delete {
+            if stop_requested() {
+                break;
+            }
+
+            // Show everything gathered so far before asking about this version
+            emit_lines(&outcome.lines);
+            outcome.lines.clear();
+
+            let bytes = version.measured_size(options);
+            match prompter.confirm(version, bytes) {
+                Answer::Yes => {
+                    if outcome.vetoed_by_hook(version, options) {
+                        continue;
+                    }
+                    let (line, result) = remove_version(version, bytes, options);
+                    info!("{}", line);
+                    let failed = result.is_err();
+                    results.push((version, String::new(), result));
+                    if failed && !options.keep_going {
+                        break;
+                    }
+                }
+                Answer::No => {
+                    outcome.info(format!("  {} {} (declined)", color::kept("Keeping:"), version.name));
+                    outcome.record_kept(version, Reason::Declined, options);
+                }
+                Answer::Quit => {
+                    outcome.aborted = true;
+                    break;
+                }
+            }
+        }
+        results
+    } else {
+        let mut results = Vec::new();
+        for version in to_delete {
+            if stop_requested() {
+                break;
+            }
+
+            let (line, result) = delete_version(version, options);
+            let failed = result.is_err();
+            results.push((version, streamed(line), result));
+            if failed && !options.keep_going {
+                break;
+            }
+        }
+        results
+    };
+
+    for (version, line, result) in results {
+        if !line.is_empty() {
+            outcome.info(line);
+        }
+        match result {
+            Ok(bytes) => {
+                options.audit(version, bytes);
+                outcome.bytes += bytes;
+                outcome.deleted += 1;
+                if is_duplicate(version) {
+                    outcome.duplicates += 1;
+                    outcome.duplicate_bytes += bytes;
+                }
+                if is_temp(version) {
+                    outcome.temp_dirs += 1;
+                    outcome.temp_bytes += bytes;
+                }
+                let mut report = VersionReport::new(version, bytes);
+                report.reason = options.explain.then(|| delete_reason(version));
+                outcome.report.deleted.push(report);
+            }
+            Err(err) => {
+                if is_permission_denied(&err) {
+                    outcome.permission_denied += 1;
+                }
+                match err.downcast_ref::<PartiallyDeleted>() {
+                    Some(partial) => {
+                        outcome.bytes += partial.freed;
+                        outcome.report.partially_deleted.push(VersionReport::new(version, partial.freed));
+                    }
+                    None => outcome.failed += 1,
+                }
+                outcome.errors.push(err);
+            }
+        }
+    }
+
+    if options.show_kept {
+        let mut kept = std::mem::take(&mut outcome.kept_versions);
+        kept.sort_by_key(|(path, _)| ranks.iter().position(|ranked| ranked == path));
+        let names: Vec<String> = kept.into_iter().map(|(_, name)| name).collect();
+        let names = if names.is_empty() { "(none)".to_string() } else { names.join(", ") };
+        outcome.info(format!("  {} {}: {}", color::kept("Keep set for"), package_name, names));
+    }
+
+    if options.verbose {
+        let total: u64 = versions.iter()
+            .chain(&pinned)
+            .chain(&temp)
+            .map(|version| version.measured_size(options))
+            .sum();
+        outcome.debug(format!("  Total size: {}, freeing: {}, remaining: {}",
+            format_bytes(total),
+            format_bytes(outcome.bytes),
+            format_bytes(total.saturating_sub(outcome.bytes))
+        ));
+    }
+
+    if unaffected {
+        outcome.lines.retain(|(level, _)| *level <= Level::Warn);
+    }
+    outcome
+}
+
+/// Splits `versions` (sorted newest first) into distinct versions and
+/// duplicates, which have the same fingerprint as a newer version. A version
+/// that can't be fingerprinted, or is a symlink, always counts as distinct.
+fn find_duplicates<'a>(
+    versions: &'a [PackageVersion],
+    mode: DedupeMode,
+    outcome: &mut PackageOutcome,
+) -> (Vec<&'a PackageVersion>, Vec<&'a PackageVersion>) {
+    let mut distinct = Vec::new();
+    let mut duplicates = Vec::new();
+    if versions.len() < 2 {
+        distinct.extend(versions);
+        return (distinct, duplicates);
+    }
+
+    let mut seen: HashMap<[u8; 32], &str> = HashMap::new();
+    for version in versions {
+        if version.is_symlink {
+            distinct.push(version);
+            continue;
+        }
+        let hash = match fingerprint(&version.path, mode) {
+            Ok(hash) => hash,
+            Err(err) => {
+                outcome.debug(format!("  Could not fingerprint {}: {:#}", version.path.display(), err));
+                distinct.push(version);
+                continue;
+            }
+        };
+
+        match seen.get(&hash) {
+            Some(original) => {
+                outcome.info(format!("  Duplicate: {} (same as {})", version.name, original));
+                duplicates.push(version);
+            }
+            None => {
+                seen.insert(hash, &version.name);
+                distinct.push(version);
+            }
+        }
+    }
+
+    (distinct, duplicates)
+}
+
+/// Deletes one version directory, or only measures it in dry-run mode.
+/// Returns the line to print alongside the number of bytes reclaimed.
+fn delete_version(version: &PackageVersion, options: &CleanOptions) -> (String, Result<u64>) {
+    // Measure before deleting, the directory is gone afterwards
+    let size = match version.size {
+        Some(bytes) => Ok(bytes),
+        None => options.dir_size(&version.path),
+    };
+    match size {
+        Ok(bytes) => remove_version(version, bytes, options),
+        Err(err) => (format!("  Failed to measure: {}", version.path.display()), Err(err)),
+    }
+}
+
+/// Removes an already measured version directory (or reports it in dry-run).
+fn remove_version(version: &PackageVersion, bytes: u64, options: &CleanOptions) -> (String, Result<u64>) {
+    // A dry run fails where the real run would, so its summary sets realistic expectations
+    if options.dry_run {
+        // Staging only renames, which needs no permission inside the version
+        let recursive = !version.is_file
+            && !options.stage
+            && (!version.is_symlink || options.follow_symlinks || options.trash);
+        if let Some(denied) = denied_at(&version.path, recursive) {
+            let line = format!("  {} {}", color::would_delete("Would fail to delete:"), version.path.display());
+            let err = anyhow::Error::new(io::Error::from(io::ErrorKind::PermissionDenied)).context(format!(
+                "Cannot delete {}: no write permission on {}",
+                version.path.display(),
+                denied.display()
+            ));
+            return (line, Err(err));
+        }
+    }
+
+    if options.stage {
+        if options.dry_run {
+            return (format!("  {} {}", color::would_delete("Would stage:"), version.path.display()), Ok(bytes));
+        }
+
+        // A link is renamed itself, like a directory or file
+        let line = format!("  {} {}", color::deleted("Staging:"), version.path.display());
+        let result = stage(&version.path)
+            .with_context(|| format!("Failed to stage for deletion: {}", version.path.display()))
+            .map(|_| bytes);
+        return (line, result);
+    }
+
+    if options.trash {
+        if options.dry_run {
+            return (format!("  {} {}", color::would_delete("Would move to trash:"), version.path.display()), Ok(bytes));
+        }
+
+        let line = format!("  {} {}", color::deleted("Moving to trash:"), version.path.display());
+        let result = trash::delete(&version.path)
+            .with_context(|| {
+                format!(
+                    "Failed to move directory to trash (is a recycle bin supported on this platform?): {}",
+                    version.path.display()
+                )
+            })
+            .map(|_| bytes);
+        return (line, result);
+    }
+
+    if version.is_symlink && !options.follow_symlinks {
+        if options.dry_run {
+            return (format!("  {} {}", color::would_delete("Would remove symlink:"), version.path.display()), Ok(bytes));
+        }
+
+        // Only the link goes away, never the directory it points to. Directory
+        // links on Windows need remove_dir, everything else remove_file.
+        let line = format!("  {} {}", color::deleted("Removing symlink:"), version.path.display());
+        let link = long_path(&version.path);
+        let result = fs::remove_file(&link)
+            .or_else(|_| fs::remove_dir(&link))
+            .with_context(|| format!("Failed to remove symlink: {}", version.path.display()))
+            .map(|_| bytes);
+        return (line, result);
+    }
+
+    if options.dry_run {
+        return (format!("  {} {}", color::would_delete("Would delete:"), version.path.display()), Ok(bytes));
+    }
+
+    if version.is_file {
+        // A symlinked file is only unlinked, even with --follow-symlinks
+        let line = format!("  {} {}", color::deleted("Deleting:"), version.path.display());
+        let result = with_retries(&version.path, options, || fs::remove_file(long_path(&version.path)))
+            .with_context(|| format!("Failed to delete file: {}", version.path.display()))
+            .map(|_| bytes);
+        return (line, result);
+    }
+
+    if version.is_symlink {
+        // --follow-symlinks: delete the target's contents, then the link itself
+        let line = format!("  {} {}", color::deleted("Deleting symlink target:"), version.path.display());
+        let link = long_path(&version.path);
+        let result = fs::canonicalize(&link)
+            .and_then(|target| with_retries(&version.path, options, || fs::remove_dir_all(&target)))
+            .and_then(|_| fs::remove_file(&link).or_else(|_| fs::remove_dir(&link)))
+            .with_context(|| format!("Failed to delete symlinked directory: {}", version.path.display()))
+            .map(|_| bytes);
+        return (line, result);
+    }
+
+    let line = format!("  {} {}", color::deleted("Deleting:"), version.path.display());
+    let result = with_retries(&version.path, options, || (options.deleter)(&long_path(&version.path)))
+        .with_context(|| format!("Failed to delete directory: {}", version.path.display()))
+        .map(|_| bytes)
+        .map_err(|err| mark_partial(err, version, bytes));
+    (line, result)
+}
+
+/// Context on a deletion error when the directory was partly removed before
+/// the failure, e.g. because one file in it was locked. Find it with
+/// `downcast_ref`.
+#[derive(Debug)]
+struct PartiallyDeleted {
+    freed: u64,
+}
+
+impl fmt::Display for PartiallyDeleted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "partially deleted, {} freed before the failure", format_bytes(self.freed))
+    }
+}
+
+/// Adds [`PartiallyDeleted`] to the error of a failed deletion of `version`
+/// (`bytes` in size) if measuring what is left shows some of it is gone.
+fn mark_partial(err: anyhow::Error, version: &PackageVersion, bytes: u64) -> anyhow::Error {
+    // Measured directly, as the size cache can't see files removed deep down
+    let remaining = if long_path(&version.path).exists() {
+        dir_size(&version.path).unwrap_or(bytes)
+    } else {
+        0
+    };
+    match bytes.saturating_sub(remaining) {
+        0 => err,
+        freed => err.context(PartiallyDeleted { freed }),
+    }
+}
+
+/// Runs `delete` for `path`, trying again up to `options.retries` more times
+/// with an exponential backoff (100 ms, 200 ms, ... up to 6.4 s) while it fails with an error
+/// that looks transient, see [`is_transient`].
+fn with_retries(path: &Path, options: &CleanOptions, mut delete: impl FnMut() -> io::Result<()>) -> io::Result<()> {
+    // Retries are only worth a warning when nothing else is printed
+    let level = if options.report_only_errors { Level::Warn } else { Level::Debug };
+    let mut attempt = 0;
+    loop {
+        match delete() {
+            Err(err) if attempt < options.retries && is_transient(&err) && !stop_requested() => {
+                let delay = Duration::from_millis(100 << attempt.min(6));
+                log::log!(level, "  Retrying {} in {} ms ({})", path.display(), delay.as_millis(), err);
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether a failed deletion is worth retrying: a file that is briefly busy
+/// or locked, e.g. by antivirus or the search indexer on Windows, or a
+/// directory that something was still writing into. Plain permission errors
+/// elsewhere won't go away by waiting.
+fn is_transient(err: &io::Error) -> bool {
+    // ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION,
+    // which is how Windows reports a file held open by another process
+    #[cfg(windows)]
+    if matches!(err.raw_os_error(), Some(5 | 32 | 33)) {
+        return true;
+    }
+
+    matches!(
+        err.kind(),
+        io::ErrorKind::ResourceBusy
+            | io::ErrorKind::DirectoryNotEmpty
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+    )
+}
+
+/// Deletes exactly the directories listed in a plan file written by
+/// `--plan-out`, without re-scanning the cache. Every entry must still be a
+/// directory inside one of `roots`; anything else is skipped or refused.
+pub fn apply_plan(plan_file: &Path, roots: &[PathBuf], options: &CleanOptions) -> Result<PlanReport> {
+    let human = options.format == OutputFormat::Human && !options.report_only_errors;
+    let mut report = PlanReport {
+        plan_file: plan_file.to_path_buf(),
+        ..PlanReport::default()
+    };
+
+    info!("Applying plan from: {}", plan_file.display());
+
+    for path in plan::read_plan(plan_file)? {
+        if stop_requested() {
+            info!("  Interrupted, no further versions will be deleted");
+            break;
+        }
+
+        if let Err(reason) = plan::check_within_roots(&path, roots, options.group_by.is_some()) {
+            let message = format!("Refusing to delete {} ({})", path.display(), reason);
+            warn!("{}", message);
+            report.errors.push(message);
+            report.refused.push(path);
+            continue;
+        }
+
+        if !options.is_version_entry(&path) {
+            info!("  Skipping {} (no longer a directory)", path.display());
+            report.skipped.push(path);
+            continue;
+        }
+
+        if options.in_use(&path) {
+            log::log!(options.notable_level(), "  Skipping {} (in use)", path.display());
+            report.skipped.push(path);
+            continue;
+        }
+
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if options.pins.is_match(&name) {
+            info!("  Skipping {} (pinned)", path.display());
+            report.skipped.push(path);
+            continue;
+        }
+        if let Some(reason) = options.hook_veto(&path) {
+            info!("  Skipping {} (skipped by hook, {})", path.display(), reason);
+            report.skipped.push(path);
+            continue;
+        }
+        let version = PackageVersion::new(name, path, options)?;
+        let (line, result) = delete_version(&version, options);
+        info!("{}", line);
+
+        match result {
+            Ok(bytes) => {
+                options.audit(&version, bytes);
+                report.bytes_freed += bytes;
+                report.deleted.push(VersionReport::new(&version, bytes));
+            }
+            Err(err) if options.keep_going => {
+                if let Some(partial) = err.downcast_ref::<PartiallyDeleted>() {
+                    report.bytes_freed += partial.freed;
+                    report.partially_deleted.push(VersionReport::new(&version, partial.freed));
+                }
+                report.errors.push(format!("{:#}", err));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    if human {
+        let action = options.removal();
+        println!("\nPlan summary:");
+        if options.dry_run {
+            println!("  Versions that would be {}: {}", action, report.deleted.len());
+        } else {
+            println!("  Versions {}: {}", action, report.deleted.len());
+        }
+        println!("{}", options.freed_line(report.bytes_freed));
+        if !report.partially_deleted.is_empty() {
+            println!("  Partially deleted (some files remain): {}", report.partially_deleted.len());
+        }
+        println!("  Skipped (missing, in use, pinned or by hook): {}", report.skipped.len());
+        println!("  Refused (outside cache roots): {}", report.refused.len());
+    }
+
+    Ok(report)
+}
+
+/// Answer to an interactive `Delete? [y/N/a/q]` prompt.
+enum Answer {
+    Yes,
+    No,
+    Quit,
+}
+
+/// Asks the user before each deletion in `--interactive` mode.
+struct Prompter {
+    /// Set once the user answers `a`; every later version is deleted unasked.
+    delete_all: bool,
+    /// Prompts are only shown on a terminal; piped stdin always means "no".
+    enabled: bool,
+}
+
+impl Prompter {
+    fn new() -> Self {
+        Prompter {
+            delete_all: false,
+            enabled: io::stdin().is_terminal(),
+        }
+    }
+
+    fn confirm(&mut self, version: &PackageVersion, bytes: u64) -> Answer {
+        if self.delete_all {
+            return Answer::Yes;
+        }
+        if !self.enabled {
+            return Answer::No;
+        }
+
+        print!("  {} ({}) Delete? [y/N/a/q] ", version.path.display(), format_bytes(bytes));
+        let _ = io::stdout().flush();
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            // EOF counts as "no"
+            println!();
+            return Answer::No;
+        }
+
+        match input.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Answer::Yes,
+            "a" | "all" => {
+                self.delete_all = true;
+                Answer::Yes
+            }
+            "q" | "quit" => Answer::Quit,
+            _ => Answer::No,
+        }
+    }
+}
+
+/// Creates the per-package progress bar, or `None` when it would only get in
+/// the way: stdout isn't a terminal, JSON output, or interactive prompts.
+fn progress_bar(packages: usize, options: &CleanOptions) -> Option<ProgressBar> {
+    if options.format != OutputFormat::Human
+        || options.interactive
+        || options.quiet
+        || options.summary_only
+        || options.silent
+        || !io::stdout().is_terminal()
+    {
+        return None;
+    }
+
+    let bar = ProgressBar::with_draw_target(Some(packages as u64), ProgressDrawTarget::stdout());
+    let style = ProgressStyle::with_template("{bar:40.cyan/blue} {pos}/{len} packages  {wide_msg}")
+        .unwrap_or_else(|_| ProgressStyle::default_bar());
+    bar.set_style(style);
+    Some(bar)
+}
+
+/// Logs a block of buffered lines while holding a lock, so output from
+/// parallel workers never interleaves.
+fn emit_lines(lines: &[(Level, String)]) {
+    static OUTPUT_LOCK: Mutex<()> = Mutex::new(());
+
+    let _guard = OUTPUT_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (level, line) in lines {
+        log::log!(*level, "{}", line);
+    }
+    log::logger().flush();
+}
+
+/// Where [`clean_package`] sends output as it happens, see there.
+type Stream<'a> = dyn Fn(&[(Level, String)]) + Sync + 'a;
+
+/// [`parse_version`] for a version's name, where a version file's name may
+/// also be numbered without its extension, like `1.0.zip`.
+fn parse_version_name(name: &str, is_file: bool) -> Option<Vec<u64>> {
+    let stem = Path::new(name).file_stem().and_then(|stem| stem.to_str()).filter(|_| is_file);
+    parse_version(name).or_else(|| stem.and_then(parse_version))
+}
+
+/// Parses a dotted numeric version like `14.38.33130` (optionally prefixed
+/// with `v`) into its components. Returns `None` if any component isn't a number.
+fn parse_version(name: &str) -> Option<Vec<u64>> {
+    let trimmed = name.strip_prefix(['v', 'V']).unwrap_or(name);
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    trimmed
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect()
+}
+
+/// What happened while cleaning a single package.
+#[derive(Default)]
+struct PackageOutcome {
+    /// Buffered log lines, emitted in one block once the package is done.
+    lines: Vec<(Level, String)>,
+    kept: usize,
+    deleted: usize,
+    bytes: u64,
+    /// How many of `deleted` and `bytes` were duplicates found by `--dedupe`.
+    duplicates: usize,
+    duplicate_bytes: u64,
+    /// How many of `kept` were pinned with `--pin`.
+    pinned: usize,
+    /// Versions whose deletion failed without removing anything; partly
+    /// deleted ones are in `report.partially_deleted`.
+    failed: usize,
+    /// Failed or partly deleted versions (or, in a dry run, ones that would
+    /// fail) for lack of permissions.
+    permission_denied: usize,
+    /// How many of `kept` the pre-delete hook kept from being deleted.
+    skipped_by_hook: usize,
+    /// How many of `deleted` and `bytes` were leftovers removed by `--clean-temp`.
+    temp_dirs: usize,
+    temp_bytes: u64,
+    errors: Vec<anyhow::Error>,
+    report: PackageReport,
+    /// Path and name of every kept version, with `show_kept`.
+    kept_versions: Vec<(PathBuf, String)>,
+    /// Path, name and reason of every kept version, with `explain`.
+    reasons: Vec<(PathBuf, String, Reason)>,
+    /// The user quit at an interactive prompt.
+    aborted: bool,
+}
+
+impl PackageOutcome {
+    fn debug(&mut self, line: String) {
+        self.lines.push((Level::Debug, line));
+    }
+
+    fn info(&mut self, line: String) {
+        self.lines.push((Level::Info, line));
+    }
+
+    fn warn(&mut self, line: String) {
+        self.lines.push((Level::Warn, line));
+    }
+
+    /// Runs the pre-delete hook for `version` and, if it vetoes the
+    /// deletion, records the version as kept. Returns whether it was vetoed.
+    fn vetoed_by_hook(&mut self, version: &PackageVersion, options: &CleanOptions) -> bool {
+        let Some(reason) = options.hook_veto(&version.path) else {
+            return false;
+        };
+
+        self.info(format!("  Skipped: {} (skipped by hook, {})", version.path.display(), reason));
+        self.record_kept(version, Reason::SkippedByHook, options);
+        self.skipped_by_hook += 1;
+        true
+    }
+
+    fn record_kept(&mut self, version: &PackageVersion, reason: Reason, options: &CleanOptions) {
+        self.kept += 1;
+        if options.show_kept {
+            self.kept_versions.push((version.path.clone(), version.name.clone()));
+        }
+        if options.explain {
+            self.reasons.push((version.path.clone(), version.name.clone(), reason));
+        }
+
+        // Sizes of kept versions are only needed for the JSON report, the tree and reasons
+        if options.format == OutputFormat::Json || options.tree.is_some() || options.explain {
+            let mut report = VersionReport::new(version, version.measured_size(options));
+            report.reason = options.explain.then_some(reason);
+            self.report.kept.push(report);
+        }
+    }
+}
+
+/// Result of `--apply-plan`.
+#[derive(Debug, Default, Serialize)]
+pub struct PlanReport {
+    pub plan_file: PathBuf,
+    pub deleted: Vec<VersionReport>,
+    /// Failed with `--keep-going` after removing part of the directory,
+    /// with the size that was freed.
+    pub partially_deleted: Vec<VersionReport>,
+    pub skipped: Vec<PathBuf>,
+    pub refused: Vec<PathBuf>,
+    pub bytes_freed: u64,
+    pub errors: Vec<String>,
+}
+
+/// Result of `--list` for one package cache root.
+#[derive(Debug, Serialize)]
+pub struct InventoryReport {
+    pub path: PathBuf,
+    /// Sorted by name, with versions in `--sort` order (newest first by default).
+    pub packages: Vec<InventoryPackage>,
+    pub total_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+/// One package in an [`InventoryReport`].
+#[derive(Debug, Serialize)]
+pub struct InventoryPackage {
+    pub name: String,
+    pub total_bytes: u64,
+    pub versions: Vec<InventoryVersion>,
+}
+
+/// One version directory in an [`InventoryReport`].
+#[derive(Debug, Serialize)]
+pub struct InventoryVersion {
+    pub name: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// The `--time-basis` timestamp, in RFC 3339.
+    pub timestamp: String,
+}
+
+/// Result of cleaning one package cache root. New fields may be added in
+/// minor releases.
+#[derive(Debug, Serialize)]
+#[non_exhaustive]
+pub struct CleanReport {
+    pub path: PathBuf,
+    pub packages: Vec<PackageReport>,
+    /// Every deleted (or, in a dry run, deletable) version directory.
+    pub deleted_paths: Vec<PathBuf>,
+    /// Package directories removed by `--prune-empty` because nothing was left in them.
+    pub pruned_packages: Vec<PathBuf>,
+    pub packages_processed: usize,
+    pub versions_kept: usize,
+    pub versions_deleted: usize,
+    pub bytes_freed: u64,
+    /// The part of `versions_deleted` and `bytes_freed` that `--dedupe` removed.
+    pub duplicates_deleted: usize,
+    pub bytes_freed_by_dedupe: u64,
+    /// The part of `versions_kept` kept because they match `--pin`.
+    pub versions_pinned: usize,
+    /// Versions whose deletion failed (with `--keep-going`) after removing
+    /// part of them. What they freed is included in `bytes_freed`.
+    pub versions_partially_deleted: usize,
+    /// Versions whose deletion failed without removing anything.
+    pub versions_failed: usize,
+    /// Versions among `versions_failed` and `versions_partially_deleted`
+    /// that failed for lack of permissions. In a dry run, the versions that
+    /// would fail this way, see [`crate::permission::denied_at`].
+    pub versions_permission_denied: usize,
+    /// The part of `versions_kept` whose deletion the `--pre-delete-hook` vetoed.
+    pub versions_skipped_by_hook: usize,
+    /// The user quit at an `--interactive` prompt, leaving later packages alone.
+    pub aborted: bool,
+    /// The part of `versions_deleted` and `bytes_freed` that `--clean-temp` removed.
+    pub temp_dirs_removed: usize,
+    pub bytes_freed_by_temp: u64,
+    /// Packages with more versions than they keep, before age, lock or
+    /// interactive exceptions.
+    pub packages_with_excess_versions: usize,
+    /// The package with the most versions.
+    pub largest_package: Option<LargestPackage>,
+    pub size_budget: Option<SizeBudgetReport>,
+    /// Free space on the root's volume before cleaning, with `free_space`,
+    /// unless it couldn't be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_space_before: Option<u64>,
+    /// The same after cleaning; never read in a dry run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_space_after: Option<u64>,
+    pub errors: Vec<String>,
+}
+
+/// Name and version count of the package with the most versions.
+#[derive(Debug, Serialize)]
+pub struct LargestPackage {
+    pub name: String,
+    pub versions: usize,
+}
+
+/// How a cache measured up against `--max-total-size`.
+#[derive(Debug, Default, Serialize)]
+pub struct SizeBudgetReport {
+    pub limit: u64,
+    pub scope: SizeScope,
+    /// Size of every version before cleaning.
+    pub total: u64,
+    /// How far the cache (or the sum over packages) exceeded the budget.
+    pub over_budget: u64,
+    /// Size of the versions selected for deletion by the budget.
+    pub trimmed: u64,
+}
+
+/// Kept and deleted versions of one package.
+#[derive(Debug, Default, Serialize)]
+pub struct PackageReport {
+    pub name: String,
+    pub versions_kept: usize,
+    /// Kept versions with their sizes, only filled in for the JSON format,
+    /// `tree` and `explain`.
+    pub kept: Vec<VersionReport>,
+    pub deleted: Vec<VersionReport>,
+    /// Versions whose deletion failed after removing part of them, with the
+    /// size that was freed.
+    pub partially_deleted: Vec<VersionReport>,
+}
+
+/// A single version directory and its size.
+#[derive(Debug, Serialize)]
+pub struct VersionReport {
+    pub name: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// Why it is kept or deleted, only filled in with `explain`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<Reason>,
+}
+
+impl VersionReport {
+    fn new(version: &PackageVersion, size_bytes: u64) -> Self {
+        VersionReport {
+            name: version.name.clone(),
+            path: version.path.clone(),
+            size_bytes,
+            reason: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PackageVersion {
+    name: String,
+    version: Option<Vec<u64>>,
+    path: PathBuf,
+    /// The `--time-basis` timestamp.
+    timestamp: SystemTime,
+    /// Size in bytes, once it has been measured.
+    size: Option<u64>,
+    /// The version directory is a symlink to a directory elsewhere.
+    is_symlink: bool,
+    /// A version file from `include_files` rather than a directory.
+    is_file: bool,
+}
+
+impl PackageVersion {
+    fn new(name: String, path: PathBuf, options: &CleanOptions) -> Result<Self> {
+        let is_symlink = fs::symlink_metadata(long_path(&path))
+            .with_context(|| format!("Failed to get metadata for: {}", path.display()))?
+            .file_type()
+            .is_symlink();
+
+        // Get the timestamp used for sorting and the age guard
+        let metadata = fs::metadata(long_path(&path))
+            .with_context(|| format!("Failed to get metadata for: {}", path.display()))?;
+
+        let mut timestamp = match options.time_basis.read(&metadata) {
+            Ok(timestamp) => timestamp,
+            Err(err) if options.time_basis != TimeBasis::Mtime => {
+                static FALLBACK: Once = Once::new();
+                FALLBACK.call_once(|| {
+                    warn!("{} is not available on this system ({}), falling back to mtime",
+                        options.time_basis.name(), err);
+                });
+                metadata.modified()
+                    .with_context(|| format!("Failed to get modification time for: {}", path.display()))?
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to get modification time for: {}", path.display()));
+            }
+        };
+
+        // A file's size is just its length, a link's target is counted where
+        // it really lives, not here
+        let is_file = metadata.is_file();
+        let size = if is_symlink && !options.follow_symlinks {
+            Some(0)
+        } else {
+            is_file.then_some(metadata.len())
+        };
+
+        let mut version = parse_version_name(&name, is_file);
+        let sidecar = options.version_from.as_ref().filter(|_| !is_file);
+        if let Some(value) = sidecar.and_then(|source| read_field(&path, source)) {
+            if let Ok(date) = parse_date(&value) {
+                timestamp = date;
+            } else if let Some(parsed) = parse_version(&value) {
+                version = Some(parsed);
+            } else {
+                warn!("ignoring '{}' from the sidecar of {}: neither a version nor a date",
+                    value, path.display());
+            }
+        }
+
+        Ok(PackageVersion {
+            version,
+            name,
+            size,
+            path,
+            timestamp,
+            is_symlink,
+            is_file,
+        })
+    }
+
+    /// Cached size if already measured, otherwise measured now. Failures count as zero.
+    fn measured_size(&self, options: &CleanOptions) -> u64 {
+        self.size.unwrap_or_else(|| options.dir_size(&self.path).unwrap_or(0))
+    }
+}
@@ -0,0 +1,48 @@
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When per-version lines are colored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// Set once at startup by [`set_enabled`]; off by default so library users
+/// get plain text.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns coloring of the helpers below on or off for the whole process.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// A version that is kept, in green.
+pub fn kept(text: &str) -> String {
+    if enabled() { text.green().to_string() } else { text.to_string() }
+}
+
+/// Something that is being deleted, in red.
+pub fn deleted(text: &str) -> String {
+    if enabled() { text.red().to_string() } else { text.to_string() }
+}
+
+/// Something a dry run would delete, in yellow.
+pub fn would_delete(text: &str) -> String {
+    if enabled() { text.yellow().to_string() } else { text.to_string() }
+}
+
+/// A package name, in bold.
+pub fn bold(text: &str) -> String {
+    if enabled() { text.bold().to_string() } else { text.to_string() }
+}
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PRECEDENCE: &str = "command-line flags > CLEANPKGCACHE_* environment > config file > built-in defaults";
+
+/// Settings read from `config.toml`. Every field is optional; anything left
+/// out falls back to the command line or the built-in default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub path: Option<PathBuf>,
+    pub keep: Option<usize>,
+    pub dry_run: Option<bool>,
+    pub verbose: Option<bool>,
+    pub clean_roo_checkpoints: Option<bool>,
+    pub roo_max_age: Option<String>,
+    /// VS Code extensions whose Roo task folders are scanned, like
+    /// `--roo-extension-id`.
+    pub roo_extension_ids: Vec<String>,
+    /// Dry-run unless `--execute` is passed, like `CLEANPKGCACHE_SAFE=1`.
+    pub safe_mode: Option<bool>,
+    /// Keep counts for specific package directory names, overriding `keep`.
+    pub keep_overrides: HashMap<String, usize>,
+    /// Cache roots that may be cleaned even though they look like a system
+    /// folder or are too shallow.
+    pub allowed_roots: Vec<PathBuf>,
+}
+
+/// Default location of the config file: `~/.config/cleanpkgcache/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("cleanpkgcache").join("config.toml"))
+}
+
+/// Loads the config file.
+///
+/// An explicitly requested file must exist. The default file is optional and
+/// an empty config is returned when it is missing.
+pub fn load_config(explicit: Option<&Path>) -> Result<Config> {
+    let path = match explicit {
+        Some(path) => {
+            if !path.is_file() {
+                anyhow::bail!(
+                    "Config file not found: {} (settings are resolved as {})",
+                    path.display(),
+                    PRECEDENCE
+                );
+            }
+            path.to_path_buf()
+        }
+        None => match default_config_path() {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(Config::default()),
+        },
+    };
+
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let config: Config = toml::from_str(&contents).with_context(|| {
+        format!(
+            "Malformed config file: {} (settings are resolved as {})",
+            path.display(),
+            PRECEDENCE
+        )
+    })?;
+
+    if config.keep == Some(0) {
+        anyhow::bail!(
+            "Invalid config file {}: keep must be at least 1 (settings are resolved as {})",
+            path.display(),
+            PRECEDENCE
+        );
+    }
+
+    if let Some((package, _)) = config.keep_overrides.iter().find(|(_, keep)| **keep == 0) {
+        anyhow::bail!(
+            "Invalid config file {}: keep_overrides.{} must be at least 1 (settings are resolved as {})",
+            path.display(),
+            package,
+            PRECEDENCE
+        );
+    }
+
+    Ok(config)
+}
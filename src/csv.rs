@@ -0,0 +1,54 @@
+use crate::cleaner::CleanReport;
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+const HEADER: &str = "timestamp,mode,root,packages_processed,versions_kept,versions_deleted,bytes_freed";
+
+/// Appends one row per cache root to the `--csv-out` file, writing the
+/// header first when the file is new or empty. `mode` is `dry-run` or
+/// `delete`, so hypothetical rows can be filtered out in a spreadsheet.
+pub fn append_summary(csv_file: &Path, reports: &[CleanReport], dry_run: bool) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(csv_file)
+        .with_context(|| format!("Failed to open CSV file: {}", csv_file.display()))?;
+    let is_new = file.metadata().map_or(true, |metadata| metadata.len() == 0);
+
+    // CRLF line endings, as Excel expects
+    let mut contents = String::new();
+    if is_new {
+        contents.push_str(HEADER);
+        contents.push_str("\r\n");
+    }
+    let timestamp = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    let mode = if dry_run { "dry-run" } else { "delete" };
+    for report in reports {
+        contents.push_str(&format!(
+            "{},{},{},{},{},{},{}\r\n",
+            timestamp,
+            mode,
+            escape(&report.path.display().to_string()),
+            report.packages_processed,
+            report.versions_kept,
+            report.versions_deleted,
+            report.bytes_freed
+        ));
+    }
+
+    // One write per run, so concurrent runs don't interleave partial rows
+    file.write_all(contents.as_bytes())
+        .with_context(|| format!("Failed to write CSV file: {}", csv_file.display()))
+}
+
+/// Quotes a field containing a comma, quote or line break, doubling any quotes.
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,199 @@
+use crate::cleaner::InventoryReport;
+use crate::color;
+use crate::longpath::long_path;
+use crate::size::format_bytes;
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// How `--dedupe` decides that two version directories are the same build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DedupeMode {
+    /// Same relative file names and sizes. Cheap, only reads metadata.
+    Manifest,
+    /// Same relative file names and byte-for-byte contents.
+    Contents,
+}
+
+/// Hashes the tree under `path` so that identical version directories get
+/// the same fingerprint. Entries are visited in sorted order, and each is
+/// identified by its path relative to `path`, so the name of the version
+/// directory itself doesn't matter. Symlinks inside are hashed by their
+/// target, not followed. A version file is hashed on its own.
+pub fn fingerprint(path: &Path, mode: DedupeMode) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    let metadata = fs::symlink_metadata(long_path(path))
+        .with_context(|| format!("Failed to read metadata for: {}", path.display()))?;
+    if metadata.is_file() {
+        hasher.update(b"f");
+        hash_file(path, metadata.len(), mode, &mut hasher)?;
+    } else {
+        hash_dir(path, Path::new(""), mode, &mut hasher)?;
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn hash_dir(root: &Path, relative: &Path, mode: DedupeMode, hasher: &mut Sha256) -> Result<()> {
+    let dir = root.join(relative);
+    let mut entries: Vec<_> = fs::read_dir(long_path(&dir))
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<_>>()
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    entries.sort();
+
+    for name in entries {
+        let relative = relative.join(&name);
+        let path = root.join(&relative);
+        let metadata = fs::symlink_metadata(long_path(&path))
+            .with_context(|| format!("Failed to read metadata for: {}", path.display()))?;
+
+        // A type tag and separators keep e.g. "a" + "bc" apart from "ab" + "c"
+        let kind: &[u8] = if metadata.is_dir() {
+            b"d"
+        } else if metadata.is_symlink() {
+            b"l"
+        } else {
+            b"f"
+        };
+        hasher.update(kind);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+
+        if metadata.is_dir() {
+            hash_dir(root, &relative, mode, hasher)?;
+        } else if metadata.is_symlink() {
+            let target = fs::read_link(long_path(&path))
+                .with_context(|| format!("Failed to read symlink: {}", path.display()))?;
+            hasher.update(target.to_string_lossy().as_bytes());
+            hasher.update([0]);
+        } else {
+            hash_file(&path, metadata.len(), mode, hasher)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Hashes the length of the file at `path`, and its contents in
+/// [`DedupeMode::Contents`].
+fn hash_file(path: &Path, len: u64, mode: DedupeMode, hasher: &mut Sha256) -> Result<()> {
+    hasher.update(len.to_le_bytes());
+    if mode == DedupeMode::Contents {
+        let mut file = File::open(long_path(path))
+            .with_context(|| format!("Failed to open: {}", path.display()))?;
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)
+                .with_context(|| format!("Failed to read: {}", path.display()))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+    }
+    Ok(())
+}
+
+/// Result of `--find-duplicates` over every cache root.
+#[derive(Debug, Default, Serialize)]
+pub struct DuplicateReport {
+    /// Largest savings first.
+    pub groups: Vec<DuplicateGroup>,
+    /// What keeping one copy of each group would free.
+    pub reclaimable_bytes: u64,
+    /// Versions that couldn't be fingerprinted and were left out.
+    pub errors: Vec<String>,
+}
+
+/// Identical version directories, in at least two different packages.
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    /// Size of each copy.
+    pub size_bytes: u64,
+    /// `size_bytes` for every copy but one.
+    pub reclaimable_bytes: u64,
+    pub versions: Vec<DuplicateVersion>,
+}
+
+/// One copy in a [`DuplicateGroup`].
+#[derive(Debug, Serialize)]
+pub struct DuplicateVersion {
+    pub package: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Finds version directories that are identical across packages of the
+/// `inventories`, as `--find-duplicates` does. Only versions of the same
+/// non-zero size are fingerprinted, so most of the cache is never read.
+/// Nothing is deleted.
+pub fn find_duplicates(inventories: &[InventoryReport], mode: DedupeMode) -> DuplicateReport {
+    let mut by_size: HashMap<u64, Vec<DuplicateVersion>> = HashMap::new();
+    for inventory in inventories {
+        for package in &inventory.packages {
+            for version in package.versions.iter().filter(|version| version.size_bytes > 0) {
+                by_size.entry(version.size_bytes).or_default().push(DuplicateVersion {
+                    package: package.name.clone(),
+                    name: version.name.clone(),
+                    path: version.path.clone(),
+                });
+            }
+        }
+    }
+
+    let candidates: Vec<(u64, DuplicateVersion)> = by_size
+        .into_iter()
+        .filter(|(_, versions)| versions.len() > 1)
+        .flat_map(|(size, versions)| versions.into_iter().map(move |version| (size, version)))
+        .collect();
+    let hashed: Vec<_> = candidates
+        .into_par_iter()
+        .map(|(size, version)| (fingerprint(&version.path, mode), size, version))
+        .collect();
+
+    let mut report = DuplicateReport::default();
+    let mut by_hash: HashMap<([u8; 32], u64), Vec<DuplicateVersion>> = HashMap::new();
+    for (hash, size, version) in hashed {
+        match hash {
+            Ok(hash) => by_hash.entry((hash, size)).or_default().push(version),
+            Err(err) => report.errors.push(format!("{:#}", err)),
+        }
+    }
+
+    for ((_, size_bytes), mut versions) in by_hash {
+        // Copies within one package are what --dedupe is for
+        if versions.iter().all(|version| version.package == versions[0].package) {
+            continue;
+        }
+        versions.sort_by(|a, b| a.path.cmp(&b.path));
+        let reclaimable_bytes = size_bytes * (versions.len() as u64 - 1);
+        report.reclaimable_bytes += reclaimable_bytes;
+        report.groups.push(DuplicateGroup { size_bytes, reclaimable_bytes, versions });
+    }
+    report.groups.sort_by(|a, b| {
+        b.reclaimable_bytes.cmp(&a.reclaimable_bytes).then_with(|| a.versions[0].path.cmp(&b.versions[0].path))
+    });
+    report.errors.sort();
+    report
+}
+
+/// Prints a [`DuplicateReport`]: each group with its copies, then the totals.
+pub fn print_duplicates(report: &DuplicateReport) {
+    for group in &report.groups {
+        println!("\n{} copies of {} ({} reclaimable):", group.versions.len(), format_bytes(group.size_bytes),
+            format_bytes(group.reclaimable_bytes));
+        for version in &group.versions {
+            println!("  {}/{}  {}", color::bold(&version.package), version.name, version.path.display());
+        }
+    }
+
+    println!("\nDuplicates across packages:");
+    println!("  Groups: {}", report.groups.len());
+    println!("  Reclaimable: {}", format_bytes(report.reclaimable_bytes));
+}
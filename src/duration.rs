@@ -0,0 +1,142 @@
+use clap::ValueEnum;
+use std::time::{Duration, SystemTime};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+/// Months are treated as 30 days, matching the original "two months" default.
+const MONTH: u64 = 30 * DAY;
+const YEAR: u64 = 365 * DAY;
+
+/// Parses a human-friendly duration such as `30d`, `6w`, `3mo`, `12h` or `1y`.
+///
+/// Supported units: `s`, `m`/`min`, `h`, `d`, `w`, `mo`, `y`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("'{}' is missing a unit (expected e.g. 30d, 6w, 3mo)", value))?;
+    let (number, unit) = value.split_at(split);
+
+    if number.is_empty() {
+        return Err(format!("'{}' must start with a number (expected e.g. 30d, 6w, 3mo)", value));
+    }
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is too large", value))?;
+
+    let unit_seconds = match unit.trim() {
+        "s" | "sec" | "secs" => 1,
+        "m" | "min" | "mins" => MINUTE,
+        "h" | "hr" | "hrs" => HOUR,
+        "d" | "day" | "days" => DAY,
+        "w" | "week" | "weeks" => WEEK,
+        "mo" | "month" | "months" => MONTH,
+        "y" | "year" | "years" => YEAR,
+        other => {
+            return Err(format!(
+                "unknown duration unit '{}' in '{}' (use s, m, h, d, w, mo or y)",
+                other, value
+            ))
+        }
+    };
+
+    number
+        .checked_mul(unit_seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("'{}' is too large", value))
+}
+
+/// A point in time given either as an age (`90d`) or a date (`2024-01-31`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cutoff {
+    Age(Duration),
+    Date(SystemTime),
+}
+
+impl Cutoff {
+    /// The cutoff as a timestamp, with ages counted back from `now`.
+    pub fn resolve(self, now: SystemTime) -> SystemTime {
+        match self {
+            Cutoff::Age(age) => now.checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH),
+            Cutoff::Date(date) => date,
+        }
+    }
+}
+
+/// Parses a [`Cutoff`]: a duration as accepted by [`parse_duration`], or a
+/// UTC date (`2024-01-31`) or date and time (`2024-01-31T12:00:00`).
+pub fn parse_cutoff(value: &str) -> Result<Cutoff, String> {
+    let value = value.trim();
+    if looks_like_date(value) {
+        return parse_date(value).map(Cutoff::Date);
+    }
+    if let Ok(date) = humantime::parse_rfc3339_weak(value) {
+        return Ok(Cutoff::Date(date));
+    }
+
+    parse_duration(value)
+        .map(Cutoff::Age)
+        .map_err(|err| format!("{} (or give a date like 2024-01-31)", err))
+}
+
+/// Parses a UTC date (`2024-01-31`, meaning midnight) or an RFC 3339 date
+/// and time (`2024-01-31T12:00:00Z`).
+pub fn parse_date(value: &str) -> Result<SystemTime, String> {
+    let value = value.trim();
+    let date = if value.len() == 10 {
+        humantime::parse_rfc3339_weak(&format!("{} 00:00:00", value))
+    } else {
+        humantime::parse_rfc3339_weak(value)
+    };
+    date.map_err(|err| format!("invalid date '{}' (expected e.g. 2024-01-31): {}", value, err))
+}
+
+/// Whether `value` is shaped like a `YYYY-MM-DD` date rather than a duration.
+fn looks_like_date(value: &str) -> bool {
+    value.len() == 10 && value.as_bytes().get(4) == Some(&b'-')
+}
+
+/// Describes a duration in the largest whole unit, e.g. `60 days` or `12 hours`.
+pub fn describe_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (count, unit) = if seconds >= DAY && seconds.is_multiple_of(DAY) {
+        (seconds / DAY, "day")
+    } else if seconds >= HOUR && seconds.is_multiple_of(HOUR) {
+        (seconds / HOUR, "hour")
+    } else if seconds >= MINUTE && seconds.is_multiple_of(MINUTE) {
+        (seconds / MINUTE, "minute")
+    } else {
+        (seconds, "second")
+    };
+
+    if count == 1 {
+        format!("{} {}", count, unit)
+    } else {
+        format!("{} {}s", count, unit)
+    }
+}
+
+/// Time zone of the timestamps in human output, with `--timestamps`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimestampZone {
+    /// UTC, e.g. 2024-05-01T10:00:00Z
+    #[default]
+    Utc,
+    /// The system's time zone, e.g. 2024-05-01T12:00:00+02:00
+    Local,
+}
+
+/// Formats `time` as RFC 3339 to the second, in UTC or with the local
+/// offset. Falls back to UTC where the local time zone can't be found.
+pub fn format_timestamp(time: SystemTime, zone: TimestampZone) -> String {
+    let local = match zone {
+        TimestampZone::Utc => None,
+        TimestampZone::Local => jiff::Timestamp::try_from(time).ok(),
+    };
+    match local {
+        Some(timestamp) => timestamp.to_zoned(jiff::tz::TimeZone::system()).strftime("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        None => humantime::format_rfc3339_seconds(time).to_string(),
+    }
+}
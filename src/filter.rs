@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::Path;
+
+/// Name of the per-root file listing package patterns that are never cleaned.
+pub const IGNORE_FILE_NAME: &str = ".cleanpkgcacheignore";
+
+/// Decides which top-level package directories get cleaned, based on the
+/// `--include` and `--exclude` glob patterns.
+#[derive(Clone, Debug)]
+pub struct PackageFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    /// Patterns from a cache root's ignore file.
+    ignored: Option<GlobSet>,
+}
+
+/// Lets every package through.
+impl Default for PackageFilter {
+    fn default() -> Self {
+        PackageFilter {
+            include: None,
+            exclude: GlobSet::empty(),
+            ignored: None,
+        }
+    }
+}
+
+impl PackageFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(include)?)
+        };
+
+        Ok(PackageFilter {
+            include,
+            exclude: build_glob_set(exclude)?,
+            ignored: None,
+        })
+    }
+
+    /// Returns this filter extended with the patterns in `root`'s
+    /// [`IGNORE_FILE_NAME`], one glob per line with `#` comments. Without an
+    /// ignore file the filter is returned unchanged.
+    pub fn with_ignore_file(&self, root: &Path) -> Result<Self> {
+        let ignore_file = root.join(IGNORE_FILE_NAME);
+        if !ignore_file.is_file() {
+            return Ok(self.clone());
+        }
+
+        let contents = fs::read_to_string(&ignore_file)
+            .with_context(|| format!("Failed to read ignore file: {}", ignore_file.display()))?;
+        let patterns: Vec<String> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        let ignored = build_glob_set(&patterns)
+            .with_context(|| format!("Invalid ignore file: {}", ignore_file.display()))?;
+
+        Ok(PackageFilter {
+            ignored: Some(ignored),
+            ..self.clone()
+        })
+    }
+
+    /// Returns why `package_name` should be skipped, or `None` if it should
+    /// be cleaned. Excludes and the ignore file take precedence over includes.
+    pub fn skip_reason(&self, package_name: &str) -> Option<&'static str> {
+        if self.exclude.is_match(package_name) {
+            return Some("matches --exclude");
+        }
+
+        if self.ignored.as_ref().is_some_and(|ignored| ignored.is_match(package_name)) {
+            return Some("listed in .cleanpkgcacheignore");
+        }
+
+        match &self.include {
+            Some(include) if !include.is_match(package_name) => Some("does not match --include"),
+            _ => None,
+        }
+    }
+}
+
+/// Glob patterns matched against version directory names, for `--pin` and
+/// `--temp-pattern`.
+#[derive(Clone, Debug)]
+pub struct VersionGlobs {
+    globs: GlobSet,
+}
+
+/// Matches nothing.
+impl Default for VersionGlobs {
+    fn default() -> Self {
+        VersionGlobs { globs: GlobSet::empty() }
+    }
+}
+
+impl VersionGlobs {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        Ok(VersionGlobs {
+            globs: build_glob_set(patterns)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    /// Whether the version directory `version_name` matches one of the patterns.
+    pub fn is_match(&self, version_name: &str) -> bool {
+        self.globs.is_match(version_name)
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .with_context(|| format!("Invalid glob pattern: {}", pattern))?;
+        builder.add(glob);
+    }
+    builder.build().context("Failed to build glob set")
+}
@@ -0,0 +1,37 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+/// A compiled `--group-by` pattern, for caches that keep every version as a
+/// flat directory at the root, like `pkgA-1.0` and `pkgA-1.1`.
+///
+/// The capture group named `package`, or else the first capture group, is
+/// the package a directory belongs to. What is left of the name once the
+/// capture is cut out, minus separators at either end, is its version.
+#[derive(Clone, Debug)]
+pub struct GroupBy {
+    regex: Regex,
+}
+
+impl GroupBy {
+    pub fn new(pattern: &str) -> Result<GroupBy> {
+        let regex = Regex::new(pattern).with_context(|| format!("Invalid --group-by pattern: {}", pattern))?;
+        if regex.captures_len() < 2 {
+            anyhow::bail!("--group-by pattern {} needs a capture group for the package name", pattern);
+        }
+        Ok(GroupBy { regex })
+    }
+
+    /// Splits a directory name into its package and version, or `None` when
+    /// the name doesn't match or the package capture is empty.
+    pub fn split<'a>(&self, name: &'a str) -> Option<(&'a str, String)> {
+        let captures = self.regex.captures(name)?;
+        let package = captures.name("package").or_else(|| captures.get(1))?;
+        if package.is_empty() {
+            return None;
+        }
+
+        let separators: &[char] = &['-', '_', '.', ' ', '@'];
+        let rest = format!("{}{}", &name[..package.start()], &name[package.end()..]);
+        Some((package.as_str(), rest.trim_matches(separators).to_string()))
+    }
+}
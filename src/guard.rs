@@ -0,0 +1,133 @@
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Cache roots need at least this many named components, e.g.
+/// `C:\PkgCache\VC17LTCG` or `/var/cache/pkgs`.
+pub const MIN_ROOT_DEPTH: usize = 2;
+
+/// Folders that hold system or user data rather than a package cache, on
+/// top of anything shallower than [`MIN_ROOT_DEPTH`].
+const SYSTEM_DIRS: &[&str] = &[
+    r"C:\Program Files",
+    r"C:\Program Files (x86)",
+    r"C:\ProgramData",
+    r"C:\Users",
+    r"C:\Windows",
+    "/Applications",
+    "/Library",
+    "/System",
+    "/Users",
+    "/etc",
+    "/home",
+    "/opt",
+    "/usr",
+    "/usr/bin",
+    "/usr/lib",
+    "/usr/local",
+    "/usr/share",
+    "/var",
+    "/var/lib",
+];
+
+/// Checks that `path` looks like a package cache rather than a drive root,
+/// a system folder or the user's home, where treating every folder as a
+/// package would be catastrophic. Paths in `allowed` (from `allowed_roots`
+/// in the config file) always pass. Returns why the path was refused.
+///
+/// Paths that can't be resolved, e.g. because they don't exist, pass here
+/// and are reported by the caller.
+pub fn check_cache_root(path: &Path, allowed: &[PathBuf]) -> Result<(), String> {
+    let Ok(resolved) = fs::canonicalize(path) else {
+        return Ok(());
+    };
+    if allowed.iter().any(|allowed| same_path(allowed, &resolved)) {
+        return Ok(());
+    }
+
+    let depth = resolved
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .count();
+    if depth < MIN_ROOT_DEPTH {
+        return Err(format!("it is fewer than {} folders below the file system root", MIN_ROOT_DEPTH));
+    }
+
+    let system_dir = SYSTEM_DIRS
+        .iter()
+        .any(|dir| same_path(Path::new(dir), &resolved));
+    if system_dir {
+        return Err("it is a system folder".to_string());
+    }
+
+    // The home folder and anything above it, like C:\Users\name
+    let home = dirs::home_dir();
+    if home.is_some_and(|home| relative_to(&home, &resolved).is_some()) {
+        return Err("it contains your home folder".to_string());
+    }
+
+    Ok(())
+}
+
+/// Resolves `path` for safety comparisons. The part that exists goes
+/// through `fs::canonicalize`, resolving symlinks, `.` and `..`; the rest is
+/// normalized lexically. A trailing slash, `a/b/../c` and a relative path
+/// all end up in the same form as the canonical `a/c`.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    let absolute = match env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    };
+
+    // Canonicalize the deepest ancestor that exists and replay the rest on top
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(resolved) = fs::canonicalize(existing) {
+            return rest.into_iter().rev().fold(resolved, push_component);
+        }
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(last)) => {
+                rest.push(last);
+                existing = parent;
+            }
+            _ => return absolute.components().fold(PathBuf::new(), push_component),
+        }
+    }
+}
+
+fn push_component(mut path: PathBuf, component: Component) -> PathBuf {
+    match component {
+        Component::CurDir => {}
+        Component::ParentDir => {
+            path.pop();
+        }
+        component => path.push(component),
+    }
+    path
+}
+
+/// Whether `a` and `b` are the same location once canonicalized, ignoring
+/// case on Windows.
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    comparable(&canonical_path(a)) == comparable(&canonical_path(b))
+}
+
+/// Where `path` is inside `root`, relative to it, comparing canonicalized
+/// paths (ignoring case on Windows). `Some("")` when it is `root` itself and
+/// `None` when it is outside, even if the path text starts with `root`.
+pub fn relative_to(path: &Path, root: &Path) -> Option<PathBuf> {
+    let path = comparable(&canonical_path(path));
+    let root = comparable(&canonical_path(root));
+    path.strip_prefix(&root).ok().map(Path::to_path_buf)
+}
+
+/// `path` in the form two paths are compared in: lowercase on Windows,
+/// where file names are case-insensitive.
+fn comparable(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+    } else {
+        path.to_path_buf()
+    }
+}
@@ -0,0 +1,63 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Environment variable holding the version path for `--pre-delete-hook`,
+/// besides the path being passed as the last argument.
+pub const HOOK_PATH_VAR: &str = "CLEANPKGCACHE_VERSION_PATH";
+
+/// Runs the `--pre-delete-hook` command for a version about to be deleted,
+/// through `sh -c` (`cmd /C` on Windows) with the version path appended as
+/// the last argument and in [`HOOK_PATH_VAR`]. The hook's output goes to
+/// stderr, so it stays out of summaries and JSON on stdout.
+///
+/// Returns why the deletion should be skipped: a non-zero exit, or a hook
+/// that couldn't be started at all.
+pub fn run_pre_delete_hook(command: &str, path: &Path) -> Result<(), String> {
+    let mut hook = shell_command(command, Some(path));
+    hook.env(HOOK_PATH_VAR, path);
+    run(hook)
+}
+
+/// Runs the `--post-run-hook` command once a run is done, the same way as
+/// [`run_pre_delete_hook`] but without an argument, with `env` describing
+/// the run. Returns why the hook failed.
+pub fn run_post_run_hook(command: &str, env: &[(&str, String)]) -> Result<(), String> {
+    let mut hook = shell_command(command, None);
+    hook.envs(env.iter().map(|(name, value)| (name, value)));
+    run(hook)
+}
+
+fn run(mut hook: Command) -> Result<(), String> {
+    hook.stdin(Stdio::null()).stdout(io::stderr());
+    match hook.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(match status.code() {
+            Some(code) => format!("exit code {}", code),
+            None => "killed by a signal".to_string(),
+        }),
+        Err(err) => Err(format!("could not be started: {}", err)),
+    }
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str, path: Option<&Path>) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c");
+    match path {
+        // "$1" keeps paths with spaces as one argument; $0 names the script in errors
+        Some(path) => shell.arg(format!("{} \"$1\"", command)).arg("cleanpkgcache-hook").arg(path),
+        None => shell.arg(command),
+    };
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str, path: Option<&Path>) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    if let Some(path) = path {
+        shell.arg(path);
+    }
+    shell
+}
@@ -0,0 +1,54 @@
+//! Cleans package cache directories by keeping only the latest versions of
+//! each package, and optionally removes stale Roo Code task checkpoints.
+//!
+//! Embedding tools should start from [`Cleaner`], configured through
+//! [`CleanerOptions::builder`]; the lower-level functions in [`cleaner`] and
+//! [`roo`] stay available for finer control.
+//!
+//! The `cleanpkgcache` binary is a thin command-line front end over the same
+//! options, [`cleaner::clean_package_cache`], [`cleaner::apply_plan`] and
+//! [`roo::clean_roo_checkpoints`].
+
+pub mod api;
+pub mod audit;
+pub mod cleaner;
+pub mod color;
+pub mod config;
+pub mod csv;
+pub mod dedupe;
+pub mod duration;
+pub mod filter;
+pub mod group;
+pub mod guard;
+pub mod hook;
+pub mod longpath;
+pub mod metrics;
+pub mod permission;
+pub mod plan;
+pub mod roo;
+pub mod sidecar;
+pub mod size;
+pub mod sizecache;
+pub mod stage;
+pub mod stats;
+pub mod template;
+pub mod tree;
+pub mod volume;
+
+pub use api::{Cleaner, CleanerOptions, CleanerOptionsBuilder};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by [`request_stop`]. Deletions already running finish, no new ones start.
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks running cleanups to stop before their next deletion, e.g. from a
+/// Ctrl-C handler. Returns whether a stop had already been requested.
+pub fn request_stop() -> bool {
+    STOP_REQUESTED.swap(true, Ordering::SeqCst)
+}
+
+/// Whether [`request_stop`] has been called.
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::SeqCst)
+}
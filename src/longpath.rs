@@ -0,0 +1,90 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Returns the form of `path` to hand to file system calls.
+///
+/// On Windows, absolute paths get the extended-length `\\?\` prefix so deep
+/// package trees past the 260 character `MAX_PATH` limit can still be read
+/// and deleted: `C:\PkgCache\x` becomes `\\?\C:\PkgCache\x` and the UNC path
+/// `\\server\share\x` becomes `\\?\UNC\server\share\x`. Relative paths and
+/// paths that already carry a `\\?\` or `\\.\` prefix are left alone.
+/// Everywhere else the path is returned unchanged.
+///
+/// Only use the result for the call itself and keep displaying the original
+/// path, so the prefix never shows up in output.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Component, PathBuf, Prefix};
+
+    if !path.is_absolute() {
+        return Cow::Borrowed(path);
+    }
+    // absolute() normalizes separators and `.`/`..`, which the verbatim
+    // form doesn't allow
+    let Ok(absolute) = std::path::absolute(path) else {
+        return Cow::Borrowed(path);
+    };
+
+    let mut extended = OsString::new();
+    match absolute.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                extended.push(r"\\?\");
+                extended.push(absolute.as_os_str());
+            }
+            Prefix::UNC(_, _) => {
+                let without_slashes: Vec<u16> = absolute.as_os_str().encode_wide().skip(2).collect();
+                extended.push(r"\\?\UNC\");
+                extended.push(OsString::from_wide(&without_slashes));
+            }
+            _ => return Cow::Borrowed(path),
+        },
+        _ => return Cow::Borrowed(path),
+    }
+
+    Cow::Owned(PathBuf::from(extended))
+}
+
+/// Returns the form of `path` to hand to file system calls. Only Windows
+/// needs a different form, see the Windows version of this function.
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::long_path;
+    use std::path::Path;
+
+    fn extended(path: &str) -> String {
+        long_path(Path::new(path)).display().to_string()
+    }
+
+    #[test]
+    fn disk_paths_get_the_extended_length_prefix() {
+        assert_eq!(extended(r"C:\PkgCache\VC17LTCG"), r"\\?\C:\PkgCache\VC17LTCG");
+        assert_eq!(extended(r"D:/PkgCache/./VC17LTCG/x/.."), r"\\?\D:\PkgCache\VC17LTCG");
+    }
+
+    #[test]
+    fn unc_paths_get_the_extended_length_unc_prefix() {
+        assert_eq!(extended(r"\\server\share\PkgCache"), r"\\?\UNC\server\share\PkgCache");
+    }
+
+    #[test]
+    fn verbatim_and_device_paths_are_left_alone() {
+        for path in [r"\\?\C:\PkgCache", r"\\?\UNC\server\share\PkgCache", r"\\.\C:\PkgCache"] {
+            assert_eq!(extended(path), path);
+        }
+    }
+
+    #[test]
+    fn relative_paths_are_left_alone() {
+        for path in [r"PkgCache\VC17LTCG", r"..\PkgCache", r"\PkgCache"] {
+            assert_eq!(extended(path), path);
+        }
+    }
+}
@@ -1,261 +1,1660 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use cleanpkgcache::audit::AuditLog;
+use cleanpkgcache::cleaner::{
+    apply_plan, clean_and_print, list_package_cache, parse_keep, preview_deletions, print_clean_report, print_inventory,
+    print_totals, CleanReport, CleanOptions, InventoryReport, KeepCount, ListOrder, OutputFormat, PlanReport,
+    RetentionPolicy, SizeScope, TimeBasis, DEFAULT_KEEP,
+};
+use cleanpkgcache::color::{self, ColorChoice};
+use cleanpkgcache::config::{self, Config};
+use cleanpkgcache::csv;
+use cleanpkgcache::dedupe::{find_duplicates, print_duplicates, DedupeMode};
+use cleanpkgcache::duration::{parse_cutoff, parse_date, parse_duration, Cutoff, TimestampZone};
+use cleanpkgcache::guard::check_cache_root;
+use cleanpkgcache::hook::run_post_run_hook;
+use cleanpkgcache::metrics::write_metrics;
+use cleanpkgcache::plan;
+use cleanpkgcache::roo::{
+    clean_roo_checkpoints, parse_extension_id, parse_subdir_name, roo_task_paths, RooOptions, RooReport,
+    ROO_EXTENSION_IDS, TWO_MONTHS_IN_SECONDS,
+};
+use cleanpkgcache::sidecar::{parse_version_source, VersionSource};
+use cleanpkgcache::size::{format_bytes, parse_size};
+use cleanpkgcache::sizecache::{default_size_cache_path, SizeCache};
+use cleanpkgcache::stage::{print_purge_report, purge_staged, PurgeReport};
+use cleanpkgcache::stats::{cache_stats, print_stats, roo_stats, CacheStats, RooStats};
+use cleanpkgcache::template::SummaryTemplate;
+use cleanpkgcache::tree::TreeStyle;
+use cleanpkgcache::volume::clean_by_volume;
+use cleanpkgcache::{request_stop, stop_requested, CleanerOptions};
+use env_logger::WriteStyle;
+use log::{error, info, warn, Level, LevelFilter};
+use serde::Serialize;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-const ROO_TASK_PATHS: [&str; 2] = [
-    r"C:\Users\zhizha\AppData\Roaming\Code\User\globalStorage\microsoftai.ms-roo-cline\tasks",
-    r"C:\Users\zhizha\AppData\Roaming\Code\User\globalStorage\rooveterinaryinc.roo-cline\tasks",
-];
-const TWO_MONTHS_IN_SECONDS: u64 = 60 * 24 * 60 * 60;
+const DEFAULT_CACHE_PATH: &str = r"C:\PkgCache\VC17LTCG";
+
+/// Setting this to `1` turns on safe mode: dry-run unless `--execute` is given.
+const SAFE_MODE_ENV: &str = "CLEANPKGCACHE_SAFE";
+/// Cache roots used when no `PATH` is given, separated like `PATH`.
+const PATH_ENV: &str = "CLEANPKGCACHE_PATH";
+/// Roo tasks directories used when no `--roo-tasks-dir` is given, separated like `PATH`.
+const ROO_DIRS_ENV: &str = "CLEANPKGCACHE_ROO_DIRS";
+
+// Exit codes, documented in the README. 0 is success, whether or not
+// anything was deleted.
+/// Invalid arguments, flags or config file.
+const EXIT_USAGE: u8 = 1;
+/// The run failed, e.g. a cache root couldn't be read or a deletion failed
+/// and stopped the run.
+const EXIT_IO: u8 = 2;
+/// Some deletions failed, but the run carried on past them (`--keep-going`
+/// or parallel jobs).
+const EXIT_PARTIAL: u8 = 3;
+/// Stopped early: Ctrl-C, `--max-runtime`, quitting an `--interactive`
+/// prompt or declining `--confirm-threshold`.
+const EXIT_ABORTED: u8 = 4;
+/// Used by `--fail-if-deleted` when anything was (or would be) deleted.
+const EXIT_DELETED: u8 = 5;
+
+/// Set when `--max-runtime` stopped the run, to tell it apart from Ctrl-C.
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// A mistake on the command line or in the config file, exiting with
+/// [`EXIT_USAGE`].
+#[derive(Debug)]
+struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Marks a failure to set up the run from its arguments as a [`UsageError`].
+fn usage(err: anyhow::Error) -> anyhow::Error {
+    anyhow::Error::msg(UsageError(format!("{:#}", err)))
+}
+
+/// Returns a [`UsageError`] built like `format!`.
+macro_rules! usage_bail {
+    ($($arg:tt)*) => {
+        return Err(anyhow::Error::msg(UsageError(format!($($arg)*))))
+    };
+}
+
+/// Deletions that failed while the run went on, exiting with [`EXIT_PARTIAL`].
+#[derive(Debug)]
+struct PartialFailure(usize);
+
+impl fmt::Display for PartialFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} version(s) could not be deleted", self.0)
+    }
+}
+
+/// The user called the run off before anything was deleted, exiting with
+/// [`EXIT_ABORTED`].
+#[derive(Debug)]
+struct Aborted(String);
+
+impl fmt::Display for Aborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What one cleanup run did, for picking the exit code.
+struct RunOutcome {
+    errors: Vec<String>,
+    deleted: usize,
+    /// The user quit at an `--interactive` prompt.
+    aborted: bool,
+}
 
 #[derive(Parser)]
 #[command(name = "cleanpkgcache")]
-#[command(about = "Clean package cache by keeping only the latest 2 versions of each package")]
+#[command(about = "Clean package cache by keeping only the latest N versions of each package (default 2)")]
 #[command(version = "0.2.1")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Path to the package cache directory
-    #[arg(default_value = r"C:\PkgCache\VC17LTCG")]
-    path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Paths to one or more package cache directories; `*`, `?` and `[...]` expand to every matching
+    /// directory [env: CLEANPKGCACHE_PATH] [default: C:\PkgCache\VC17LTCG]
+    #[arg(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
+    /// Also clean the cache roots listed in FILE, one per line; blank lines and lines starting with `#` are ignored
+    #[arg(long, value_name = "FILE")]
+    paths_from: Option<PathBuf>,
+
+    /// Only list every package and version with its size and timestamp, without cleaning anything
+    #[arg(long, conflicts_with_all = ["apply_plan", "plan_out", "interactive", "watch", "clean_roo_checkpoints", "log_file"])]
+    list: bool,
+
+    /// Only report version directories that are identical across packages and what removing the copies would free,
+    /// without cleaning anything (compares contents with --dedupe-strict)
+    #[arg(long, conflicts_with_all = ["list", "apply_plan", "plan_out", "interactive", "watch", "clean_roo_checkpoints",
+        "log_file"])]
+    find_duplicates: bool,
 
     /// Dry run - show what would be deleted without actually deleting
     #[arg(short, long)]
     dry_run: bool,
 
-    /// Verbose output
+    /// Actually delete in safe mode (CLEANPKGCACHE_SAFE=1 or safe_mode in the config), which otherwise only dry-runs;
+    /// also overrides dry_run in the config
+    #[arg(long, visible_alias = "no-dry-run", conflicts_with = "dry_run")]
+    execute: bool,
+
+    /// Verbose output (debug-level logging; RUST_LOG is honored as well)
     #[arg(short, long)]
     verbose: bool,
 
-    /// Also clean Roo checkpoints older than 2 months
+    /// Don't log verbosely even though the config file sets verbose
+    #[arg(long, conflicts_with = "verbose")]
+    no_verbose: bool,
+
+    /// Only print the final summaries, not each package and deletion
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print nothing unless something needs attention: errors, warnings, versions skipped as in use
+    /// and retried deletions. A successful run prints no summary at all
+    #[arg(long, conflicts_with_all = ["verbose", "quiet", "summary_only", "format", "interactive", "list"])]
+    report_only_errors: bool,
+
+    /// In a dry run, print one "before -> after" line per package instead of every path
+    #[arg(long)]
+    summary_only: bool,
+
+    /// Print this line per cache root instead of the summary, e.g. "{deleted} deleted, {freed} freed from
+    /// {packages} packages". Placeholders: path, mode, packages, kept, deleted, would-delete, freed,
+    /// freed-bytes, failed, pinned, duplicates, excess
+    #[arg(long, value_name = "TEMPLATE", value_parser = SummaryTemplate::parse,
+        conflicts_with_all = ["format", "report_only_errors"])]
+    summary_format: Option<SummaryTemplate>,
+
+    /// Which rule decides what each package keeps: the newest --keep versions, everything newer
+    /// than --delete-older-than, or the newest that fit in --max-total-size [default: count, or
+    /// size with --max-total-size]
+    #[arg(long, value_enum)]
+    policy: Option<RetentionPolicy>,
+
+    /// Number of latest versions to keep for each package, or a percentage of them like 25% [default: 2]
+    #[arg(short, long, value_name = "N|PERCENT%", value_parser = parse_keep)]
+    keep: Option<KeepCount>,
+
+    /// Number of worker threads used to delete version directories; 1 deletes
+    /// sequentially without a thread pool [default: number of CPUs, at most 8]
+    #[arg(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    jobs: Option<usize>,
+
+    /// Clean roots on different disks at the same time, with this many worker threads per disk
+    /// (default: 2), instead of one root after the other
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "2",
+        conflicts_with_all = ["jobs", "interactive"],
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    jobs_per_volume: Option<usize>,
+
+    /// Directory levels below the root that name a package, e.g. 2 for package/arch/version
+    #[arg(long, value_name = "N", default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    package_depth: usize,
+
+    /// Treat the root's directories as versions of a flat cache, grouped into packages by this
+    /// regex's capture group (named `package`, or else the first), e.g. '^(.*)-[0-9.]+$'
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["package_depth", "prune_empty"])]
+    group_by: Option<String>,
+
+    /// Only clean packages whose directory name matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Never clean packages whose directory name matches this glob (repeatable, wins over --include)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Always keep version directories whose name matches this glob, on top of --keep (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pin: Vec<String>,
+
+    /// Delete version directories left by interrupted installs (matching --temp-pattern), outside the keep count
+    #[arg(long)]
+    clean_temp: bool,
+
+    /// Name of leftover directories for --clean-temp (repeatable) [default: *.tmp, *~, .partial*]
+    #[arg(long, value_name = "GLOB", requires = "clean_temp")]
+    temp_pattern: Vec<String>,
+
+    /// Ask before deleting each version: y = yes, N = no (default), a = all, q = quit
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// Move old versions to the recycle bin / trash instead of deleting them permanently
+    #[arg(long)]
+    trash: bool,
+
+    /// Rename old versions to <name>.to-delete instead of deleting them, for a later --purge-staged run
+    #[arg(long, conflicts_with = "trash")]
+    stage: bool,
+
+    /// Only permanently delete the versions an earlier --stage run renamed, without cleaning anything else
+    #[arg(long, conflicts_with_all = ["list", "find_duplicates", "apply_plan", "plan_out", "interactive", "watch",
+        "clean_roo_checkpoints", "stage", "trash"])]
+    purge_staged: bool,
+
+    /// Never delete versions younger than this, e.g. 30m, 12h, 2d [default: no limit]
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    min_age: Option<Duration>,
+
+    /// Keep at least M versions of every package, whatever --keep, --delete-older-than or --max-total-size allow
+    #[arg(long, value_name = "M")]
+    min_versions: Option<usize>,
+
+    /// Only delete versions older than this age (e.g. 90d) or date (e.g. 2024-01-31).
+    /// The newest --keep versions are kept regardless; of the rest, newer ones are kept too
+    #[arg(long, value_name = "DURATION|DATE", value_parser = parse_cutoff)]
+    delete_older_than: Option<Cutoff>,
+
+    /// Skip any version directory containing a file with this name, as it is in use (repeatable)
+    #[arg(long, value_name = "NAME", default_values_t = [".lock".to_string(), "in_use.marker".to_string()])]
+    lock_file_name: Vec<String>,
+
+    /// Run this command before deleting each version, with the version path as its last argument and in
+    /// CLEANPKGCACHE_VERSION_PATH. A non-zero exit skips that version. Not run in dry runs
+    #[arg(long, value_name = "CMD")]
+    pre_delete_hook: Option<String>,
+
+    /// Run this command once the run is done, with the JSON report's path in CLEANPKGCACHE_REPORT and
+    /// the totals in CLEANPKGCACHE_* variables. A failing hook is only logged, unless --fail-on-hook-error
+    #[arg(long, value_name = "CMD")]
+    post_run_hook: Option<String>,
+
+    /// Exit with an error when the --post-run-hook fails
+    #[arg(long, requires = "post_run_hook")]
+    fail_on_hook_error: bool,
+
+    /// Order of the verbose "Found N versions" listing (doesn't affect what is kept)
+    #[arg(long, value_enum, default_value_t = ListOrder::Newest)]
+    sort: ListOrder,
+
+    /// Show at most N versions per package in the verbose listing, then "... and M more" (doesn't affect what is kept)
+    #[arg(long, value_name = "N")]
+    verbose_limit: Option<usize>,
+
+    /// In a dry run, print the versions each package keeps, newest first, to check them before a real run
+    #[arg(long, conflicts_with_all = ["quiet", "summary_only", "report_only_errors"])]
+    show_kept: bool,
+
+    /// In a dry run, print why each version is kept or deleted: the rule that decided it, like "kept: within latest 2"
+    #[arg(long, conflicts_with_all = ["quiet", "summary_only", "report_only_errors"])]
+    explain: bool,
+
+    /// Report the free space of each cache root's disk before and after cleaning, as measured rather than summed;
+    /// a dry run reports it before, with an estimate for after
+    #[arg(long)]
+    free_space: bool,
+
+    /// Print nothing about packages with no version to delete, even with --verbose (warnings still show)
+    #[arg(long)]
+    affected_only: bool,
+
+    /// In a dry run, print each cache root as a tree of packages and versions marked KEEP or DELETE with their sizes
+    #[arg(long, conflicts_with_all = ["report_only_errors", "summary_format"])]
+    tree: bool,
+
+    /// Which timestamp decides how old a version is
+    #[arg(long, value_enum, default_value_t = TimeBasis::Mtime)]
+    time_basis: TimeBasis,
+
+    /// Time zone of the timestamps in verbose listings and Roo messages
+    #[arg(long, value_enum, default_value_t = TimestampZone::Utc)]
+    timestamps: TimestampZone,
+
+    /// Read each version's real version or date from FIELD of the JSON file FILE inside it, e.g. metadata.json:version (falls back to the name and --time-basis)
+    #[arg(long, value_name = "FILE:FIELD", value_parser = parse_version_source)]
+    version_from: Option<VersionSource>,
+
+    /// Keep the newest versions until their total size reaches this budget, e.g. 50GiB (replaces --keep)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_total_size: Option<u64>,
+
+    /// Let --max-total-size delete every version of a package, including the newest
+    #[arg(long)]
+    allow_empty_packages: bool,
+
+    /// Whether --max-total-size applies to each package or to the whole cache
+    #[arg(long, value_enum, default_value_t = SizeScope::Package)]
+    size_scope: SizeScope,
+
+    /// For symlinked version directories, delete the target's contents instead of only the link
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Also treat regular files at the version level, like pkg-1.0.zip, as versions
+    #[arg(long)]
+    include_files: bool,
+
+    /// Delete versions whose file names and sizes match a newer version of the same package, whatever --keep says
+    #[arg(long)]
+    dedupe: bool,
+
+    /// Like --dedupe, but compare full file contents
+    #[arg(long)]
+    dedupe_strict: bool,
+
+    /// Remember version directory sizes between runs and reuse them while a directory's mtime is unchanged
+    #[arg(long)]
+    size_cache: bool,
+
+    /// Remove package directories that are empty once their old versions are gone
+    #[arg(long)]
+    prune_empty: bool,
+
+    /// Times to retry a deletion that failed because a file was briefly locked or busy
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    retries: u32,
+
+    /// Keep deleting after a failure and report all errors at the end
+    #[arg(long)]
+    keep_going: bool,
+
+    /// During a dry run, write the directories that would be deleted to this file
+    #[arg(long, value_name = "FILE")]
+    plan_out: Option<PathBuf>,
+
+    /// Delete exactly the directories listed in a plan file instead of scanning
+    #[arg(long, value_name = "FILE")]
+    apply_plan: Option<PathBuf>,
+
+    /// Ask before deleting more than this many versions (e.g. 100) or this much data (e.g. 20GiB), even without --interactive
+    #[arg(long, value_name = "N|SIZE", value_parser = parse_threshold,
+        conflicts_with_all = ["interactive", "apply_plan", "watch"])]
+    confirm_threshold: Option<Threshold>,
+
+    /// Exit with code 5 if any version was deleted (or would be, in a dry run)
+    #[arg(long)]
+    fail_if_deleted: bool,
+
+    /// Keep running, cleaning again after every interval, e.g. 1h or 1d
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration,
+        conflicts_with_all = ["apply_plan", "plan_out", "interactive", "fail_if_deleted"])]
+    watch: Option<Duration>,
+
+    /// Stop starting new deletions once the run has taken this long, e.g. 30s or 5m, and exit
+    /// successfully with a summary of what was done
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "watch")]
+    max_runtime: Option<Duration>,
+
+    /// Append a timestamped record of every deleted (or would-be deleted) directory to this file
+    #[arg(long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// After each run, write Prometheus metrics for the node_exporter textfile collector to this file
+    #[arg(long, value_name = "FILE")]
+    metrics_out: Option<PathBuf>,
+
+    /// Append a summary row per cache root to this CSV file after each run
+    #[arg(long, value_name = "FILE")]
+    csv_out: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+
+    /// Color kept, deleted and would-be-deleted versions (never with --quiet or --format json)
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Also clean Roo checkpoints older than --roo-max-age
     #[arg(long)]
     clean_roo_checkpoints: bool,
+
+    /// Don't clean Roo checkpoints even though the config file sets clean_roo_checkpoints
+    #[arg(long, conflicts_with = "clean_roo_checkpoints")]
+    no_clean_roo_checkpoints: bool,
+
+    /// Age after which Roo task checkpoints are cleaned, e.g. 30d, 6w, 3mo [default: 2mo]
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    roo_max_age: Option<Duration>,
+
+    /// Clean Roo checkpoints of tasks not modified since this UTC date, e.g. 2024-01-01 or 2024-01-01T12:00:00Z
+    #[arg(long, value_name = "DATE", value_parser = parse_date, conflicts_with = "roo_max_age")]
+    roo_since: Option<SystemTime>,
+
+    /// Roo tasks directory to scan instead of the auto-detected ones (repeatable) [env: CLEANPKGCACHE_ROO_DIRS]
+    #[arg(long, value_name = "PATH")]
+    roo_tasks_dir: Vec<PathBuf>,
+
+    /// VS Code extension whose Roo tasks are scanned in the auto-detected global storage, e.g. saoudrizwan.claude-dev
+    /// for Cline (repeatable) [default: microsoftai.ms-roo-cline, rooveterinaryinc.roo-cline]
+    #[arg(long, value_name = "ID", value_parser = parse_extension_id)]
+    roo_extension_id: Vec<String>,
+
+    /// Subdirectories of an aged Roo task to delete (comma-separated or repeated)
+    #[arg(long, value_name = "NAMES", value_delimiter = ',', value_parser = parse_subdir_name,
+        default_value = "checkpoints")]
+    roo_subdirs: Vec<String>,
+
+    /// Delete aged Roo task folders entirely, not just their subdirectories
+    #[arg(long, conflicts_with = "roo_subdirs")]
+    roo_delete_task: bool,
+
+    /// Judge a Roo task's age by the newest file or folder inside it rather than the task folder's own mtime
+    #[arg(long)]
+    roo_deep_age: bool,
+
+    /// Keep a Roo task subdirectory modified less than this long ago, e.g. 7d, even when its task is old
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    roo_protect_newer_than: Option<Duration>,
+
+    /// Clean a PATH even if it looks like a drive root, a system folder or your home folder
+    #[arg(long = "i-know-what-im-doing")]
+    skip_root_check: bool,
+
+    /// Clean the folder containing a PATH that is a file, e.g. an archive inside the cache, instead of failing
+    #[arg(long)]
+    auto_parent: bool,
+
+    /// Config file to read [default: ~/.config/cleanpkgcache/config.toml]
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Per-package keep counts, only settable from the config file
+    #[arg(skip)]
+    keep_overrides: HashMap<String, usize>,
+
+    /// Roots exempt from the dangerous-root check, only settable from the config file
+    #[arg(skip)]
+    allowed_roots: Vec<PathBuf>,
+
+    /// From `CLEANPKGCACHE_SAFE=1` or the config file
+    #[arg(skip)]
+    safe_mode: bool,
+
+    /// Set by the `roo` subcommand: clean Roo checkpoints and leave package caches alone
+    #[arg(skip)]
+    roo_only: bool,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+#[derive(Subcommand)]
+enum Command {
+    /// Print a shell completion script to stdout, e.g. `cleanpkgcache completions bash > cleanpkgcache.bash`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Clean only Roo task checkpoints, never touching a package cache
+    Roo {
+        /// Dry run - show what would be deleted without actually deleting
+        #[arg(short, long)]
+        dry_run: bool,
 
-    if args.dry_run {
-        println!("DRY RUN MODE - No files will be deleted");
-    }
+        /// Actually delete in safe mode, which otherwise only dry-runs; also overrides dry_run in the config
+        #[arg(long, visible_alias = "no-dry-run", conflicts_with = "dry_run")]
+        execute: bool,
+
+        /// Verbose output (debug-level logging)
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Don't log verbosely even though the config file sets verbose
+        #[arg(long, conflicts_with = "verbose")]
+        no_verbose: bool,
+
+        /// Age after which task checkpoints are cleaned, e.g. 30d, 6w, 3mo [default: 2mo]
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        max_age: Option<Duration>,
+
+        /// Clean checkpoints of tasks not modified since this UTC date, e.g. 2024-01-01
+        #[arg(long, value_name = "DATE", value_parser = parse_date, conflicts_with = "max_age")]
+        since: Option<SystemTime>,
+
+        /// Roo tasks directory to scan instead of the auto-detected ones (repeatable) [env: CLEANPKGCACHE_ROO_DIRS]
+        #[arg(long, value_name = "PATH")]
+        tasks_dir: Vec<PathBuf>,
+
+        /// VS Code extension whose tasks are scanned in the auto-detected global storage, e.g. saoudrizwan.claude-dev
+        /// for Cline (repeatable) [default: microsoftai.ms-roo-cline, rooveterinaryinc.roo-cline]
+        #[arg(long, value_name = "ID", value_parser = parse_extension_id)]
+        extension_id: Vec<String>,
+
+        /// Subdirectories of an aged task to delete (comma-separated or repeated)
+        #[arg(long, value_name = "NAMES", value_delimiter = ',', value_parser = parse_subdir_name,
+            default_value = "checkpoints")]
+        subdirs: Vec<String>,
+
+        /// Delete aged task folders entirely, not just their subdirectories
+        #[arg(long, conflicts_with = "subdirs")]
+        delete_task: bool,
+
+        /// Judge a task's age by the newest file or folder inside it
+        #[arg(long)]
+        deep_age: bool,
+
+        /// Keep a task subdirectory modified less than this long ago, e.g. 7d, even when its task is old
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        protect_newer_than: Option<Duration>,
+
+        /// Time zone of the timestamps in the keep and delete messages
+        #[arg(long, value_enum, default_value_t = TimestampZone::Utc)]
+        timestamps: TimestampZone,
+
+        /// Append a timestamped record of every deleted (or would-be deleted) directory to this file
+        #[arg(long, value_name = "FILE")]
+        log_file: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+
+        /// Config file to read [default: ~/.config/cleanpkgcache/config.toml]
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Report the size of every cache root and the Roo task folders, and how much a cleanup would free, without deleting anything
+    Stats {
+        /// Package cache directories to report on [env: CLEANPKGCACHE_PATH] [default: C:\PkgCache\VC17LTCG]
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+
+        /// Number of latest versions a cleanup would keep for each package, or a percentage of them
+        /// like 25% [default: 2]
+        #[arg(short, long, value_name = "N|PERCENT%", value_parser = parse_keep)]
+        keep: Option<KeepCount>,
 
-    // Only clean package cache if path exists or if not running roo-only mode
-    let should_clean_packages = args.path.exists() && args.path.is_dir();
+        /// Age after which Roo task checkpoints count as reclaimable [default: 2mo]
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        roo_max_age: Option<Duration>,
 
-    if should_clean_packages {
-        println!("Cleaning package cache at: {}", args.path.display());
-        clean_package_cache(&args.path, args.dry_run, args.verbose)?;
-    } else if !args.clean_roo_checkpoints {
-        // Only error out if we're not cleaning roo checkpoints either
-        if !args.path.exists() {
-            anyhow::bail!("Path does not exist: {}", args.path.display());
+        /// Judge a Roo task's age by the newest file or folder inside it
+        #[arg(long)]
+        roo_deep_age: bool,
+
+        /// Roo tasks directory to report on instead of the auto-detected ones (repeatable) [env: CLEANPKGCACHE_ROO_DIRS]
+        #[arg(long, value_name = "PATH")]
+        roo_tasks_dir: Vec<PathBuf>,
+
+        /// VS Code extension whose Roo tasks are reported on in the auto-detected global storage (repeatable)
+        /// [default: microsoftai.ms-roo-cline, rooveterinaryinc.roo-cline]
+        #[arg(long, value_name = "ID", value_parser = parse_extension_id)]
+        roo_extension_id: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+
+        /// Config file to read [default: ~/.config/cleanpkgcache/config.toml]
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+}
+
+impl Args {
+    /// Fills in anything not given on the command line or in the environment
+    /// from the config file. Flags and `CLEANPKGCACHE_*` variables always win
+    /// over the config, which wins over built-in defaults.
+    fn merge_config(&mut self, config: Config) -> Result<()> {
+        if self.paths.is_empty() && self.paths_from.is_none() {
+            self.paths.extend(config.path);
         }
-        if !args.path.is_dir() {
-            anyhow::bail!("Path is not a directory: {}", args.path.display());
+        if self.keep.is_none() {
+            self.keep = config.keep.map(KeepCount::Count);
         }
+        // --execute overrides a config-file dry_run like any other flag
+        self.dry_run |= config.dry_run.unwrap_or(false) && !self.execute;
+        self.safe_mode |= config.safe_mode.unwrap_or(false);
+        // The --no- flags switch off what the config turns on
+        self.verbose |= config.verbose.unwrap_or(false) && !self.no_verbose;
+        self.clean_roo_checkpoints |= config.clean_roo_checkpoints.unwrap_or(false) && !self.no_clean_roo_checkpoints;
+        self.keep_overrides = config.keep_overrides;
+        self.allowed_roots = config.allowed_roots;
+        if self.roo_extension_id.is_empty() {
+            self.roo_extension_id = config
+                .roo_extension_ids
+                .iter()
+                .map(|id| parse_extension_id(id))
+                .collect::<Result<_, _>>()
+                .map_err(|err| anyhow::anyhow!("Invalid roo_extension_ids in config file: {}", err))?;
+        }
+        // A --roo-since on the command line replaces the config's age entirely
+        if self.roo_max_age.is_none() && self.roo_since.is_none() {
+            if let Some(max_age) = config.roo_max_age {
+                self.roo_max_age = Some(parse_duration(&max_age)
+                    .map_err(|err| anyhow::anyhow!("Invalid roo_max_age in config file: {}", err))?);
+            }
+        }
+        Ok(())
     }
+}
 
-    if args.clean_roo_checkpoints {
-        clean_roo_checkpoints(args.dry_run, args.verbose)?;
-    }
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            // --help and --version also end up here, on stdout
+            let _ = err.print();
+            return if err.use_stderr() { ExitCode::from(EXIT_USAGE) } else { ExitCode::SUCCESS };
+        }
+    };
 
-    Ok(())
+    match run(args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(if err.downcast_ref::<UsageError>().is_some() {
+                EXIT_USAGE
+            } else if err.downcast_ref::<PartialFailure>().is_some() {
+                EXIT_PARTIAL
+            } else if err.downcast_ref::<Aborted>().is_some() {
+                EXIT_ABORTED
+            } else {
+                EXIT_IO
+            })
+        }
+    }
 }
 
-fn clean_package_cache(cache_path: &Path, dry_run: bool, verbose: bool) -> Result<()> {
-    let mut packages: HashMap<String, Vec<PackageVersion>> = HashMap::new();
+fn run(mut args: Args) -> Result<ExitCode> {
+    // `roo` and `stats` run through the same setup as a cleanup, with their flags in place of the top-level ones
+    let stats = match args.command.take() {
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Args::command(), "cleanpkgcache", &mut io::stdout());
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Roo {
+            dry_run, execute, verbose, no_verbose, max_age, since, tasks_dir, extension_id, subdirs, delete_task, deep_age,
+            protect_newer_than, timestamps, log_file, format, config,
+        }) => {
+            args.dry_run = dry_run;
+            args.execute = execute;
+            args.verbose = verbose;
+            args.no_verbose = no_verbose;
+            args.roo_max_age = max_age;
+            args.roo_since = since;
+            args.roo_tasks_dir = tasks_dir;
+            args.roo_extension_id = extension_id;
+            args.roo_subdirs = subdirs;
+            args.roo_delete_task = delete_task;
+            args.roo_deep_age = deep_age;
+            args.roo_protect_newer_than = protect_newer_than;
+            args.timestamps = timestamps;
+            args.log_file = log_file;
+            args.format = format;
+            args.config = config;
+            args.clean_roo_checkpoints = true;
+            args.roo_only = true;
+            false
+        }
+        Some(Command::Stats { paths, keep, roo_max_age, roo_deep_age, roo_tasks_dir, roo_extension_id, format, config }) => {
+            args.paths = paths;
+            args.keep = keep;
+            args.roo_max_age = roo_max_age;
+            args.roo_deep_age = roo_deep_age;
+            args.roo_tasks_dir = roo_tasks_dir;
+            args.roo_extension_id = roo_extension_id;
+            args.format = format;
+            args.config = config;
+            true
+        }
+        None => false,
+    };
 
-    // First pass: collect all package directories and their versions
-    for entry in fs::read_dir(cache_path)
-        .with_context(|| format!("Failed to read directory: {}", cache_path.display()))?
-    {
-        let entry = entry?;
-        let path = entry.path();
+    // Checked for existence once logging is set up and can warn about it
+    let listed = match &args.paths_from {
+        Some(file) => paths_from_file(file).map_err(usage)?,
+        None => Vec::new(),
+    };
+    // The environment sits between the command line and the config file,
+    // neither of which is used when a list of roots was given
+    if args.paths.is_empty() && listed.is_empty() {
+        args.paths = paths_from_env(PATH_ENV);
+    }
+    if args.roo_tasks_dir.is_empty() {
+        args.roo_tasks_dir = paths_from_env(ROO_DIRS_ENV);
+    }
 
-        if !path.is_dir() {
-            continue;
+    let config = config::load_config(args.config.as_deref()).map_err(usage)?;
+    // The age and size policies don't use a keep count. One from the config
+    // file is ignored, but one on the command line is a mistake.
+    if args.keep.is_some() {
+        match args.policy {
+            Some(RetentionPolicy::Age) => usage_bail!("--keep cannot be combined with --policy age"),
+            Some(RetentionPolicy::Size) => usage_bail!("--keep cannot be combined with --policy size"),
+            _ => {}
         }
+    }
+    // The library checks the same in builder terms; these name the flags
+    let by_age = args.delete_older_than.is_some();
+    let by_size = args.max_total_size.is_some();
+    match args.policy {
+        Some(RetentionPolicy::Count) if by_age || by_size => {
+            usage_bail!("--policy count cannot be combined with --delete-older-than or --max-total-size")
+        }
+        Some(RetentionPolicy::Age) if !by_age => usage_bail!("--policy age needs --delete-older-than"),
+        Some(RetentionPolicy::Age) if by_size => usage_bail!("--policy age cannot be combined with --max-total-size"),
+        Some(RetentionPolicy::Size) if !by_size => usage_bail!("--policy size needs --max-total-size"),
+        Some(RetentionPolicy::Size) if by_age => usage_bail!("--policy size cannot be combined with --delete-older-than"),
+        _ => {}
+    }
+    args.merge_config(config).map_err(usage)?;
 
-        let package_name = path.file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("")
-            .to_string();
+    // Safe mode inverts the default: nothing is deleted without --execute
+    args.safe_mode |= std::env::var(SAFE_MODE_ENV).is_ok_and(|value| value == "1");
+    if args.safe_mode && !args.execute {
+        args.dry_run = true;
+    }
 
-        if package_name.is_empty() {
-            continue;
-        }
+    if args.paths.is_empty() && args.paths_from.is_none() {
+        args.paths.push(PathBuf::from(DEFAULT_CACHE_PATH));
+    }
+    let keep = args.keep.unwrap_or(KeepCount::Count(DEFAULT_KEEP));
 
-        // Collect all version directories for this package
-        let mut versions = Vec::new();
+    let human = args.format == OutputFormat::Human;
+    // Checked after merging so a config-file verbose is caught too
+    if args.quiet && args.verbose {
+        usage_bail!("--quiet cannot be combined with --verbose");
+    }
+    if args.report_only_errors && args.verbose {
+        usage_bail!("--report-only-errors cannot be combined with --verbose");
+    }
+    // Human output is printed only when something needs attention
+    let summaries = human && !args.report_only_errors;
 
-        for version_entry in fs::read_dir(&path)
-            .with_context(|| format!("Failed to read package directory: {}", path.display()))?
-        {
-            let version_entry = version_entry?;
-            let version_path = version_entry.path();
+    if args.summary_only && !args.dry_run {
+        usage_bail!("--summary-only can only be used together with --dry-run");
+    }
+    if args.show_kept && !args.dry_run {
+        usage_bail!("--show-kept can only be used together with --dry-run");
+    }
+    if args.explain && !args.dry_run {
+        usage_bail!("--explain can only be used together with --dry-run");
+    }
+    // Cycles would follow each other without a pause
+    if args.watch.is_some_and(|interval| interval.is_zero()) {
+        usage_bail!("--watch interval must be longer than zero, e.g. 1h");
+    }
+    if args.tree && (!args.dry_run || args.format != OutputFormat::Human) {
+        usage_bail!("--tree can only be used together with --dry-run and the human format");
+    }
+
+    // --summary-only replaces the per-path lines, unless --verbose asks for everything
+    let colored = summaries && !args.quiet && match args.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        // Per-version lines go to stderr and summaries to stdout, so both must be terminals
+        ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            && io::stdout().is_terminal()
+            && io::stderr().is_terminal(),
+    };
+    color::set_enabled(colored);
+    init_logging(
+        args.verbose,
+        args.quiet || args.report_only_errors || stats || (args.summary_only && !args.verbose),
+        args.format,
+        colored,
+    );
+    let verbose = log::log_enabled!(Level::Debug);
 
-            if !version_path.is_dir() {
-                continue;
+    if let Some(file) = &args.paths_from {
+        for (line, path) in listed {
+            if path.to_string_lossy().contains(['*', '?', '[']) || fs::symlink_metadata(&path).is_ok() {
+                args.paths.push(path);
+            } else {
+                warn!("skipping {} from {} line {} (path does not exist)", path.display(), file.display(), line);
             }
+        }
+        // Falling back to the default root would clean something the list never named
+        if args.paths.is_empty() {
+            usage_bail!("{} lists no cache root that exists", file.display());
+        }
+    }
 
-            let version_name = version_path.file_name()
-                .and_then(|name| name.to_str())
-                .unwrap_or("")
-                .to_string();
+    if !args.roo_only {
+        args.paths = expand_path_globs(&args.paths).map_err(usage)?;
+        if args.auto_parent {
+            args.paths = parents_of_files(&args.paths);
+        }
+    }
 
-            if version_name.is_empty() {
-                continue;
+    // Every folder of a root is taken for a package, so `/` or a home folder
+    // would lose all but the newest subfolders of everything in it
+    if !args.skip_root_check && !args.roo_only {
+        for path in &args.paths {
+            if let Err(reason) = check_cache_root(path, &args.allowed_roots) {
+                usage_bail!(
+                    "Refusing to clean {} because {}; add it to allowed_roots in the config file or pass --i-know-what-im-doing",
+                    path.display(),
+                    reason
+                );
             }
+        }
+    }
 
-            // Get modification time for sorting
-            let metadata = fs::metadata(&version_path)
-                .with_context(|| format!("Failed to get metadata for: {}", version_path.display()))?;
+    // Checked after merging so a config-file dry_run is caught too
+    if args.interactive && args.dry_run {
+        usage_bail!("--interactive cannot be combined with --dry-run");
+    }
+    if args.plan_out.is_some() && !args.dry_run {
+        usage_bail!("--plan-out can only be used together with --dry-run");
+    }
+    if args.plan_out.is_some() && args.apply_plan.is_some() {
+        usage_bail!("--plan-out cannot be combined with --apply-plan");
+    }
+    if args.interactive && !human {
+        usage_bail!("--interactive can only be used with the human output format");
+    }
 
-            let modified = metadata.modified()
-                .with_context(|| format!("Failed to get modification time for: {}", version_path.display()))?;
+    if args.list || args.find_duplicates {
+        // Nothing gets deleted, so no mode line
+    } else if args.safe_mode && args.dry_run {
+        info!("SAFE MODE (DRY RUN) - No files will be deleted, pass --execute to delete");
+    } else if args.safe_mode {
+        info!("SAFE MODE (EXECUTE) - Files will be deleted");
+    } else if args.dry_run {
+        info!("DRY RUN MODE - No files will be deleted");
+    }
+    if let Some(hook) = args.pre_delete_hook.as_ref().filter(|_| !args.list && !args.find_duplicates && !args.purge_staged) {
+        if args.dry_run {
+            info!("Pre-delete hook not run in a dry run: {}", hook);
+        } else {
+            info!("Running pre-delete hook before each deletion: {}", hook);
+        }
+    }
 
-            versions.push(PackageVersion {
-                name: version_name,
-                path: version_path,
-                modified,
-            });
+    // The first Ctrl-C lets the current deletion finish and prints what was
+    // done so far; a second one exits immediately.
+    ctrlc::set_handler(|| {
+        if request_stop() {
+            std::process::exit(EXIT_ABORTED.into());
         }
+        warn!("interrupted, finishing the current deletion and stopping (press Ctrl-C again to exit now)");
+    })
+    .context("Failed to install Ctrl-C handler")?;
+
+    // Running workers check for a stop between deletions, like after Ctrl-C
+    if let Some(max_runtime) = args.max_runtime {
+        std::thread::spawn(move || {
+            std::thread::sleep(max_runtime);
+            if !stop_requested() {
+                TIMED_OUT.store(true, Ordering::SeqCst);
+                warn!("--max-runtime of {} reached, finishing the current deletion and stopping",
+                    humantime::format_duration(max_runtime));
+                request_stop();
+            }
+        });
+    }
+
+    let audit = match &args.log_file {
+        Some(log_file) => Some(Arc::new(AuditLog::open(log_file, args.dry_run)?)),
+        None => None,
+    };
+
+    let mut builder = CleanerOptions::builder()
+        .paths(args.paths.iter().cloned())
+        .dry_run(args.dry_run)
+        .verbose(verbose)
+        .quiet(args.quiet || args.report_only_errors)
+        .report_only_errors(args.report_only_errors)
+        .summary_only(args.summary_only)
+        .format(args.format)
+        .interactive(args.interactive)
+        .trash(args.trash)
+        .stage(args.stage)
+        .keep_going(args.keep_going)
+        .retries(args.retries)
+        .prune_empty(args.prune_empty)
+        .package_depth(args.package_depth)
+        .min_age(args.min_age.unwrap_or(Duration::ZERO))
+        .min_versions(args.min_versions.unwrap_or(0))
+        .sort(args.sort)
+        .lock_file_names(args.lock_file_name.iter().cloned())
+        .time_basis(args.time_basis)
+        .timestamps(args.timestamps)
+        .follow_symlinks(args.follow_symlinks)
+        .include_files(args.include_files)
+        .size_scope(args.size_scope)
+        .allow_empty_packages(args.allow_empty_packages)
+        .clean_temp(args.clean_temp);
+    for pattern in &args.include {
+        builder = builder.include(pattern);
+    }
+    for pattern in &args.exclude {
+        builder = builder.exclude(pattern);
+    }
+    for pattern in &args.pin {
+        builder = builder.pin(pattern);
+    }
+    for pattern in &args.temp_pattern {
+        builder = builder.temp_pattern(pattern);
+    }
+    builder = match keep {
+        KeepCount::Count(count) => builder.keep(count),
+        KeepCount::Percent(percent) => builder.keep_percent(percent),
+    };
+    for (package, keep) in &args.keep_overrides {
+        builder = builder.keep_override(package, *keep);
+    }
+    if let Some(pattern) = &args.group_by {
+        builder = builder.group_by(pattern);
+    }
+    if let Some(policy) = args.policy {
+        builder = builder.policy(policy);
+    }
+    if let Some(source) = &args.version_from {
+        builder = builder.version_from(source.clone());
+    }
+    if let Some(jobs) = args.jobs {
+        builder = builder.jobs(jobs);
+    }
+    if let Some(jobs) = args.jobs_per_volume {
+        builder = builder.jobs_per_volume(jobs);
+    }
+    if let Some(template) = &args.summary_format {
+        builder = builder.summary_format(template.clone());
+    }
+    if let Some(limit) = args.verbose_limit {
+        builder = builder.verbose_limit(limit);
+    }
+    builder = builder.show_kept(args.show_kept).explain(args.explain).affected_only(args.affected_only);
+    builder = builder.free_space(args.free_space);
+    if args.tree {
+        // Box-drawing characters may come out garbled wherever colors are unwelcome too
+        let style = if args.color == ColorChoice::Never { TreeStyle::Ascii } else { TreeStyle::detect() };
+        builder = builder.tree(style);
+    }
+    if let Some(hook) = &args.pre_delete_hook {
+        builder = builder.pre_delete_hook(hook.clone());
+    }
+    if let Some(cutoff) = args.delete_older_than {
+        builder = builder.delete_older_than(cutoff.resolve(SystemTime::now()));
+    }
+    if let Some(limit) = args.max_total_size {
+        builder = builder.max_total_size(limit);
+    }
+    if let Some(audit) = &audit {
+        builder = builder.audit_log(audit.clone());
+    }
+    if args.size_cache {
+        let path = default_size_cache_path()
+            .context("Could not locate the user cache directory for --size-cache")?;
+        builder = builder.size_cache(Arc::new(SizeCache::load(&path)));
+    }
+    if args.dedupe_strict {
+        builder = builder.dedupe(DedupeMode::Contents);
+    } else if args.dedupe {
+        builder = builder.dedupe(DedupeMode::Manifest);
+    }
+    let cleaner_options = builder.build().map_err(usage)?;
+    let options = cleaner_options.clean_options();
+    // Measuring sizes runs on the global pool outside of deletions, so --jobs bounds it too
+    if let Err(err) = rayon::ThreadPoolBuilder::new().num_threads(options.jobs).build_global() {
+        warn!("could not limit size measuring to {} jobs: {}", options.jobs, err);
+    }
 
-        if !versions.is_empty() {
-            packages.insert(package_name, versions);
+    if args.list {
+        return list(&args, options);
+    }
+    if args.find_duplicates {
+        return print_duplicate_report(&args, options);
+    }
+    if args.purge_staged {
+        return purge(&args, options, summaries);
+    }
+    if stats {
+        return print_stats_report(&args, options, &roo_options(&args, None));
+    }
+
+    if let Some(plan_file) = &args.apply_plan {
+        let plan_report = apply_plan(plan_file, &args.paths, options)?;
+        let errors = plan_report.errors.clone();
+        let deleted = plan_report.deleted.len();
+        let report = JsonReport {
+            dry_run: args.dry_run,
+            policy: options.policy,
+            keep,
+            package_caches: Vec::new(),
+            plan: Some(plan_report),
+            roo_checkpoints: None,
+        };
+        if !human {
+            println!("{}", serde_json::to_string_pretty(&report)?);
         }
+        if let Some(hook) = &args.post_run_hook {
+            post_run_hook(hook, &report, args.fail_on_hook_error)?;
+        }
+        return finish(RunOutcome { errors, deleted, aborted: false }, args.fail_if_deleted);
     }
 
-    // Second pass: clean each package
-    let mut total_deleted = 0;
-    let mut total_kept = 0;
-    let packages_count = packages.len();
+    let roo_options = args.clean_roo_checkpoints.then(|| roo_options(&args, audit));
 
-    for (package_name, mut versions) in packages {
-        // Sort versions by modification time (newest first)
-        versions.sort_by(|a, b| b.modified.cmp(&a.modified));
+    let Some(interval) = args.watch else {
+        let outcome = run_once(&args, options, roo_options.as_ref())?;
+        return finish(outcome, args.fail_if_deleted);
+    };
 
-        if verbose {
-            println!("\nPackage: {}", package_name);
-            println!("  Found {} versions:", versions.len());
-            for (i, version) in versions.iter().enumerate() {
-                println!("    {}: {} (modified: {:?})",
-                    i + 1,
-                    version.name,
-                    version.modified
-                );
+    // A failed cycle is reported and retried on the next one; Ctrl-C is the
+    // normal way out, so it exits successfully.
+    loop {
+        if summaries {
+            println!("\n=== {} ===", humantime::format_rfc3339_seconds(SystemTime::now()));
+        }
+        match run_once(&args, options, roo_options.as_ref()) {
+            Ok(outcome) => {
+                for err in outcome.errors {
+                    error!("{}", err);
+                }
             }
+            Err(err) => error!("{:#}", err),
         }
 
-        // Keep the latest 2 versions, delete the rest
-        let to_keep = versions.iter().take(2);
-        let to_delete = versions.iter().skip(2);
+        if !wait_unless_stopped(interval) {
+            break;
+        }
+    }
 
-        for version in to_keep {
-            if verbose {
-                println!("  Keeping: {}", version.name);
-            }
-            total_kept += 1;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The Roo checkpoint cleanup settings from `args`.
+fn roo_options(args: &Args, audit: Option<Arc<AuditLog>>) -> RooOptions {
+    RooOptions {
+        dry_run: args.dry_run,
+        format: args.format,
+        cutoff: match args.roo_since {
+            Some(since) => Cutoff::Date(since),
+            None => Cutoff::Age(args.roo_max_age.unwrap_or(Duration::from_secs(TWO_MONTHS_IN_SECONDS))),
+        },
+        task_dirs: if !args.roo_tasks_dir.is_empty() {
+            args.roo_tasks_dir.clone()
+        } else if args.roo_extension_id.is_empty() {
+            roo_task_paths(&ROO_EXTENSION_IDS)
+        } else {
+            roo_task_paths(&args.roo_extension_id)
+        },
+        subdirs: args.roo_subdirs.clone(),
+        delete_task: args.roo_delete_task,
+        deep_age: args.roo_deep_age,
+        timestamps: args.timestamps,
+        protect_newer_than: args.roo_protect_newer_than,
+        audit,
+        report_only_errors: args.report_only_errors,
+    }
+}
+
+/// Runs `cleanpkgcache stats`. Missing cache roots are skipped with a
+/// warning, so the Roo task folders are still reported.
+fn print_stats_report(args: &Args, options: &CleanOptions, roo_options: &RooOptions) -> Result<ExitCode> {
+    let mut caches = Vec::new();
+    for path in &args.paths {
+        if !path.is_dir() {
+            let (problem, hint) = root_problem(path);
+            warn!("skipping {} (path {}{})", path.display(), problem, hint);
+            continue;
         }
+        caches.push(cache_stats(path, options)?);
+    }
+    let roo = roo_stats(roo_options)?;
 
-        for version in to_delete {
-            if dry_run {
-                println!("  Would delete: {}", version.path.display());
-            } else {
-                println!("  Deleting: {}", version.path.display());
-                fs::remove_dir_all(&version.path)
-                    .with_context(|| format!("Failed to delete directory: {}", version.path.display()))?;
+    if options.format == OutputFormat::Human {
+        print_stats(&caches, &roo, options);
+    } else {
+        let report = StatsReport { keep: options.keep, caches, roo };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let errors: Vec<String> = caches.iter().flat_map(|cache| cache.errors.iter().cloned()).collect();
+    report_errors(&errors)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the `--list` inventory of every cache root, as text or one JSON
+/// document.
+fn list(args: &Args, options: &CleanOptions) -> Result<ExitCode> {
+    let inventories = inventories(args, options)?;
+
+    if options.format == OutputFormat::Human {
+        for (i, inventory) in inventories.iter().enumerate() {
+            if i > 0 {
+                println!();
             }
-            total_deleted += 1;
+            print_inventory(inventory, options);
         }
+    } else {
+        println!("{}", serde_json::to_string_pretty(&ListReport { inventories })?);
+        return Ok(ExitCode::SUCCESS);
     }
 
-    println!("\nSummary:");
-    println!("  Packages processed: {}", packages_count);
-    println!("  Versions kept: {}", total_kept);
-    if dry_run {
-        println!("  Versions that would be deleted: {}", total_deleted);
+    let errors: Vec<String> = inventories.iter().flat_map(|inventory| inventory.errors.iter().cloned()).collect();
+    report_errors(&errors)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the `--find-duplicates` report over every cache root, as text or
+/// JSON.
+fn print_duplicate_report(args: &Args, options: &CleanOptions) -> Result<ExitCode> {
+    let inventories = inventories(args, options)?;
+    let report = find_duplicates(&inventories, options.dedupe.unwrap_or(DedupeMode::Manifest));
+
+    if options.format == OutputFormat::Human {
+        print_duplicates(&report);
     } else {
-        println!("  Versions deleted: {}", total_deleted);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(ExitCode::SUCCESS);
     }
 
-    Ok(())
+    let errors: Vec<String> = inventories
+        .iter()
+        .flat_map(|inventory| inventory.errors.iter().cloned())
+        .chain(report.errors.iter().cloned())
+        .collect();
+    report_errors(&errors)?;
+    Ok(ExitCode::SUCCESS)
 }
 
-fn clean_roo_checkpoints(dry_run: bool, verbose: bool) -> Result<()> {
-    let two_months = Duration::from_secs(TWO_MONTHS_IN_SECONDS);
-    let now = SystemTime::now();
-    let mut tasks_checked = 0;
-    let mut checkpoints_targets = 0;
+/// Runs `--purge-staged` over every cache root, printing a summary for each
+/// or one JSON document.
+fn purge(args: &Args, options: &CleanOptions, summaries: bool) -> Result<ExitCode> {
+    let mut reports = Vec::new();
+    for path in &args.paths {
+        if !path.is_dir() {
+            let (problem, hint) = root_problem(path);
+            usage_bail!("Path {}: {}{}", problem, path.display(), hint);
+        }
+        info!("Purging staged versions under: {}", path.display());
+        let report = purge_staged(path, options)?;
+        if summaries {
+            print_purge_report(&report, options.dry_run);
+        }
+        reports.push(report);
+    }
+
+    if options.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&PurgeListReport { purges: &reports })?);
+    }
+    let errors: Vec<String> = reports.iter().flat_map(|report| report.errors.iter().cloned()).collect();
+    report_errors(&errors)?;
+    Ok(ExitCode::SUCCESS)
+}
 
-    println!("\nCleaning Roo checkpoints older than approximately 2 months...");
+/// Lists the packages and versions of every cache root for `--list` and
+/// `--find-duplicates`, failing on a root that isn't a directory.
+fn inventories(args: &Args, options: &CleanOptions) -> Result<Vec<InventoryReport>> {
+    let mut inventories = Vec::new();
+    for path in &args.paths {
+        if !path.is_dir() {
+            let (problem, hint) = root_problem(path);
+            usage_bail!("Path {}: {}{}", problem, path.display(), hint);
+        }
+        inventories.push(list_package_cache(path, options)?);
+    }
 
-    for base_path in ROO_TASK_PATHS {
-        let base_dir = Path::new(base_path);
+    if let Some(size_cache) = &options.size_cache {
+        if let Err(err) = size_cache.save() {
+            warn!("{:#}", err);
+        }
+    }
+    Ok(inventories)
+}
 
-        if !base_dir.exists() {
-            if verbose {
-                println!("  Skipping {} (path not found)", base_dir.display());
+/// Expands cache roots containing `*`, `?` or `[` into the directories they
+/// match, in order and without duplicates. Other paths are kept as given,
+/// whether or not they exist; a pattern matching nothing only gets a warning.
+fn expand_path_globs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if !pattern.contains(['*', '?', '[']) {
+            if !expanded.contains(path) {
+                expanded.push(path.clone());
             }
             continue;
         }
 
-        for entry in fs::read_dir(base_dir)
-            .with_context(|| format!("Failed to read Roo tasks directory: {}", base_dir.display()))?
-        {
-            let entry = entry?;
-            let task_path = entry.path();
-
-            if !task_path.is_dir() {
-                continue;
+        let mut matched = false;
+        for entry in glob::glob(&pattern).with_context(|| format!("Invalid path pattern: {}", pattern))? {
+            let entry = match entry {
+                Ok(entry) if entry.is_dir() => entry,
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!("skipping part of {} ({})", pattern, err);
+                    continue;
+                }
+            };
+            matched = true;
+            if !expanded.contains(&entry) {
+                expanded.push(entry);
             }
-            tasks_checked += 1;
+        }
+        if !matched {
+            warn!("{} matches no directories", pattern);
+        }
+    }
+    Ok(expanded)
+}
 
-            let metadata = fs::metadata(&task_path)
-                .with_context(|| format!("Failed to read metadata for task: {}", task_path.display()))?;
-            let modified = metadata.modified()
-                .with_context(|| format!("Failed to get modification time for task: {}", task_path.display()))?;
-            let age = now.duration_since(modified).unwrap_or(Duration::ZERO);
+/// Replaces every path that is a file with the folder containing it, for
+/// `--auto-parent`, keeping the order and dropping duplicates.
+fn parents_of_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let root = if path.is_file() {
+            let parent = parent_folder(path);
+            warn!("{} is a file, cleaning {} instead", path.display(), parent.display());
+            parent
+        } else {
+            path.clone()
+        };
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
 
-            if age < two_months {
-                if verbose {
-                    println!("  Keeping checkpoints for {} (age < 2 months)", task_path.display());
-                }
-                continue;
-            }
+/// The folder containing `path`, `.` for a bare file name.
+fn parent_folder(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
 
-            let checkpoints_path = task_path.join("checkpoints");
-            if !checkpoints_path.exists() {
-                continue;
-            }
+/// Why `path` can't be a cache root: what it is instead, and for a file a
+/// hint at the folder containing it, which is usually what was meant.
+fn root_problem(path: &Path) -> (&'static str, String) {
+    match fs::metadata(path) {
+        Err(_) => ("does not exist", String::new()),
+        Ok(metadata) if metadata.is_file() => (
+            "is a file, not a directory",
+            format!("; did you mean {}? --auto-parent cleans that instead", parent_folder(path).display()),
+        ),
+        Ok(_) => ("is not a directory", String::new()),
+    }
+}
 
-            if dry_run {
-                println!("  Would delete checkpoints: {}", checkpoints_path.display());
-            } else {
-                println!("  Deleting checkpoints: {}", checkpoints_path.display());
-                fs::remove_dir_all(&checkpoints_path).with_context(|| {
-                    format!("Failed to delete checkpoints directory: {}", checkpoints_path.display())
-                })?;
+/// The paths listed in environment variable `name`, separated by `:` (`;` on
+/// Windows) like `PATH`. Empty when it is unset or lists nothing.
+fn paths_from_env(name: &str) -> Vec<PathBuf> {
+    std::env::var_os(name)
+        .map(|value| std::env::split_paths(&value).filter(|path| !path.as_os_str().is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// The cache roots listed in `file` for `--paths-from`, one per line, with
+/// the number of their line. Blank lines and `#` comments are skipped.
+fn paths_from_file(file: &Path) -> Result<Vec<(usize, PathBuf)>> {
+    let contents = fs::read_to_string(file).with_context(|| format!("Failed to read path list: {}", file.display()))?;
+    // Notepad saves a byte order mark in front of the first line
+    Ok(contents
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, path)| (line, PathBuf::from(path)))
+        .collect())
+}
+
+/// Sleeps for `interval`, returning `false` early if Ctrl-C is pressed.
+fn wait_unless_stopped(interval: Duration) -> bool {
+    let deadline = Instant::now() + interval;
+    while !stop_requested() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return true;
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(200)));
+    }
+    false
+}
+
+/// Cleans every cache root once, plus the Roo checkpoints when requested,
+/// and writes the plan file and JSON report.
+fn run_once(args: &Args, options: &CleanOptions, roo_options: Option<&RooOptions>) -> Result<RunOutcome> {
+    let human = options.format == OutputFormat::Human;
+    let summaries = human && !options.report_only_errors;
+    let multiple_roots = args.paths.len() > 1;
+    let mut cache_reports = Vec::new();
+
+    if let Some(threshold) = args.confirm_threshold.filter(|_| !options.dry_run) {
+        confirm_large_deletion(&args.paths, options, threshold)?;
+    }
+
+    let mut package_paths: &[PathBuf] = if args.roo_only { &[] } else { &args.paths };
+    if let Some(jobs) = options.jobs_per_volume.filter(|_| multiple_roots) {
+        cache_reports = clean_volumes_in_parallel(package_paths, options, jobs)?;
+        package_paths = &[];
+    }
+    for path in package_paths {
+        if stop_requested() {
+            break;
+        }
+
+        if path.is_dir() {
+            if multiple_roots {
+                info!("");
             }
-            checkpoints_targets += 1;
+            info!("Cleaning package cache at: {}", path.display());
+            cache_reports.push(clean_and_print(path, options)?);
+            continue;
+        }
+
+        let (problem, hint) = root_problem(path);
+        if multiple_roots {
+            // One bad root among several shouldn't stop the others
+            warn!("skipping {} (path {}{})", path.display(), problem, hint);
+        } else if args.clean_roo_checkpoints {
+            // Still clean the checkpoints that were asked for, but say why nothing else happened
+            warn!("skipping {} (path {}{}), use `cleanpkgcache roo` to clean only Roo checkpoints",
+                path.display(), problem, hint);
+        } else {
+            usage_bail!("Path {}: {}{}", problem, path.display(), hint);
+        }
+    }
+
+    // A template already gives one line per root
+    if summaries && cache_reports.len() > 1 && options.summary_format.is_none() {
+        print_grand_total(&cache_reports, options);
+    }
+
+    // A stale or missing cache only costs time on the next run
+    if let Some(size_cache) = &options.size_cache {
+        if let Err(err) = size_cache.save() {
+            warn!("{:#}", err);
+        }
+    }
+
+    let mut roo_report = None;
+    if let Some(roo_options) = roo_options.filter(|_| !stop_requested()) {
+        roo_report = Some(clean_roo_checkpoints(roo_options)?);
+    }
+
+    let errors: Vec<String> = cache_reports
+        .iter()
+        .flat_map(|report| report.errors.iter().cloned())
+        .collect();
+    let deleted: usize = cache_reports.iter().map(|report| report.versions_deleted).sum();
+    let aborted = cache_reports.iter().any(|report| report.aborted);
+
+    if let Some(plan_file) = &args.plan_out {
+        let planned: Vec<PathBuf> = cache_reports
+            .iter()
+            .flat_map(|report| report.deleted_paths.iter().cloned())
+            .collect();
+        plan::write_plan(plan_file, &args.paths, &planned)?;
+        if summaries {
+            println!("\nWrote plan with {} directories to {}", planned.len(), plan_file.display());
         }
     }
 
-    println!("Roo checkpoints summary:");
-    println!("  Task folders inspected: {}", tasks_checked);
-    if dry_run {
-        println!("  Checkpoints eligible for deletion: {}", checkpoints_targets);
+    if let Some(metrics_file) = &args.metrics_out {
+        write_metrics(metrics_file, &cache_reports, options.dry_run)?;
+    }
+    if let Some(csv_file) = &args.csv_out {
+        csv::append_summary(csv_file, &cache_reports, options.dry_run)?;
+    }
+
+    let report = JsonReport {
+        dry_run: options.dry_run,
+        policy: options.policy,
+        keep: options.keep,
+        package_caches: cache_reports,
+        plan: None,
+        roo_checkpoints: roo_report,
+    };
+    if !human {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    if let Some(hook) = &args.post_run_hook {
+        post_run_hook(hook, &report, args.fail_on_hook_error)?;
+    }
+
+    Ok(RunOutcome { errors, deleted, aborted })
+}
+
+/// Runs the `--post-run-hook`, with the `--format json` report written to a
+/// temporary file for it and the totals in environment variables. A failure
+/// is logged and, with `fail`, returned as an error.
+fn post_run_hook(hook: &str, report: &JsonReport, fail: bool) -> Result<()> {
+    let report_file = std::env::temp_dir().join(format!("cleanpkgcache-report-{}.json", std::process::id()));
+    fs::write(&report_file, serde_json::to_string_pretty(report)?)
+        .with_context(|| format!("Failed to write report for the post-run hook: {}", report_file.display()))?;
+
+    let caches = &report.package_caches;
+    let sum = |count: fn(&CleanReport) -> usize| caches.iter().map(count).sum::<usize>();
+    let mut deleted = sum(|cache| cache.versions_deleted);
+    let mut freed: u64 = caches.iter().map(|cache| cache.bytes_freed).sum();
+    let mut failed = sum(|cache| cache.versions_failed + cache.versions_partially_deleted);
+    if let Some(plan) = &report.plan {
+        deleted += plan.deleted.len();
+        freed += plan.bytes_freed;
+        failed += plan.errors.len();
+    }
+    let env = [
+        ("CLEANPKGCACHE_REPORT", report_file.display().to_string()),
+        ("CLEANPKGCACHE_DRY_RUN", u8::from(report.dry_run).to_string()),
+        ("CLEANPKGCACHE_PACKAGES", sum(|cache| cache.packages_processed).to_string()),
+        ("CLEANPKGCACHE_KEPT", sum(|cache| cache.versions_kept).to_string()),
+        ("CLEANPKGCACHE_DELETED", deleted.to_string()),
+        ("CLEANPKGCACHE_FREED_BYTES", freed.to_string()),
+        ("CLEANPKGCACHE_FAILED", failed.to_string()),
+    ];
+
+    info!("Running post-run hook: {}", hook);
+    let result = run_post_run_hook(hook, &env);
+    let _ = fs::remove_file(&report_file);
+    match result {
+        Ok(()) => Ok(()),
+        Err(reason) if fail => anyhow::bail!("post-run hook failed ({})", reason),
+        Err(reason) => {
+            warn!("post-run hook failed ({})", reason);
+            Ok(())
+        }
+    }
+}
+
+/// Cleans several roots with `--jobs-per-volume`, printing each summary in
+/// the order the roots were given once all are done. Roots that aren't
+/// directories are skipped with a warning, like when cleaning one at a time.
+fn clean_volumes_in_parallel(paths: &[PathBuf], options: &CleanOptions, jobs: usize) -> Result<Vec<CleanReport>> {
+    let mut roots = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            roots.push(path.clone());
+        } else {
+            let (problem, hint) = root_problem(path);
+            warn!("skipping {} (path {}{})", path.display(), problem, hint);
+        }
+    }
+
+    let results = clean_by_volume(&roots, options, jobs, |path| {
+        info!("Cleaning package cache at: {}", path.display());
+    });
+    let human = options.format == OutputFormat::Human && !options.report_only_errors;
+    let mut reports = Vec::new();
+    for (path, result) in results {
+        let report = result?;
+        if human {
+            println!("\nPackage cache at: {}", path.display());
+            print_clean_report(&report, options);
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// Limit set with `--confirm-threshold`: a plain number counts versions,
+/// anything with a unit is a size.
+#[derive(Clone, Copy, Debug)]
+enum Threshold {
+    Versions(usize),
+    Bytes(u64),
+}
+
+fn parse_threshold(value: &str) -> Result<Threshold, String> {
+    match value.trim().parse() {
+        Ok(versions) => Ok(Threshold::Versions(versions)),
+        Err(_) => parse_size(value).map(Threshold::Bytes),
+    }
+}
+
+/// Works out what the run would delete and, if that's more than
+/// `threshold`, asks on the terminal before going ahead. Fails when the
+/// answer isn't yes, or when stdin isn't a terminal to ask on.
+fn confirm_large_deletion(paths: &[PathBuf], options: &CleanOptions, threshold: Threshold) -> Result<()> {
+    let (versions, bytes) = preview_deletions(paths, options)?;
+    let exceeded = match threshold {
+        Threshold::Versions(limit) => versions > limit,
+        Threshold::Bytes(limit) => bytes > limit,
+    };
+    if !exceeded {
+        return Ok(());
+    }
+
+    let totals = format!("{} versions ({}) are about to be deleted", versions, format_bytes(bytes));
+    if !io::stdin().is_terminal() {
+        return Err(anyhow::Error::msg(Aborted(format!(
+            "{}, more than --confirm-threshold allows, and stdin is not a terminal to confirm on",
+            totals
+        ))));
+    }
+
+    print!("{}. Continue? [y/N] ", totals);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !matches!(input.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+        return Err(anyhow::Error::msg(Aborted("aborted, nothing was deleted".to_string())));
+    }
+    Ok(())
+}
+
+/// Reports any deletion errors and picks the exit code. A run that stopped
+/// early exits with [`EXIT_ABORTED`] even if some deletions failed.
+fn finish(outcome: RunOutcome, fail_if_deleted: bool) -> Result<ExitCode> {
+    let stopped = if TIMED_OUT.load(Ordering::SeqCst) {
+        Some("by --max-runtime")
+    } else if stop_requested() {
+        Some("after Ctrl-C")
+    } else if outcome.aborted {
+        Some("at the prompt")
     } else {
-        println!("  Checkpoints deleted: {}", checkpoints_targets);
+        None
+    };
+    if let Some(reason) = stopped {
+        for err in &outcome.errors {
+            error!("{}", err);
+        }
+        warn!("stopped early {}, the summary above only covers what was done", reason);
+        return Ok(ExitCode::from(EXIT_ABORTED));
+    }
+
+    report_errors(&outcome.errors)?;
+    Ok(exit_code(fail_if_deleted, outcome.deleted))
+}
+
+/// With `--fail-if-deleted`, a run that deleted (or would delete) anything
+/// exits with [`EXIT_DELETED`] so monitoring can tell it apart from errors.
+fn exit_code(fail_if_deleted: bool, deleted: usize) -> ExitCode {
+    if fail_if_deleted && deleted > 0 {
+        ExitCode::from(EXIT_DELETED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Failures collected by --keep-going or parallel workers are reported once, at the end.
+fn report_errors(errors: &[String]) -> Result<()> {
+    if !errors.is_empty() {
+        for err in errors {
+            error!("{}", err);
+        }
+        return Err(anyhow::Error::msg(PartialFailure(errors.len())));
     }
 
     Ok(())
 }
 
-#[derive(Debug)]
-struct PackageVersion {
-    name: String,
-    path: PathBuf,
-    modified: SystemTime,
+/// Prints the combined totals after cleaning several cache roots.
+fn print_grand_total(reports: &[CleanReport], options: &CleanOptions) {
+    let packages: usize = reports.iter().map(|report| report.packages_processed).sum();
+    let kept: usize = reports.iter().map(|report| report.versions_kept).sum();
+    let deleted: usize = reports.iter().map(|report| report.versions_deleted).sum();
+    let bytes: u64 = reports.iter().map(|report| report.bytes_freed).sum();
+
+    let title = format!("Grand total ({} cache roots)", reports.len());
+    print_totals(&title, packages, kept, deleted, bytes, options);
+}
+
+/// Sets up `env_logger` for diagnostics on stderr. Informational lines are
+/// shown by default, `--verbose` adds debug lines, and JSON output only shows
+/// warnings so stdout stays clean, as does `--quiet`. `RUST_LOG` overrides
+/// all of this. With `colored`, color codes in the lines are passed through
+/// even when stderr isn't a terminal.
+fn init_logging(verbose: bool, quiet: bool, format: OutputFormat, colored: bool) {
+    let level = if verbose {
+        LevelFilter::Debug
+    } else if quiet || format == OutputFormat::Json {
+        LevelFilter::Warn
+    } else {
+        LevelFilter::Info
+    };
+
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Warn)
+        .filter_module("cleanpkgcache", level)
+        .parse_default_env()
+        .write_style(if colored { WriteStyle::Always } else { WriteStyle::Auto })
+        .format(|buf, record| match record.level() {
+            Level::Error => writeln!(buf, "error: {}", record.args()),
+            Level::Warn => writeln!(buf, "warning: {}", record.args()),
+            _ => writeln!(buf, "{}", record.args()),
+        })
+        .init();
+}
+
+/// Top-level document printed by `--list --format json`.
+#[derive(Debug, Serialize)]
+struct ListReport {
+    inventories: Vec<InventoryReport>,
+}
+
+/// Top-level document printed by `--purge-staged --format json`.
+#[derive(Debug, Serialize)]
+struct PurgeListReport<'a> {
+    purges: &'a [PurgeReport],
+}
+
+/// Top-level document printed by `stats --format json`.
+#[derive(Debug, Serialize)]
+struct StatsReport {
+    keep: KeepCount,
+    caches: Vec<CacheStats>,
+    roo: RooStats,
+}
+
+/// Top-level document printed by `--format json`.
+#[derive(Debug, Serialize)]
+struct JsonReport {
+    dry_run: bool,
+    policy: RetentionPolicy,
+    keep: KeepCount,
+    package_caches: Vec<CleanReport>,
+    plan: Option<PlanReport>,
+    roo_checkpoints: Option<RooReport>,
 }
@@ -0,0 +1,64 @@
+use crate::cleaner::CleanReport;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Writes the results of a run in the Prometheus text format, for the
+/// node_exporter textfile collector. Each cache root gets its own series,
+/// labeled with its path.
+///
+/// The file is written next to `metrics_file` under a temporary name and
+/// then renamed over it, so a collector never reads a partial file.
+pub fn write_metrics(metrics_file: &Path, reports: &[CleanReport], dry_run: bool) -> Result<()> {
+    let mut contents = String::new();
+    per_root(&mut contents, "cleanpkgcache_versions_deleted_total", "Versions deleted in the last run.",
+        reports, |report| report.versions_deleted as u64);
+    per_root(&mut contents, "cleanpkgcache_bytes_freed_total", "Bytes freed in the last run.",
+        reports, |report| report.bytes_freed);
+    per_root(&mut contents, "cleanpkgcache_packages_processed", "Packages processed in the last run.",
+        reports, |report| report.packages_processed as u64);
+
+    header(&mut contents, "cleanpkgcache_dry_run",
+        "Whether the last run was a dry run, so nothing was actually deleted.");
+    let _ = writeln!(contents, "cleanpkgcache_dry_run {}", u8::from(dry_run));
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    header(&mut contents, "cleanpkgcache_last_run_timestamp", "Unix time the last run finished.");
+    let _ = writeln!(contents, "cleanpkgcache_last_run_timestamp {}", now);
+
+    let mut temp_name = metrics_file.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_file = metrics_file.with_file_name(temp_name);
+    fs::write(&temp_file, contents)
+        .with_context(|| format!("Failed to write metrics file: {}", temp_file.display()))?;
+    if let Err(err) = fs::rename(&temp_file, metrics_file) {
+        let _ = fs::remove_file(&temp_file);
+        return Err(err).with_context(|| format!("Failed to replace metrics file: {}", metrics_file.display()));
+    }
+    Ok(())
+}
+
+/// Writes the `# HELP` and `# TYPE` lines of a gauge.
+fn header(contents: &mut String, name: &str, help: &str) {
+    let _ = writeln!(contents, "# HELP {} {}", name, help);
+    let _ = writeln!(contents, "# TYPE {} gauge", name);
+}
+
+/// Writes a gauge with one sample per cache root.
+fn per_root(contents: &mut String, name: &str, help: &str, reports: &[CleanReport], value: fn(&CleanReport) -> u64) {
+    header(contents, name, help);
+    for report in reports {
+        let _ = writeln!(contents, "{}{{path=\"{}\"}} {}", name, escape_label(&report.path), value(report));
+    }
+}
+
+/// Escapes a label value as the text format requires.
+fn escape_label(path: &Path) -> String {
+    path.display()
+        .to_string()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
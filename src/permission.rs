@@ -0,0 +1,67 @@
+use crate::longpath::long_path;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Whether `err`, or anything in its chain of causes, is an I/O error for
+/// missing permissions, e.g. a version directory owned by another user.
+pub fn is_permission_denied(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|err| err.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// Predicts whether deleting `path` would fail for lack of permissions, for
+/// dry runs. Removing an entry takes write access to the directory holding
+/// it, so this checks the parent of `path` and, with `recursive`, every
+/// directory under `path` too. Returns the first directory that isn't
+/// writable.
+///
+/// Only POSIX permission bits are checked; on Windows, where access is
+/// governed by ACLs, a failure only shows once deletion is tried.
+pub fn denied_at(path: &Path, recursive: bool) -> Option<PathBuf> {
+    if cfg!(not(unix)) {
+        return None;
+    }
+    let parent = path.parent()?;
+    if !writable(parent) {
+        return Some(parent.to_path_buf());
+    }
+    if recursive {
+        return denied_below(path);
+    }
+    None
+}
+
+/// The first directory at or under `dir` that can't be listed or changed.
+/// Symlinks are not followed.
+fn denied_below(dir: &Path) -> Option<PathBuf> {
+    if !writable(dir) {
+        return Some(dir.to_path_buf());
+    }
+    let Ok(entries) = fs::read_dir(long_path(dir)) else {
+        return Some(dir.to_path_buf());
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .find_map(|entry| denied_below(&dir.join(entry.file_name())))
+}
+
+/// Whether the current user may add and remove entries in `dir`.
+#[cfg(unix)]
+fn writable(dir: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return true;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string that outlives the call
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn writable(_dir: &Path) -> bool {
+    true
+}
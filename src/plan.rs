@@ -0,0 +1,63 @@
+use crate::guard::relative_to;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Writes a deletion plan: one directory per line, preceded by `#` comments
+/// recording the cache roots it was computed from.
+pub fn write_plan(plan_file: &Path, roots: &[PathBuf], paths: &[PathBuf]) -> Result<()> {
+    let mut contents = String::from("# cleanpkgcache deletion plan\n");
+    for root in roots {
+        contents.push_str(&format!("# root: {}\n", root.display()));
+    }
+    for path in paths {
+        contents.push_str(&format!("{}\n", path.display()));
+    }
+
+    fs::write(plan_file, contents)
+        .with_context(|| format!("Failed to write plan file: {}", plan_file.display()))
+}
+
+/// Reads the directories listed in a plan file, ignoring blank lines and
+/// `#` comments.
+pub fn read_plan(plan_file: &Path) -> Result<Vec<PathBuf>> {
+    let contents = fs::read_to_string(plan_file)
+        .with_context(|| format!("Failed to read plan file: {}", plan_file.display()))?;
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Checks that a planned path is a version directory inside one of the known
+/// cache roots, i.e. at least `root/package/version`, or just `root/version`
+/// when `flat` (for `--group-by`). Paths containing `..` are refused outright.
+///
+/// Both sides are canonicalized first, so a root given with a trailing slash
+/// or in another case on Windows still matches, while a path under a
+/// symlinked package folder that leads out of the root is refused. The
+/// version entry itself isn't resolved, since a symlinked version is only
+/// unlinked.
+pub fn check_within_roots(path: &Path, roots: &[PathBuf], flat: bool) -> Result<(), String> {
+    let min_depth = if flat { 1 } else { 2 };
+    if path.components().any(|component| component == Component::ParentDir) {
+        return Err("path contains '..'".to_string());
+    }
+
+    // Measured from the parent, plus the version entry itself
+    let parent = path.parent().filter(|_| path.file_name().is_some());
+    let inside = parent.is_some_and(|parent| {
+        roots.iter().any(|root| {
+            relative_to(parent, root).is_some_and(|relative| relative.components().count() + 1 >= min_depth)
+        })
+    });
+
+    if inside {
+        Ok(())
+    } else {
+        Err("not a version directory under a known cache root".to_string())
+    }
+}
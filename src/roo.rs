@@ -0,0 +1,350 @@
+use crate::audit::AuditLog;
+use crate::cleaner::OutputFormat;
+use crate::color;
+use crate::duration::{describe_duration, format_timestamp, Cutoff, TimestampZone};
+use crate::longpath::long_path;
+use crate::size::{dir_size, format_bytes};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// VS Code extension IDs of MS Roo Code and the Roo Code extension, the
+/// default for `--roo-extension-id`.
+pub const ROO_EXTENSION_IDS: [&str; 2] = [
+    "microsoftai.ms-roo-cline",
+    "rooveterinaryinc.roo-cline",
+];
+
+/// Default age after which task checkpoints are removed.
+pub const TWO_MONTHS_IN_SECONDS: u64 = 60 * 24 * 60 * 60;
+
+/// Settings that control the Roo checkpoint cleanup.
+#[derive(Debug)]
+pub struct RooOptions {
+    pub dry_run: bool,
+    pub format: OutputFormat,
+    /// Tasks last modified before this get their checkpoints removed: an age
+    /// from `--roo-max-age`, or a fixed date from `--roo-since`.
+    pub cutoff: Cutoff,
+    /// Roo `tasks` folders to scan.
+    pub task_dirs: Vec<PathBuf>,
+    /// Subdirectories of an aged task that get removed, e.g. `checkpoints`.
+    pub subdirs: Vec<String>,
+    /// Remove aged task folders entirely instead of just `subdirs`.
+    pub delete_task: bool,
+    /// Judge a task's age by the newest modification time of anything inside
+    /// it, with `--roo-deep-age`, instead of the task folder's own.
+    pub deep_age: bool,
+    /// Time zone of the timestamps in the keep and delete lines.
+    pub timestamps: TimestampZone,
+    /// Subdirectories modified less than this long ago are kept even in an
+    /// aged task, with `--roo-protect-newer-than`; with `delete_task` such a
+    /// subdirectory keeps its whole task.
+    pub protect_newer_than: Option<Duration>,
+    /// Where removed checkpoint folders are recorded, with `--log-file`.
+    pub audit: Option<Arc<AuditLog>>,
+    /// Print no summary, with `--report-only-errors`.
+    pub report_only_errors: bool,
+}
+
+/// Result of the Roo checkpoint cleanup.
+#[derive(Debug, Serialize)]
+pub struct RooReport {
+    pub tasks_inspected: usize,
+    /// Every removed (or, in a dry run, removable) task subdirectory.
+    pub deleted: Vec<PathBuf>,
+    /// How many of each subdirectory name were removed.
+    pub deleted_per_subdir: BTreeMap<String, usize>,
+    /// Whole task folders removed with `delete_task`.
+    pub tasks_deleted: usize,
+    /// Total size of everything in `deleted`, measured before removal.
+    pub bytes_freed: u64,
+}
+
+/// Checks that a `--roo-subdirs` entry is a plain directory name, so it
+/// can't point outside the task folder.
+pub fn parse_subdir_name(value: &str) -> Result<String, String> {
+    let mut components = Path::new(value).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(value.to_string()),
+        _ => Err(format!("'{}' must be a single directory name like checkpoints", value)),
+    }
+}
+
+/// Checks that a `--roo-extension-id` is a plain `publisher.name` like
+/// `saoudrizwan.claude-dev`, so it can't point outside the global storage.
+pub fn parse_extension_id(value: &str) -> Result<String, String> {
+    let plain = parse_subdir_name(value).is_ok();
+    match value.split_once('.') {
+        Some((publisher, name)) if plain && !publisher.is_empty() && !name.is_empty() => Ok(value.to_string()),
+        _ => Err(format!("'{}' must be an extension ID like publisher.name", value)),
+    }
+}
+
+/// Locates the Roo `tasks` folders under VS Code's global storage for the
+/// current user: `%APPDATA%\Code\User\globalStorage` on Windows,
+/// `~/Library/Application Support/Code/User/globalStorage` on macOS and
+/// `~/.config/Code/User/globalStorage` on Linux. One folder is returned for
+/// each of `extension_ids`, normally [`ROO_EXTENSION_IDS`].
+pub fn roo_task_paths<S: AsRef<str>>(extension_ids: &[S]) -> Vec<PathBuf> {
+    let Some(config_dir) = dirs::config_dir() else {
+        return Vec::new();
+    };
+    let global_storage = config_dir.join("Code").join("User").join("globalStorage");
+    extension_task_paths(&global_storage, extension_ids)
+}
+
+/// The `tasks` folder of each of `extension_ids` under `global_storage`.
+pub fn extension_task_paths<S: AsRef<str>>(global_storage: &Path, extension_ids: &[S]) -> Vec<PathBuf> {
+    extension_ids
+        .iter()
+        .map(|id| global_storage.join(id.as_ref()).join("tasks"))
+        .collect()
+}
+
+/// Deletes the `options.subdirs` folders (normally just `checkpoints`) of
+/// every Roo task under `options.task_dirs` last modified before
+/// `options.cutoff`. With `options.delete_task` the whole task folder goes
+/// instead; only immediate children of a tasks directory are ever removed.
+pub fn clean_roo_checkpoints(options: &RooOptions) -> Result<RooReport> {
+    let RooOptions { dry_run, format, cutoff, .. } = *options;
+    let (cutoff_text, keep_reason) = match cutoff {
+        Cutoff::Age(max_age) => {
+            let max_age = describe_duration(max_age);
+            (format!("older than {}", max_age), format!("age < {}", max_age))
+        }
+        Cutoff::Date(since) => {
+            let since = format_timestamp(since, options.timestamps);
+            (format!("not modified since {}", since), format!("modified since {}", since))
+        }
+    };
+    let now = SystemTime::now();
+    let mut tasks_checked = 0;
+    let mut deleted = Vec::new();
+    let mut tasks_deleted = 0;
+    let mut bytes_freed = 0;
+    let mut deleted_per_subdir: BTreeMap<String, usize> = if options.delete_task {
+        BTreeMap::new()
+    } else {
+        options.subdirs.iter().map(|name| (name.clone(), 0)).collect()
+    };
+    let human = format == OutputFormat::Human && !options.report_only_errors;
+
+    info!("\nCleaning Roo checkpoints {}...", cutoff_text);
+
+    for base_dir in &options.task_dirs {
+        let base_dir = base_dir.as_path();
+
+        if !long_path(base_dir).exists() {
+            debug!("  Skipping {} (path not found)", base_dir.display());
+            continue;
+        }
+
+        let mut task_names = fs::read_dir(long_path(base_dir))
+            .and_then(|entries| entries.map(|entry| entry.map(|entry| entry.file_name())).collect::<io::Result<Vec<_>>>())
+            .with_context(|| format!("Failed to read Roo tasks directory: {}", base_dir.display()))?;
+        // Sorted so that repeated runs list tasks in the same order
+        task_names.sort();
+
+        for task_name in task_names {
+            let task_path = base_dir.join(&task_name);
+
+            if !long_path(&task_path).is_dir() {
+                continue;
+            }
+            tasks_checked += 1;
+
+            let metadata = fs::metadata(long_path(&task_path))
+                .with_context(|| format!("Failed to read metadata for task: {}", task_path.display()))?;
+            let mut modified = metadata.modified()
+                .with_context(|| format!("Failed to get modification time for task: {}", task_path.display()))?;
+            if options.deep_age {
+                modified = modified.max(newest_mtime(&task_path));
+            }
+            // A task modified exactly at a --roo-since date counts as touched since then
+            let recent = match cutoff {
+                Cutoff::Age(max_age) => now.duration_since(modified).unwrap_or(Duration::ZERO) < max_age,
+                Cutoff::Date(since) => modified >= since,
+            };
+
+            let modified_text = format_timestamp(modified, options.timestamps);
+            if recent {
+                debug!("  Keeping {} (modified {}, {})", task_path.display(), modified_text, keep_reason);
+                continue;
+            }
+
+            if options.delete_task {
+                let fresh = options.subdirs.iter()
+                    .map(|name| task_path.join(name))
+                    .find(|path| long_path(path).exists() && protected(path, options.protect_newer_than, now));
+                if let Some(fresh) = fresh {
+                    debug!("  Keeping {} ({} is recent)", task_path.display(), fresh.display());
+                    continue;
+                }
+                // Never the tasks directory itself or anything deeper than its children
+                let mut components = Path::new(&task_name).components();
+                if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+                    warn!("skipping task {} (not a plain folder name)", task_path.display());
+                    continue;
+                }
+
+                let size = measure(&task_path);
+                if let Some(audit) = &options.audit {
+                    let action = if dry_run { "would-delete-task" } else { "deleted-task" };
+                    audit.record(action, size, &task_path);
+                }
+
+                if dry_run {
+                    info!("  {} {} (modified {})", color::would_delete("Would delete task folder:"), task_path.display(),
+                        modified_text);
+                } else {
+                    info!("  {} {} (modified {})", color::deleted("Deleting task folder:"), task_path.display(),
+                        modified_text);
+                    fs::remove_dir_all(long_path(&task_path)).with_context(|| {
+                        format!("Failed to delete task folder: {}", task_path.display())
+                    })?;
+                }
+                tasks_deleted += 1;
+                bytes_freed += size.unwrap_or(0);
+                deleted.push(task_path);
+                continue;
+            }
+
+            for name in &options.subdirs {
+                let subdir_path = task_path.join(name);
+                if !long_path(&subdir_path).exists() {
+                    continue;
+                }
+                if protected(&subdir_path, options.protect_newer_than, now) {
+                    debug!("  Keeping {} (modified recently)", subdir_path.display());
+                    continue;
+                }
+
+                let size = measure(&subdir_path);
+                if let Some(audit) = &options.audit {
+                    let action = if dry_run { format!("would-delete-{}", name) } else { format!("deleted-{}", name) };
+                    audit.record(&action, size, &subdir_path);
+                }
+
+                if dry_run {
+                    info!("  {} {} (task modified {})", color::would_delete(&format!("Would delete {}:", name)),
+                        subdir_path.display(), modified_text);
+                } else {
+                    info!("  {} {} (task modified {})", color::deleted(&format!("Deleting {}:", name)),
+                        subdir_path.display(), modified_text);
+                    fs::remove_dir_all(long_path(&subdir_path)).with_context(|| {
+                        format!("Failed to delete {} directory: {}", name, subdir_path.display())
+                    })?;
+                }
+                *deleted_per_subdir.entry(name.clone()).or_insert(0) += 1;
+                bytes_freed += size.unwrap_or(0);
+                deleted.push(subdir_path);
+            }
+        }
+    }
+
+    if human {
+        println!("Roo checkpoints summary:");
+        println!("  Task folders inspected: {}", tasks_checked);
+        if options.delete_task {
+            if dry_run {
+                println!("  Task folders eligible for deletion: {}", tasks_deleted);
+            } else {
+                println!("  Task folders deleted: {}", tasks_deleted);
+            }
+        }
+        for (name, count) in &deleted_per_subdir {
+            if dry_run {
+                println!("  {} folders eligible for deletion: {}", name, count);
+            } else {
+                println!("  {} folders deleted: {}", name, count);
+            }
+        }
+        if dry_run {
+            println!("  Would free {}", format_bytes(bytes_freed));
+        } else {
+            println!("  Freed {}", format_bytes(bytes_freed));
+        }
+    }
+
+    Ok(RooReport {
+        tasks_inspected: tasks_checked,
+        deleted,
+        deleted_per_subdir,
+        tasks_deleted,
+        bytes_freed,
+    })
+}
+
+/// The newest modification time of anything under `dir`, or `UNIX_EPOCH`
+/// when it is empty. Symlinks are not followed, and entries that can't be
+/// read are skipped with a warning.
+fn newest_mtime(dir: &Path) -> SystemTime {
+    let mut newest = UNIX_EPOCH;
+    let entries = match fs::read_dir(long_path(dir)) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("skipping unreadable directory {}: {}", dir.display(), err);
+            return newest;
+        }
+    };
+
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => dir.join(entry.file_name()),
+            Err(err) => {
+                warn!("skipping unreadable entry in {}: {}", dir.display(), err);
+                continue;
+            }
+        };
+        let metadata = match fs::symlink_metadata(long_path(&path)) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                warn!("skipping unreadable file {}: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        if let Ok(modified) = metadata.modified() {
+            newest = newest.max(modified);
+        }
+        if metadata.is_dir() {
+            newest = newest.max(newest_mtime(&path));
+        }
+    }
+
+    newest
+}
+
+/// Whether `path` was modified less than `protect` ago. A modification time
+/// that can't be read protects it too, with a warning.
+fn protected(path: &Path, protect: Option<Duration>, now: SystemTime) -> bool {
+    let Some(protect) = protect else {
+        return false;
+    };
+    match fs::metadata(long_path(path)).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => now.duration_since(modified).unwrap_or(Duration::ZERO) < protect,
+        Err(err) => {
+            warn!("keeping {}: could not read its modification time: {}", path.display(), err);
+            true
+        }
+    }
+}
+
+/// Measures a folder about to be removed. A failure only costs its share of
+/// the freed total, with a warning.
+fn measure(path: &Path) -> Option<u64> {
+    match dir_size(path) {
+        Ok(size) => Some(size),
+        Err(err) => {
+            warn!("could not measure {}: {:#}", path.display(), err);
+            None
+        }
+    }
+}
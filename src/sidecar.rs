@@ -0,0 +1,61 @@
+use crate::longpath::long_path;
+use log::warn;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+
+/// A `--version-from` source: a JSON file inside each version directory and
+/// the field in it that holds the real version or build date.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionSource {
+    /// File name relative to the version directory, e.g. `metadata.json`.
+    pub file: String,
+    /// Field to read; dots descend into nested objects, e.g. `build.date`.
+    pub field: String,
+}
+
+/// Parses a `--version-from` value like `metadata.json:version`. The file
+/// must be a plain name so it can't point outside the version directory.
+pub fn parse_version_source(value: &str) -> Result<VersionSource, String> {
+    let Some((file, field)) = value.rsplit_once(':') else {
+        return Err(format!("'{}' must look like FILE:FIELD, e.g. metadata.json:version", value));
+    };
+    let mut components = Path::new(file).components();
+    if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+        return Err(format!("'{}' must be a single file name like metadata.json", file));
+    }
+    if field.is_empty() || field.split('.').any(str::is_empty) {
+        return Err(format!("'{}' must name a field like version or build.date", field));
+    }
+    Ok(VersionSource { file: file.to_string(), field: field.to_string() })
+}
+
+/// Reads `source.field` from the sidecar file in `dir`. Returns `None` when
+/// the file or field is missing, so the caller falls back to the directory
+/// name and timestamp; an unreadable or malformed file also warns.
+pub fn read_field(dir: &Path, source: &VersionSource) -> Option<String> {
+    let path = dir.join(&source.file);
+    let text = match fs::read_to_string(long_path(&path)) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            warn!("could not read {}: {}", path.display(), err);
+            return None;
+        }
+    };
+    let json: Value = match serde_json::from_str(&text) {
+        Ok(json) => json,
+        Err(err) => {
+            warn!("could not parse {}: {}", path.display(), err);
+            return None;
+        }
+    };
+
+    let value = source.field.split('.').try_fold(&json, |value, key| value.get(key))?;
+    match value {
+        Value::String(text) => Some(text.trim().to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,120 @@
+use crate::longpath::long_path;
+use anyhow::Result;
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const KIB: f64 = 1024.0;
+
+/// Parses a size such as `50GiB`, `500MB`, `1.5TiB` or a plain byte count.
+///
+/// Binary units (`KiB`, `MiB`, `GiB`, `TiB`, or the short `K`/`M`/`G`/`T`)
+/// are powers of 1024; decimal units (`KB`, `MB`, `GB`, `TB`) are powers of 1000.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("'{}' must start with a number (expected e.g. 50GiB, 500MB)", value))?;
+
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kib" => KIB,
+        "m" | "mib" => KIB * KIB,
+        "g" | "gib" => KIB * KIB * KIB,
+        "t" | "tib" => KIB * KIB * KIB * KIB,
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "gb" => 1e9,
+        "tb" => 1e12,
+        other => {
+            return Err(format!(
+                "unknown size unit '{}' in '{}' (use B, KiB, MiB, GiB, TiB, KB, MB, GB or TB)",
+                other, value
+            ))
+        }
+    };
+
+    let bytes = number * multiplier;
+    if !bytes.is_finite() || bytes > u64::MAX as f64 {
+        return Err(format!("'{}' is too large", value));
+    }
+    Ok(bytes as u64)
+}
+
+/// Recursively sums the size of all files under `path`.
+///
+/// Each subdirectory is read as its own task on the current rayon thread
+/// pool, so large trees are measured in parallel, as many at once as the
+/// pool has threads. Only the directories still waiting to be read are held
+/// in memory, never a list of the files, and deep trees don't grow the
+/// stack. Entries that cannot be read are skipped with a warning so a
+/// single locked file doesn't abort the whole run. Symlinks are not followed.
+pub fn dir_size(path: &Path) -> Result<u64> {
+    let total = AtomicU64::new(0);
+    rayon::scope(|scope| add_dir_size(scope, path.to_path_buf(), &total));
+    Ok(total.into_inner())
+}
+
+/// Adds the sizes of the files directly in `dir` to `total`, and spawns a
+/// task for each directory in it.
+fn add_dir_size<'scope>(scope: &rayon::Scope<'scope>, dir: PathBuf, total: &'scope AtomicU64) {
+    let entries = match fs::read_dir(long_path(&dir)) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("skipping unreadable directory {}: {}", dir.display(), err);
+            return;
+        }
+    };
+
+    let mut bytes = 0;
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("skipping unreadable entry in {}: {}", dir.display(), err);
+                continue;
+            }
+        };
+        let entry_path = dir.join(entry.file_name());
+
+        // Like symlink_metadata, and free on Windows where listing already returns it
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                warn!("skipping unreadable file {}: {}", entry_path.display(), err);
+                continue;
+            }
+        };
+
+        if metadata.is_dir() {
+            scope.spawn(move |scope| add_dir_size(scope, entry_path, total));
+        } else {
+            bytes += metadata.len();
+        }
+    }
+    total.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Formats a byte count using binary units (KiB, MiB, GiB, TiB).
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,106 @@
+use crate::longpath::long_path;
+use crate::size::dir_size;
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Default location of the size cache: `sizes.json` under the user's cache
+/// directory, e.g. `~/.cache/cleanpkgcache` or `%LOCALAPPDATA%\cleanpkgcache`.
+pub fn default_size_cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|cache| cache.join("cleanpkgcache").join("sizes.json"))
+}
+
+/// Directory sizes remembered across runs for `--size-cache`, keyed by path
+/// and only trusted while the directory's modification time is unchanged.
+///
+/// A directory's mtime only changes when entries directly inside it are
+/// added, removed or renamed, so a file rewritten deeper down goes unnoticed.
+/// That fits version directories, which are written once and then left alone.
+#[derive(Debug)]
+pub struct SizeCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct Entry {
+    /// Modification time when measured, in nanoseconds since the Unix epoch.
+    mtime: u64,
+    size: u64,
+}
+
+impl SizeCache {
+    /// Reads the cache at `path`. A missing file is an empty cache, and so is
+    /// an unreadable one, with a warning; it is rewritten on [`SizeCache::save`].
+    pub fn load(path: &Path) -> SizeCache {
+        let entries = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                warn!("ignoring unreadable size cache {}: {}", path.display(), err);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        SizeCache {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// An empty cache that lives only as long as the run, for sharing sizes
+    /// between passes over the same directories. It has nowhere to be saved.
+    pub fn in_memory() -> SizeCache {
+        SizeCache {
+            path: PathBuf::new(),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The size of the directory at `path`: the cached value if its mtime
+    /// still matches, otherwise measured with [`dir_size`] and remembered.
+    pub fn dir_size(&self, path: &Path) -> Result<u64> {
+        let Some(mtime) = mtime(path) else {
+            return dir_size(path);
+        };
+
+        let cached = self.lock().get(path).copied();
+        if let Some(entry) = cached.filter(|entry| entry.mtime == mtime) {
+            return Ok(entry.size);
+        }
+
+        let size = dir_size(path)?;
+        self.lock().insert(path.to_path_buf(), Entry { mtime, size });
+        Ok(size)
+    }
+
+    /// Writes the cache back to disk, dropping entries for directories that
+    /// no longer exist.
+    pub fn save(&self) -> Result<()> {
+        let mut entries = self.lock();
+        entries.retain(|path, _| long_path(path).is_dir());
+        let contents = serde_json::to_string(&*entries)?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create size cache directory: {}", parent.display()))?;
+        }
+        fs::write(&self.path, contents)
+            .with_context(|| format!("Failed to write size cache: {}", self.path.display()))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Entry>> {
+        // A panicked worker can't leave the map half-updated, so keep using it
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Modification time of `path` in nanoseconds since the Unix epoch.
+fn mtime(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(long_path(path)).and_then(|metadata| metadata.modified()).ok()?;
+    u64::try_from(modified.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_nanos()).ok()
+}
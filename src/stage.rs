@@ -0,0 +1,135 @@
+use crate::cleaner::CleanOptions;
+use crate::color;
+use crate::longpath::long_path;
+use crate::size::{dir_size, format_bytes};
+use anyhow::{Context, Result};
+use log::info;
+use serde::Serialize;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Appended to a version's name by `--stage` instead of deleting it.
+pub const STAGED_SUFFIX: &str = ".to-delete";
+
+/// Whether `name` is that of a version staged for deletion. Such entries
+/// are never taken for versions, so staging doesn't change what is kept.
+pub fn is_staged(name: &OsStr) -> bool {
+    name.to_string_lossy().ends_with(STAGED_SUFFIX)
+}
+
+/// Renames the version at `path` to its staged name next to it, failing
+/// rather than replacing an earlier staged copy of the same name.
+pub fn stage(path: &Path) -> io::Result<PathBuf> {
+    let mut name = path.file_name().map(OsStr::to_os_string).unwrap_or_default();
+    name.push(STAGED_SUFFIX);
+    let staged = path.with_file_name(&name);
+    if fs::symlink_metadata(long_path(&staged)).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", staged.display())));
+    }
+    fs::rename(long_path(path), long_path(&staged))?;
+    Ok(staged)
+}
+
+/// Result of `--purge-staged` for one cache root.
+#[derive(Debug, Serialize)]
+pub struct PurgeReport {
+    pub path: PathBuf,
+    /// Every removed (or, in a dry run, removable) staged version.
+    pub purged: Vec<PathBuf>,
+    pub bytes_freed: u64,
+    pub errors: Vec<String>,
+}
+
+/// Permanently removes the versions an earlier `--stage` run renamed under
+/// `cache_path`. Only entries at the version level are looked at: below
+/// `options.package_depth` package levels, or directly in the root with
+/// `--group-by`. Symlinks are removed, never followed.
+pub fn purge_staged(cache_path: &Path, options: &CleanOptions) -> Result<PurgeReport> {
+    let mut staged = Vec::new();
+    let depth = if options.group_by.is_some() { 1 } else { options.package_depth + 1 };
+    find_staged(cache_path, depth, &mut staged)?;
+    staged.sort();
+
+    let mut report = PurgeReport {
+        path: cache_path.to_path_buf(),
+        purged: Vec::new(),
+        bytes_freed: 0,
+        errors: Vec::new(),
+    };
+    for path in staged {
+        let result = fs::symlink_metadata(long_path(&path))
+            .with_context(|| format!("Failed to read metadata for: {}", path.display()))
+            .and_then(|metadata| purge(&path, &metadata, options));
+        match result {
+            Ok(bytes) => {
+                if let Some(audit) = &options.audit {
+                    audit.record(if options.dry_run { "would-purge" } else { "purged" }, Some(bytes), &path);
+                }
+                report.bytes_freed += bytes;
+                report.purged.push(path);
+            }
+            Err(err) if options.keep_going => report.errors.push(format!("{:#}", err)),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(report)
+}
+
+/// Collects the staged entries `depth` levels below `dir`.
+fn find_staged(dir: &Path, depth: usize, staged: &mut Vec<PathBuf>) -> Result<()> {
+    let names = fs::read_dir(long_path(dir))
+        .and_then(|entries| entries.map(|entry| entry.map(|entry| entry.file_name())).collect::<io::Result<Vec<OsString>>>())
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+    for name in names {
+        let path = dir.join(&name);
+        if depth == 1 {
+            if is_staged(&name) {
+                staged.push(path);
+            }
+        } else if fs::symlink_metadata(long_path(&path)).is_ok_and(|metadata| metadata.is_dir()) {
+            find_staged(&path, depth - 1, staged)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes one staged entry (or only measures it in a dry run) and returns
+/// its size.
+fn purge(path: &Path, metadata: &fs::Metadata, options: &CleanOptions) -> Result<u64> {
+    let bytes = if metadata.is_dir() {
+        dir_size(path)?
+    } else if metadata.is_symlink() {
+        0
+    } else {
+        metadata.len()
+    };
+
+    if options.dry_run {
+        info!("  {} {}", color::would_delete("Would purge:"), path.display());
+        return Ok(bytes);
+    }
+    info!("  {} {}", color::deleted("Purging:"), path.display());
+    let result = if metadata.is_dir() {
+        (options.deleter)(&long_path(path))
+    } else {
+        // Directory links on Windows need remove_dir, everything else remove_file
+        fs::remove_file(long_path(path)).or_else(|err| fs::remove_dir(long_path(path)).map_err(|_| err))
+    };
+    result.with_context(|| format!("Failed to purge staged version: {}", path.display()))?;
+    Ok(bytes)
+}
+
+/// Prints the human summary of a [`PurgeReport`].
+pub fn print_purge_report(report: &PurgeReport, dry_run: bool) {
+    println!("\nPurge summary for {}:", report.path.display());
+    if dry_run {
+        println!("  Staged versions that would be purged: {}", report.purged.len());
+        println!("  Would free {}", format_bytes(report.bytes_freed));
+    } else {
+        println!("  Staged versions purged: {}", report.purged.len());
+        println!("  Freed {}", format_bytes(report.bytes_freed));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::cleaner::{list_package_cache, preview_deletions, CleanOptions};
+use crate::color;
+use crate::longpath::long_path;
+use crate::roo::{clean_roo_checkpoints, RooOptions};
+use crate::size::{dir_size, format_bytes};
+use crate::sizecache::SizeCache;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Overview of one package cache root for `cleanpkgcache stats`.
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub path: PathBuf,
+    pub total_bytes: u64,
+    pub packages: usize,
+    pub versions: usize,
+    pub average_versions_per_package: f64,
+    /// Oldest and newest `--time-basis` timestamps of any version, in RFC 3339.
+    pub oldest_version: Option<String>,
+    pub newest_version: Option<String>,
+    /// What a run with the same settings would delete.
+    pub reclaimable_versions: usize,
+    pub reclaimable_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+/// Overview of the Roo task folders for `cleanpkgcache stats`.
+#[derive(Debug, Default, Serialize)]
+pub struct RooStats {
+    pub tasks: usize,
+    pub total_bytes: u64,
+    /// Checkpoint (or task) folders a cleanup with the same settings would remove.
+    pub reclaimable_folders: usize,
+    pub reclaimable_bytes: u64,
+}
+
+/// Measures a cache root and works out what cleaning it with `options`
+/// would free, without deleting anything.
+pub fn cache_stats(cache_path: &Path, options: &CleanOptions) -> Result<CacheStats> {
+    // Sizes measured for the inventory are reused by the preview
+    let options = CleanOptions {
+        size_cache: options.size_cache.clone().or_else(|| Some(Arc::new(SizeCache::in_memory()))),
+        ..options.clone()
+    };
+    let inventory = list_package_cache(cache_path, &options)?;
+    let (reclaimable_versions, reclaimable_bytes) = preview_deletions(&[cache_path.to_path_buf()], &options)?;
+
+    // RFC 3339 timestamps in UTC all have the same width, so they sort as text
+    let timestamps = || inventory.packages.iter().flat_map(|package| &package.versions).map(|version| &version.timestamp);
+    let versions = timestamps().count();
+    let packages = inventory.packages.len();
+
+    Ok(CacheStats {
+        path: inventory.path.clone(),
+        total_bytes: inventory.total_bytes,
+        packages,
+        versions,
+        average_versions_per_package: if packages == 0 { 0.0 } else { versions as f64 / packages as f64 },
+        oldest_version: timestamps().min().cloned(),
+        newest_version: timestamps().max().cloned(),
+        reclaimable_versions,
+        reclaimable_bytes,
+        errors: inventory.errors,
+    })
+}
+
+/// Measures the Roo task folders under `options.task_dirs` and works out
+/// what a checkpoint cleanup with `options` would free, without deleting
+/// anything.
+pub fn roo_stats(options: &RooOptions) -> Result<RooStats> {
+    let mut stats = RooStats::default();
+    for base_dir in options.task_dirs.iter().filter(|dir| long_path(dir).is_dir()) {
+        for entry in fs::read_dir(long_path(base_dir))
+            .with_context(|| format!("Failed to read Roo tasks directory: {}", base_dir.display()))?
+        {
+            let task_path = base_dir.join(entry?.file_name());
+            if long_path(&task_path).is_dir() {
+                stats.tasks += 1;
+                stats.total_bytes += dir_size(&task_path)?;
+            }
+        }
+    }
+
+    let preview = clean_roo_checkpoints(&RooOptions {
+        dry_run: true,
+        format: options.format,
+        cutoff: options.cutoff,
+        task_dirs: options.task_dirs.clone(),
+        subdirs: options.subdirs.clone(),
+        delete_task: options.delete_task,
+        deep_age: options.deep_age,
+        timestamps: options.timestamps,
+        protect_newer_than: options.protect_newer_than,
+        audit: None,
+        report_only_errors: true,
+    })?;
+    stats.reclaimable_folders = preview.deleted.len();
+    stats.reclaimable_bytes = preview.bytes_freed;
+    Ok(stats)
+}
+
+/// Prints the human form of `cleanpkgcache stats`: each cache root, the Roo
+/// task folders, then the totals.
+pub fn print_stats(caches: &[CacheStats], roo: &RooStats, options: &CleanOptions) {
+    for cache in caches {
+        println!("{}", color::bold(&format!("Package cache at: {}", cache.path.display())));
+        println!("  Total size: {}", format_bytes(cache.total_bytes));
+        println!("  Packages: {}", cache.packages);
+        println!("  Versions: {} ({:.1} per package)", cache.versions, cache.average_versions_per_package);
+        if let (Some(oldest), Some(newest)) = (&cache.oldest_version, &cache.newest_version) {
+            println!("  Oldest version: {}", oldest);
+            println!("  Newest version: {}", newest);
+        }
+        println!("  Reclaimable (keeping latest {}): {} versions ({})", options.keep, cache.reclaimable_versions,
+            format_bytes(cache.reclaimable_bytes));
+        println!();
+    }
+
+    println!("{}", color::bold("Roo tasks:"));
+    println!("  Task folders: {} ({})", roo.tasks, format_bytes(roo.total_bytes));
+    println!("  Reclaimable folders: {} ({})", roo.reclaimable_folders, format_bytes(roo.reclaimable_bytes));
+
+    let total: u64 = caches.iter().map(|cache| cache.total_bytes).sum::<u64>() + roo.total_bytes;
+    let reclaimable: u64 = caches.iter().map(|cache| cache.reclaimable_bytes).sum::<u64>() + roo.reclaimable_bytes;
+    println!("\nTotal:");
+    println!("  Size: {}", format_bytes(total));
+    println!("  Reclaimable: {}", format_bytes(reclaimable));
+}
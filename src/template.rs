@@ -0,0 +1,105 @@
+use crate::cleaner::CleanReport;
+use crate::size::format_bytes;
+
+/// Placeholders understood by [`SummaryTemplate`], with what they stand for.
+pub const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("path", "the cache root"),
+    ("mode", "dry-run or delete"),
+    ("packages", "packages processed"),
+    ("kept", "versions kept"),
+    ("deleted", "versions deleted, or that would be in a dry run"),
+    ("would-delete", "the same as deleted, for dry-run templates"),
+    ("freed", "space freed, e.g. 1.5 GiB"),
+    ("freed-bytes", "space freed in bytes"),
+    ("failed", "versions that failed to delete"),
+    ("pinned", "versions kept by --pin"),
+    ("duplicates", "duplicates deleted by --dedupe"),
+    ("excess", "packages with more versions than they keep"),
+];
+
+/// A `--summary-format` template such as `"{deleted} deleted, {freed} freed"`,
+/// printed instead of the summary block for each cache root. `{{` and `}}`
+/// stand for literal braces.
+#[derive(Clone, Debug)]
+pub struct SummaryTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Text(String),
+    Placeholder(&'static str),
+}
+
+impl SummaryTemplate {
+    /// Parses a template, refusing unknown placeholders and unbalanced braces.
+    pub fn parse(template: &str) -> Result<SummaryTemplate, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed '{{{}' (write '{{{{' for a literal brace)", name)),
+                        }
+                    }
+                    let Some(&(known, _)) = PLACEHOLDERS.iter().find(|(known, _)| *known == name) else {
+                        let names: Vec<&str> = PLACEHOLDERS.iter().map(|(name, _)| *name).collect();
+                        return Err(format!("unknown placeholder '{{{}}}' (expected one of: {})", name, names.join(", ")));
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Placeholder(known));
+                }
+                '}' => return Err("unmatched '}' (write '}}' for a literal brace)".to_string()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(SummaryTemplate { parts })
+    }
+
+    /// Fills in the placeholders from the report of one cache root.
+    pub fn render(&self, report: &CleanReport, dry_run: bool) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Placeholder(name) => value(name, report, dry_run),
+            })
+            .collect()
+    }
+}
+
+fn value(name: &str, report: &CleanReport, dry_run: bool) -> String {
+    match name {
+        "path" => report.path.display().to_string(),
+        "mode" => if dry_run { "dry-run" } else { "delete" }.to_string(),
+        "packages" => report.packages_processed.to_string(),
+        "kept" => report.versions_kept.to_string(),
+        "deleted" | "would-delete" => report.versions_deleted.to_string(),
+        "freed" => format_bytes(report.bytes_freed),
+        "freed-bytes" => report.bytes_freed.to_string(),
+        "failed" => report.versions_failed.to_string(),
+        "pinned" => report.versions_pinned.to_string(),
+        "duplicates" => report.duplicates_deleted.to_string(),
+        "excess" => report.packages_with_excess_versions.to_string(),
+        _ => unreachable!("placeholders are checked when parsing"),
+    }
+}
@@ -0,0 +1,113 @@
+use crate::cleaner::{CleanReport, VersionReport};
+use crate::color;
+use crate::size::format_bytes;
+use std::path::Path;
+
+/// Characters `--tree` draws the cache hierarchy with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeStyle {
+    /// Box-drawing characters: `├─`, `└─` and `│`.
+    Unicode,
+    /// Plain `+-`, `` `- `` and `|`, for terminals that can't show the above.
+    Ascii,
+}
+
+impl TreeStyle {
+    /// Unicode unless the locale says the terminal isn't UTF-8. Windows
+    /// consoles are written to as UTF-16, so only other systems check.
+    pub fn detect() -> TreeStyle {
+        if cfg!(windows) {
+            return TreeStyle::Unicode;
+        }
+        // The first of these that is set decides, as for any C program
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            TreeStyle::Unicode
+        } else {
+            TreeStyle::Ascii
+        }
+    }
+
+    /// Prefixes for a child that has later siblings, the last child, and
+    /// the lines below each of them.
+    fn branches(self) -> [&'static str; 4] {
+        match self {
+            TreeStyle::Unicode => ["├─ ", "└─ ", "│  ", "   "],
+            TreeStyle::Ascii => ["+- ", "`- ", "|  ", "   "],
+        }
+    }
+}
+
+/// A directory of the tree, or a version annotated with its decision.
+#[derive(Default)]
+struct Node {
+    /// In the order first seen, so versions stay in the report's order.
+    children: Vec<(String, Node)>,
+    decision: Option<String>,
+}
+
+impl Node {
+    fn child(&mut self, name: &str) -> &mut Node {
+        let index = match self.children.iter().position(|(child, _)| child == name) {
+            Some(index) => index,
+            None => {
+                self.children.push((name.to_string(), Node::default()));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[index].1
+    }
+}
+
+/// Prints the packages and versions of `report` as a tree below its root,
+/// each version marked KEEP or DELETE with its size. Backs `--tree`, which
+/// needs the kept versions in the report.
+pub fn print_tree(report: &CleanReport, style: TreeStyle) {
+    let mut root = Node::default();
+    for package in &report.packages {
+        let mut add = |version: &VersionReport, decision: String| {
+            // Versions are found by joining onto the root, so it is a plain prefix
+            let relative = version.path.strip_prefix(&report.path).unwrap_or(Path::new(&version.name));
+            let mut parts: Vec<String> = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            // Flat --group-by caches have no package directory to show
+            if parts.len() < 2 {
+                parts.insert(0, package.name.clone());
+            }
+            let mut node = &mut root;
+            for part in &parts {
+                node = node.child(part);
+            }
+            node.decision = Some(decision);
+        };
+        for version in &package.kept {
+            add(version, format!("{} {}", color::kept("KEEP"), format_bytes(version.size_bytes)));
+        }
+        for version in &package.deleted {
+            add(version, format!("{} {}", color::would_delete("DELETE"), format_bytes(version.size_bytes)));
+        }
+    }
+
+    println!("{}", report.path.display());
+    print_children(&root, "", style);
+}
+
+fn print_children(node: &Node, indent: &str, style: TreeStyle) {
+    let [branch, last_branch, line, space] = style.branches();
+    for (index, (name, child)) in node.children.iter().enumerate() {
+        let last = index + 1 == node.children.len();
+        let prefix = if last { last_branch } else { branch };
+        match &child.decision {
+            Some(decision) => println!("{}{}{} ({})", indent, prefix, name, decision),
+            None => println!("{}{}{}/", indent, prefix, color::bold(name)),
+        }
+        print_children(child, &format!("{}{}", indent, if last { space } else { line }), style);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::cleaner::{clean_package_cache, CleanOptions, CleanReport};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Identifies the volume `path` is on, for `--jobs-per-volume`: the drive
+/// letter or UNC share on Windows, the device number elsewhere. `None` when
+/// the path can't be resolved.
+pub fn volume_of(path: &Path) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(path).ok().map(|metadata| metadata.dev().to_string())
+    }
+
+    #[cfg(not(unix))]
+    {
+        use std::path::{Component, Prefix};
+        let resolved = fs::canonicalize(path).ok()?;
+        match resolved.components().next()? {
+            Component::Prefix(prefix) => Some(match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                    format!("{}:", char::from(letter).to_ascii_uppercase())
+                }
+                Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                    format!(r"\\{}\{}", server.to_string_lossy(), share.to_string_lossy()).to_lowercase()
+                }
+                _ => prefix.as_os_str().to_string_lossy().into_owned(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes free for the current user on the volume `path` is on, for
+/// `--free-space`. Unlike summed file sizes this sees what hardlinks, sparse
+/// files and the trash really leave behind.
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+        // SAFETY: `path` is a valid NUL-terminated string and `stats` is
+        // only read after statvfs filled it in
+        let stats = unsafe {
+            let mut stats = std::mem::zeroed::<libc::statvfs>();
+            if libc::statvfs(path.as_ptr(), &mut stats) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            stats
+        };
+        Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+        let wide: Vec<u16> = crate::longpath::long_path(path).as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut available = 0u64;
+        // SAFETY: `wide` is NUL-terminated and the totals we don't need may be null
+        let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(available)
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        Err(io::Error::new(io::ErrorKind::Unsupported, "free space can't be read on this platform"))
+    }
+}
+
+/// Cleans `roots` with one worker per volume, so roots on different disks
+/// are cleaned at the same time while each disk only sees `jobs_per_volume`
+/// concurrent deletions. Roots sharing a volume are cleaned one after the
+/// other, in the order given, and unless `keep_going` is set a root that
+/// fails stops the rest of its volume. Roots whose volume can't be told are
+/// treated as sharing one. `on_start` is called as each root is started.
+///
+/// Returns a result for every root that was cleaned, in the order of
+/// `roots`. Progress bars are left out, as several would overwrite each other.
+pub fn clean_by_volume<'a>(
+    roots: &'a [PathBuf],
+    options: &CleanOptions,
+    jobs_per_volume: usize,
+    on_start: impl Fn(&Path) + Sync,
+) -> Vec<(&'a Path, Result<CleanReport>)> {
+    let options = CleanOptions {
+        jobs: jobs_per_volume,
+        quiet: true,
+        ..options.clone()
+    };
+
+    let mut volumes: BTreeMap<Option<String>, Vec<usize>> = BTreeMap::new();
+    for (i, root) in roots.iter().enumerate() {
+        volumes.entry(volume_of(root)).or_default().push(i);
+    }
+
+    let mut results: Vec<(usize, Result<CleanReport>)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = volumes
+            .into_values()
+            .map(|indices| {
+                let (options, on_start) = (&options, &on_start);
+                scope.spawn(move || {
+                    let mut results = Vec::new();
+                    for i in indices {
+                        on_start(&roots[i]);
+                        let result = clean_package_cache(&roots[i], options);
+                        let failed = result.is_err();
+                        results.push((i, result));
+                        if failed && !options.keep_going {
+                            break;
+                        }
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(i, result)| (roots[i].as_path(), result)).collect()
+}
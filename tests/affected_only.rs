@@ -0,0 +1,32 @@
+mod common;
+
+use common::{cleanpkgcache, Cache, DAY};
+
+/// Runs `cleanpkgcache --verbose --dry-run --keep 1` on `cache` with extra
+/// `args`, away from any user config, and returns its log output.
+fn verbose_log(cache: &Cache, args: &[&str]) -> String {
+    let output = cleanpkgcache()
+        .arg(cache.root())
+        .args(["--verbose", "--dry-run", "--keep", "1", "--color", "never"])
+        .args(args)
+        .output()
+        .expect("run cleanpkgcache");
+    assert!(output.status.success());
+    String::from_utf8(output.stderr).expect("utf-8 output")
+}
+
+#[test]
+fn affected_only_hides_packages_with_nothing_to_delete() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 2 * DAY);
+    cache.version("zlib", "2.0", DAY);
+    cache.version("zstd", "1.5", DAY);
+
+    let everything = verbose_log(&cache, &[]);
+    let affected = verbose_log(&cache, &["--affected-only"]);
+
+    assert!(everything.contains("Package: zstd"), "{}", everything);
+    assert!(!affected.contains("zstd"), "{}", affected);
+    assert!(affected.contains("Package: zlib"), "{}", affected);
+    assert!(affected.contains("Would delete:"), "{}", affected);
+}
@@ -0,0 +1,155 @@
+//! Synthetic cache trees for the integration tests.
+
+#![allow(dead_code)]
+
+use filetime::FileTime;
+use std::env;
+use std::fs;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+pub const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A package cache root in a temporary directory, removed when dropped.
+pub struct Cache {
+    dir: TempDir,
+}
+
+impl Cache {
+    pub fn new() -> Cache {
+        Cache {
+            dir: TempDir::new().expect("create temporary cache root"),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Creates `root/package/version` holding one 100-byte file, last
+    /// modified `age` ago.
+    pub fn version(&self, package: &str, version: &str, age: Duration) -> PathBuf {
+        let path = self.root().join(package).join(version);
+        fs::create_dir_all(&path).expect("create version directory");
+        fs::write(path.join("payload.bin"), [0u8; 100]).expect("write payload");
+        set_age(&path, age);
+        path
+    }
+
+    /// Creates `root/package/name` as a version file of `len` bytes, last
+    /// modified `age` ago.
+    pub fn file(&self, package: &str, name: &str, len: usize, age: Duration) -> PathBuf {
+        let path = self.root().join(package).join(name);
+        fs::create_dir_all(self.root().join(package)).expect("create package directory");
+        fs::write(&path, vec![0u8; len]).expect("write version file");
+        set_age(&path, age);
+        path
+    }
+
+    /// Names of the versions of `package` still on disk, sorted.
+    pub fn surviving(&self, package: &str) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(self.root().join(package))
+            .expect("read package directory")
+            .map(|entry| entry.expect("read entry").file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+/// A cache with one package, `zlib`, holding one version more than `--keep 1`
+/// keeps.
+pub fn cache_with_excess() -> Cache {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 2 * DAY);
+    cache.version("zlib", "2.0", DAY);
+    cache
+}
+
+/// A Roo `tasks` directory in a temporary directory, removed when dropped.
+pub struct RooTasks {
+    dir: TempDir,
+}
+
+impl RooTasks {
+    pub fn new() -> RooTasks {
+        RooTasks {
+            dir: TempDir::new().expect("create temporary tasks directory"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Creates a task folder with a `checkpoints` subfolder, last modified
+    /// at `modified`.
+    pub fn task(&self, name: &str, modified: SystemTime) -> PathBuf {
+        let path = self.path().join(name);
+        fs::create_dir_all(path.join("checkpoints")).expect("create task directory");
+        fs::write(path.join("checkpoints").join("state.json"), "{}").expect("write checkpoint");
+        // Last, as creating the checkpoints touched the task folder
+        filetime::set_file_mtime(&path, FileTime::from_system_time(modified)).expect("set task mtime");
+        path
+    }
+}
+
+/// The `cleanpkgcache` binary, away from any user config or environment:
+/// its home and config directories are a temporary directory, removed when
+/// this is dropped, and no `CLEANPKGCACHE_*` variable is passed on.
+pub struct Cli {
+    home: TempDir,
+    command: Command,
+}
+
+pub fn cleanpkgcache() -> Cli {
+    let home = TempDir::new().expect("create home dir");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cleanpkgcache"));
+    command
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path())
+        .env("APPDATA", home.path());
+    for (name, _) in env::vars_os() {
+        if name.to_string_lossy().starts_with("CLEANPKGCACHE_") {
+            command.env_remove(name);
+        }
+    }
+    Cli { home, command }
+}
+
+impl Cli {
+    /// The temporary home directory, e.g. for a config file.
+    pub fn home(&self) -> &Path {
+        self.home.path()
+    }
+}
+
+impl Deref for Cli {
+    type Target = Command;
+
+    fn deref(&self) -> &Command {
+        &self.command
+    }
+}
+
+impl DerefMut for Cli {
+    fn deref_mut(&mut self) -> &mut Command {
+        &mut self.command
+    }
+}
+
+/// Sets the modification time of `path` to `age` before now.
+pub fn set_age(path: &Path, age: Duration) {
+    let modified = SystemTime::now() - age;
+    filetime::set_file_mtime(path, FileTime::from_system_time(modified)).expect("set mtime");
+}
+
+/// Sorted version names, for comparing with [`Cache::surviving`].
+pub fn names(names: &[&str]) -> Vec<String> {
+    let mut names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    names.sort();
+    names
+}
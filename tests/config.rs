@@ -0,0 +1,49 @@
+mod common;
+
+use common::{cache_with_excess, cleanpkgcache, names, Cache, DAY};
+use std::fs;
+use std::process::Output;
+
+/// Runs the `cleanpkgcache` binary with `args` and a config file holding
+/// `config`, away from any user config or environment.
+fn run_with_config(config: &str, args: &[&str]) -> Output {
+    let mut cli = cleanpkgcache();
+    let config_path = cli.home().join("config.toml");
+    fs::write(&config_path, config).expect("write config");
+    cli.args(["--color", "never", "--config", config_path.to_str().unwrap()])
+        .args(args)
+        .output()
+        .expect("run cleanpkgcache")
+}
+
+#[test]
+fn flags_switch_off_config_booleans() {
+    let cache = cache_with_excess();
+    let root = cache.root().to_str().unwrap();
+
+    let verbose = run_with_config("verbose = true\n", &[root, "--dry-run", "--keep", "1"]);
+    let quiet = run_with_config("verbose = true\n", &[root, "--dry-run", "--keep", "1", "--no-verbose"]);
+    assert!(String::from_utf8_lossy(&verbose.stderr).contains("Package: zlib"));
+    assert!(!String::from_utf8_lossy(&quiet.stderr).contains("Package: zlib"));
+
+    let output = run_with_config("dry_run = true\n", &[root, "--keep", "1", "--no-dry-run"]);
+    assert!(output.status.success());
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+}
+
+#[test]
+fn config_keep_overrides_apply_to_their_package_only() {
+    let cache = Cache::new();
+    for package in ["VC17LTCG", "zlib"] {
+        for version in 1..=7u32 {
+            cache.version(package, &format!("{}.0", version), (8 - version) * DAY);
+        }
+    }
+    let config = "keep = 2\n\n[keep_overrides]\n\"VC17LTCG\" = 5\n";
+
+    let output = run_with_config(config, &[cache.root().to_str().unwrap()]);
+
+    assert!(output.status.success());
+    assert_eq!(cache.surviving("VC17LTCG"), names(&["3.0", "4.0", "5.0", "6.0", "7.0"]));
+    assert_eq!(cache.surviving("zlib"), names(&["6.0", "7.0"]));
+}
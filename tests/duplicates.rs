@@ -0,0 +1,99 @@
+mod common;
+
+use cleanpkgcache::cleaner::{list_package_cache, CleanOptions, CleanReport};
+use cleanpkgcache::dedupe::{find_duplicates, DedupeMode};
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+use std::fs;
+
+#[test]
+fn identical_versions_of_different_packages_are_grouped() {
+    let cache = Cache::new();
+    let vendored = cache.version("app", "1.0", DAY);
+    let original = cache.version("zlib", "1.2.13", DAY);
+    cache.version("zlib", "1.2.12", DAY);
+    // Same size as the others, but a different file name
+    let other = cache.version("zstd", "1.5", DAY);
+    fs::rename(other.join("payload.bin"), other.join("other.bin")).expect("rename payload");
+
+    let inventory = list_package_cache(cache.root(), &CleanOptions::default()).expect("list cache");
+    let report = find_duplicates(&[inventory], DedupeMode::Manifest);
+
+    assert_eq!(report.groups.len(), 1);
+    let paths: Vec<_> = report.groups[0].versions.iter().map(|version| version.path.clone()).collect();
+    assert_eq!(paths, vec![vendored, cache.root().join("zlib").join("1.2.12"), original]);
+    assert_eq!(report.groups[0].reclaimable_bytes, 200);
+    assert_eq!(report.reclaimable_bytes, 200);
+}
+
+#[test]
+fn copies_within_one_package_are_left_to_dedupe() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.2.12", DAY);
+    cache.version("zlib", "1.2.13", DAY);
+
+    let inventory = list_package_cache(cache.root(), &CleanOptions::default()).expect("list cache");
+    let report = find_duplicates(&[inventory], DedupeMode::Manifest);
+
+    assert!(report.groups.is_empty());
+    assert_eq!(report.reclaimable_bytes, 0);
+}
+
+#[test]
+fn strict_mode_tells_same_sized_contents_apart() {
+    let cache = Cache::new();
+    let first = cache.version("app", "1.0", DAY);
+    cache.version("zlib", "1.2.13", DAY);
+    fs::write(first.join("payload.bin"), [1u8; 100]).expect("rewrite payload");
+    let inventories = [list_package_cache(cache.root(), &CleanOptions::default()).expect("list cache")];
+
+    assert_eq!(find_duplicates(&inventories, DedupeMode::Manifest).groups.len(), 1);
+    assert!(find_duplicates(&inventories, DedupeMode::Contents).groups.is_empty());
+}
+
+/// `zlib` with two identical old versions and a newer one of the same size
+/// but different contents.
+fn package_with_copies() -> Cache {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 3 * DAY);
+    cache.version("zlib", "1.1", 2 * DAY);
+    let newest = cache.version("zlib", "2.0", DAY);
+    fs::write(newest.join("payload.bin"), [2u8; 100]).expect("rewrite payload");
+    cache
+}
+
+/// Cleans `cache` for real with `mode` deduplication, keeping two versions.
+fn dedupe(cache: &Cache, mode: DedupeMode) -> CleanReport {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(2)
+        .dedupe(mode)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+#[test]
+fn dedupe_deletes_versions_with_the_same_manifest() {
+    let cache = package_with_copies();
+
+    let report = dedupe(&cache, DedupeMode::Manifest);
+
+    // Only names and sizes are compared, so all three look alike
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+    assert_eq!(report.duplicates_deleted, 2);
+    assert_eq!(report.bytes_freed_by_dedupe, 200);
+}
+
+#[test]
+fn strict_dedupe_deletes_only_identical_contents() {
+    let cache = package_with_copies();
+
+    let report = dedupe(&cache, DedupeMode::Contents);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.1", "2.0"]));
+    assert_eq!(report.duplicates_deleted, 1);
+    assert_eq!(report.bytes_freed_by_dedupe, 100);
+}
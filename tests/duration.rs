@@ -0,0 +1,45 @@
+use cleanpkgcache::duration::{parse_date, parse_duration};
+use std::time::{Duration, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+
+#[test]
+fn parses_days_weeks_and_months() {
+    assert_eq!(parse_duration("30d"), Ok(Duration::from_secs(30 * DAY)));
+    assert_eq!(parse_duration("6w"), Ok(Duration::from_secs(6 * 7 * DAY)));
+    assert_eq!(parse_duration("3mo"), Ok(Duration::from_secs(3 * 30 * DAY)));
+}
+
+#[test]
+fn trims_surrounding_whitespace() {
+    assert_eq!(parse_duration("  12h \n"), Ok(Duration::from_secs(12 * 60 * 60)));
+    assert_eq!(parse_duration("2 days"), Ok(Duration::from_secs(2 * DAY)));
+}
+
+#[test]
+fn rejects_malformed_durations() {
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("d").is_err());
+    assert!(parse_duration("5").is_err());
+    assert!(parse_duration("5x").is_err());
+    assert!(parse_duration("99999999999999999999d").is_err());
+    // Fits in u64 seconds only before the unit is applied
+    assert!(parse_duration(&format!("{}y", u64::MAX / 2)).is_err());
+}
+
+#[test]
+fn parses_utc_dates_and_times() {
+    let midnight = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+    assert_eq!(parse_date("2024-01-01"), Ok(midnight));
+    assert_eq!(parse_date(" 2024-01-01 "), Ok(midnight));
+    assert_eq!(parse_date("2024-01-01T12:00:00Z"), Ok(midnight + Duration::from_secs(12 * 60 * 60)));
+}
+
+#[test]
+fn rejects_malformed_dates() {
+    assert!(parse_date("").is_err());
+    assert!(parse_date("2024-13-01").is_err());
+    assert!(parse_date("2024-02-30").is_err());
+    assert!(parse_date("01/02/2024").is_err());
+    assert!(parse_date("30d").is_err());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{cache_with_excess, cleanpkgcache, names, Cache, RooTasks, DAY};
+use std::env;
+use std::fs;
+use std::time::SystemTime;
+
+/// Runs `cleanpkgcache --keep 1` with `args`, the variables in `envs` and a
+/// config file whose cache root is `config_root`, away from any user config
+/// or environment.
+fn run(args: &[&str], envs: &[(&str, String)], config_root: &Cache) {
+    let mut cli = cleanpkgcache();
+    let config_path = cli.home().join("config.toml");
+    fs::write(&config_path, format!("path = '{}'\n", config_root.root().display())).expect("write config");
+    let status = cli
+        .args(["--keep", "1", "--color", "never", "--config", config_path.to_str().unwrap()])
+        .args(args)
+        .envs(envs.iter().cloned())
+        .status()
+        .expect("run cleanpkgcache");
+    assert!(status.success());
+}
+
+fn was_cleaned(cache: &Cache) -> bool {
+    cache.surviving("zlib") == names(&["2.0"])
+}
+
+#[test]
+fn a_path_argument_wins_over_the_environment_and_the_config() {
+    let (cli, from_env, config) = (cache_with_excess(), cache_with_excess(), cache_with_excess());
+
+    run(
+        &[cli.root().to_str().unwrap()],
+        &[("CLEANPKGCACHE_PATH", from_env.root().display().to_string())],
+        &config,
+    );
+
+    assert!(was_cleaned(&cli));
+    assert!(!was_cleaned(&from_env));
+    assert!(!was_cleaned(&config));
+}
+
+#[test]
+fn the_environment_wins_over_the_config() {
+    let (first, second, config) = (cache_with_excess(), cache_with_excess(), cache_with_excess());
+    let joined = env::join_paths([first.root(), second.root()]).expect("join paths");
+
+    run(&[], &[("CLEANPKGCACHE_PATH", joined.to_string_lossy().into_owned())], &config);
+
+    assert!(was_cleaned(&first));
+    assert!(was_cleaned(&second));
+    assert!(!was_cleaned(&config));
+}
+
+#[test]
+fn the_config_is_used_without_an_argument_or_variable() {
+    let config = cache_with_excess();
+
+    run(&[], &[("CLEANPKGCACHE_PATH", String::new())], &config);
+
+    assert!(was_cleaned(&config));
+}
+
+#[test]
+fn roo_tasks_dirs_come_from_the_environment_unless_given() {
+    let config = Cache::new();
+    let (first, second) = (RooTasks::new(), RooTasks::new());
+    let old = SystemTime::now() - 90 * DAY;
+    let (first_task, second_task) = (first.task("old", old), second.task("old", old));
+    let joined = env::join_paths([first.path(), second.path()]).expect("join paths");
+    let envs = [("CLEANPKGCACHE_ROO_DIRS", joined.to_string_lossy().into_owned())];
+
+    run(&["--clean-roo-checkpoints", "--roo-tasks-dir", first.path().to_str().unwrap()], &envs, &config);
+    assert!(!first_task.join("checkpoints").exists());
+    assert!(second_task.join("checkpoints").exists(), "--roo-tasks-dir replaces the variable");
+
+    run(&["--clean-roo-checkpoints"], &envs, &config);
+    assert!(!second_task.join("checkpoints").exists());
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{cache_with_excess, cleanpkgcache};
+use std::fs;
+
+/// Runs the `cleanpkgcache` binary with `args`, away from any user config
+/// or environment, and returns its exit code.
+fn exit_code(args: &[&str]) -> i32 {
+    let status = cleanpkgcache()
+        .args(args)
+        .output()
+        .expect("run cleanpkgcache")
+        .status;
+    status.code().expect("exited normally")
+}
+
+#[test]
+fn success_exits_zero_with_or_without_deletions() {
+    let cache = cache_with_excess();
+    let root = cache.root().to_str().unwrap();
+
+    assert_eq!(exit_code(&[root, "--keep", "5"]), 0);
+    assert_eq!(exit_code(&[root, "--keep", "1"]), 0);
+}
+
+#[test]
+fn fail_if_deleted_exits_five() {
+    let cache = cache_with_excess();
+    let root = cache.root().to_str().unwrap();
+
+    assert_eq!(exit_code(&[root, "--keep", "1", "--dry-run", "--fail-if-deleted"]), 5);
+}
+
+#[test]
+fn usage_errors_exit_one() {
+    let cache = cache_with_excess();
+    let root = cache.root().to_str().unwrap();
+    let missing = cache.root().join("missing");
+
+    assert_eq!(exit_code(&[root, "--no-such-flag"]), 1);
+    assert_eq!(exit_code(&[root, "--keep", "0"]), 1);
+    assert_eq!(exit_code(&[root, "--summary-only"]), 1);
+    assert_eq!(exit_code(&[root, "--watch", "0s"]), 1);
+    // A watch has nobody to confirm its later runs
+    assert_eq!(exit_code(&[root, "--watch", "1h", "--confirm-threshold", "1"]), 1);
+    assert_eq!(exit_code(&[missing.to_str().unwrap()]), 1);
+    assert_eq!(exit_code(&["--help"]), 0);
+}
+
+#[test]
+fn io_errors_exit_two() {
+    let cache = cache_with_excess();
+    let root = cache.root().to_str().unwrap();
+    let plan = cache.root().join("missing").join("plan.txt");
+
+    assert_eq!(exit_code(&[root, "--dry-run", "--plan-out", plan.to_str().unwrap()]), 2);
+}
+
+#[test]
+fn errors_after_carrying_on_exit_three() {
+    let cache = cache_with_excess();
+    let root = cache.root().to_str().unwrap();
+    let plan = cache.root().join("plan.txt");
+    fs::write(&plan, "/etc/zlib/1.0\n").expect("write plan");
+
+    assert_eq!(exit_code(&[root, "--apply-plan", plan.to_str().unwrap()]), 3);
+    assert!(cache.root().join("zlib").join("1.0").exists());
+}
+
+#[test]
+fn declined_confirmation_exits_four() {
+    let cache = cache_with_excess();
+    let root = cache.root().to_str().unwrap();
+
+    // stdin isn't a terminal here, so the confirmation can't be given
+    assert_eq!(exit_code(&[root, "--keep", "1", "--confirm-threshold", "0"]), 4);
+    assert!(cache.root().join("zlib").join("1.0").exists());
+}
+
+#[test]
+fn a_file_path_is_a_usage_error_unless_auto_parent() {
+    let cache = cache_with_excess();
+    let archive = cache.root().join("zlib-2.0.zip");
+    fs::write(&archive, "archive").expect("write archive");
+    let archive = archive.to_str().unwrap();
+
+    assert_eq!(exit_code(&[archive, "--keep", "1"]), 1);
+    assert!(cache.root().join("zlib").join("1.0").exists());
+
+    assert_eq!(exit_code(&[archive, "--keep", "1", "--auto-parent"]), 0);
+    assert!(!cache.root().join("zlib").join("1.0").exists());
+    assert!(cache.root().join("zlib").join("2.0").exists());
+}
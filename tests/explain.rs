@@ -0,0 +1,100 @@
+mod common;
+
+use cleanpkgcache::cleaner::{CleanReport, Reason};
+use cleanpkgcache::{Cleaner, CleanerOptions, CleanerOptionsBuilder};
+use common::{Cache, DAY};
+use std::io::IsTerminal;
+use std::time::Duration;
+
+/// Dry-runs `cache` with `--explain` and the options `configure` adds.
+fn explain(cache: &Cache, configure: impl FnOnce(CleanerOptionsBuilder) -> CleanerOptionsBuilder) -> CleanReport {
+    let builder = CleanerOptions::builder().path(cache.root()).dry_run(true).explain(true).quiet(true).jobs(1);
+    let options = configure(builder).build().expect("valid options");
+    Cleaner::new(options).run().expect("dry run").remove(0)
+}
+
+/// Every version of `report` with its reason, sorted by name.
+fn reasons(report: &CleanReport) -> Vec<(String, Reason)> {
+    let mut reasons: Vec<(String, Reason)> = report
+        .packages
+        .iter()
+        .flat_map(|package| package.kept.iter().chain(&package.deleted))
+        .map(|version| (version.name.clone(), version.reason.expect("version has a reason")))
+        .collect();
+    reasons.sort_by(|a, b| a.0.cmp(&b.0));
+    reasons
+}
+
+#[test]
+fn explains_keep_count_pins_and_min_age() {
+    let cache = Cache::new();
+    cache.version("tool", "1.0", 40 * DAY);
+    cache.version("tool", "2.0", 30 * DAY);
+    cache.version("tool", "3.0", 20 * DAY);
+    cache.version("tool", "4.0", 2 * DAY);
+    cache.version("tool", "5.0", DAY);
+
+    let report = explain(&cache, |builder| builder.keep(1).pin("1.0").min_age(Duration::from_secs(7 * 24 * 60 * 60)));
+
+    assert_eq!(
+        reasons(&report),
+        vec![
+            ("1.0".to_string(), Reason::Pinned),
+            ("2.0".to_string(), Reason::BeyondLatest(1)),
+            ("3.0".to_string(), Reason::BeyondLatest(1)),
+            ("4.0".to_string(), Reason::TooRecent),
+            ("5.0".to_string(), Reason::WithinLatest(1)),
+        ]
+    );
+}
+
+#[test]
+fn explains_size_budget() {
+    let cache = Cache::new();
+    cache.version("tool", "1.0", 3 * DAY);
+    cache.version("tool", "2.0", 2 * DAY);
+    cache.version("tool", "3.0", DAY);
+
+    // Each version holds 100 bytes, so two of them fit
+    let report = explain(&cache, |builder| builder.max_total_size(250));
+
+    assert_eq!(
+        reasons(&report),
+        vec![
+            ("1.0".to_string(), Reason::OverSizeBudget),
+            ("2.0".to_string(), Reason::WithinSizeBudget),
+            ("3.0".to_string(), Reason::WithinSizeBudget),
+        ]
+    );
+    assert_eq!(Reason::OverSizeBudget.to_string(), "deleted: over size budget");
+    assert_eq!(Reason::WithinLatest(2).to_string(), "kept: within latest 2");
+}
+
+#[test]
+fn versions_declined_at_the_prompt_are_explained() {
+    // Prompts only read a terminal; anything else declines every deletion
+    if std::io::stdin().is_terminal() {
+        return;
+    }
+    let cache = Cache::new();
+    cache.version("tool", "1.0", 2 * DAY);
+    cache.version("tool", "2.0", DAY);
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .interactive(true)
+        .explain(true)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    assert_eq!(
+        reasons(&report),
+        vec![("1.0".to_string(), Reason::Declined), ("2.0".to_string(), Reason::WithinLatest(1))]
+    );
+    assert_eq!(report.versions_kept, 2);
+    assert_eq!(Reason::Declined.to_string(), "kept: declined at the prompt");
+}
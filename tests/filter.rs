@@ -0,0 +1,68 @@
+mod common;
+
+use cleanpkgcache::filter::IGNORE_FILE_NAME;
+use cleanpkgcache::{Cleaner, CleanerOptions, CleanerOptionsBuilder};
+use common::{names, Cache, DAY};
+use std::fs;
+
+/// Creates packages `zlib`, `zstd` and `boost` with an old and a new version each.
+fn three_packages() -> Cache {
+    let cache = Cache::new();
+    for package in ["zlib", "zstd", "boost"] {
+        cache.version(package, "1.0", 2 * DAY);
+        cache.version(package, "2.0", DAY);
+    }
+    cache
+}
+
+/// Cleans `cache` for real, keeping one version, with the options `configure` adds.
+fn clean(cache: &Cache, configure: impl FnOnce(CleanerOptionsBuilder) -> CleanerOptionsBuilder) {
+    let builder = CleanerOptions::builder().path(cache.root()).keep(1).quiet(true).jobs(1);
+    let options = configure(builder).build().expect("valid options");
+    Cleaner::new(options).run().expect("clean cache");
+}
+
+#[test]
+fn excluded_packages_keep_their_old_versions() {
+    let cache = three_packages();
+
+    clean(&cache, |builder| builder.exclude("zs*"));
+
+    assert_eq!(cache.surviving("zstd"), names(&["1.0", "2.0"]));
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+    assert_eq!(cache.surviving("boost"), names(&["2.0"]));
+}
+
+#[test]
+fn only_included_packages_are_cleaned() {
+    let cache = three_packages();
+
+    clean(&cache, |builder| builder.include("z*").exclude("zstd"));
+
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+    assert_eq!(cache.surviving("zstd"), names(&["1.0", "2.0"]));
+    assert_eq!(cache.surviving("boost"), names(&["1.0", "2.0"]));
+}
+
+#[test]
+fn packages_in_the_ignore_file_are_left_alone() {
+    let cache = three_packages();
+    fs::write(cache.root().join(IGNORE_FILE_NAME), "# pinned toolchains\nboo*\n\n").expect("write ignore file");
+
+    clean(&cache, |builder| builder);
+
+    assert_eq!(cache.surviving("boost"), names(&["1.0", "2.0"]));
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+    assert_eq!(cache.surviving("zstd"), names(&["2.0"]));
+}
+
+#[test]
+fn the_ignore_file_wins_over_include() {
+    let cache = three_packages();
+    fs::write(cache.root().join(IGNORE_FILE_NAME), "zlib\n").expect("write ignore file");
+
+    clean(&cache, |builder| builder.include("z*"));
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.0", "2.0"]));
+    assert_eq!(cache.surviving("zstd"), names(&["2.0"]));
+}
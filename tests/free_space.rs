@@ -0,0 +1,41 @@
+mod common;
+
+use cleanpkgcache::volume::free_space;
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{Cache, DAY};
+
+fn clean(cache: &Cache, dry_run: bool) -> cleanpkgcache::cleaner::CleanReport {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .dry_run(dry_run)
+        .free_space(true)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+#[test]
+fn reports_free_space_before_and_after() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 2 * DAY);
+    cache.version("zlib", "2.0", DAY);
+
+    let dry_run = clean(&cache, true);
+    assert!(dry_run.free_space_before.is_some());
+    assert_eq!(dry_run.free_space_after, None);
+
+    let report = clean(&cache, false);
+    assert!(report.free_space_before.is_some());
+    assert!(report.free_space_after.is_some());
+}
+
+#[test]
+fn missing_path_has_no_free_space() {
+    let cache = Cache::new();
+
+    assert!(free_space(cache.root()).is_ok());
+    assert!(free_space(&cache.root().join("missing")).is_err());
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{cleanpkgcache, names, set_age, DAY};
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+use tempfile::TempDir;
+
+/// Runs `cleanpkgcache --keep 1` with `args`, away from any user config or
+/// environment.
+fn run(args: &[&str]) -> Output {
+    cleanpkgcache()
+        .args(["--keep", "1", "--color", "never"])
+        .args(args)
+        .output()
+        .expect("run cleanpkgcache")
+}
+
+/// A directory holding the cache roots `VC16`, `VC17` and `other`, each with
+/// an excess `zlib` version.
+fn parent_of_caches() -> TempDir {
+    let parent = TempDir::new().expect("create parent directory");
+    for root in ["VC16", "VC17", "other"] {
+        for (version, age) in [("1.0", 2 * DAY), ("2.0", DAY)] {
+            let path = parent.path().join(root).join("zlib").join(version);
+            fs::create_dir_all(&path).expect("create version directory");
+            fs::write(path.join("payload.bin"), [0u8; 100]).expect("write payload");
+            set_age(&path, age);
+        }
+    }
+    parent
+}
+
+fn surviving(root: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(root.join("zlib"))
+        .expect("read package directory")
+        .map(|entry| entry.expect("read entry").file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn a_glob_expands_to_every_matching_cache_root() {
+    let parent = parent_of_caches();
+    let pattern = parent.path().join("VC*");
+    let vc17 = parent.path().join("VC17");
+
+    // VC17 is named twice but only cleaned once
+    let output = run(&[pattern.to_str().unwrap(), vc17.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(surviving(&parent.path().join("VC16")), names(&["2.0"]));
+    assert_eq!(surviving(&vc17), names(&["2.0"]));
+    assert_eq!(surviving(&parent.path().join("other")), names(&["1.0", "2.0"]));
+    let summary = String::from_utf8_lossy(&output.stdout);
+    assert!(summary.contains("Grand total (2 cache roots)"), "{}", summary);
+}
+
+#[test]
+fn a_glob_matching_nothing_only_warns() {
+    let parent = parent_of_caches();
+    let pattern = parent.path().join("VC9*");
+    let vc17 = parent.path().join("VC17");
+
+    let output = run(&[pattern.to_str().unwrap(), vc17.to_str().unwrap()]);
+
+    assert!(output.status.success());
+    let log = String::from_utf8_lossy(&output.stderr);
+    assert!(log.contains(&format!("{} matches no directories", pattern.display())), "{}", log);
+    assert_eq!(surviving(&vc17), names(&["2.0"]));
+}
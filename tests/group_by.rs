@@ -0,0 +1,118 @@
+mod common;
+
+use cleanpkgcache::cleaner::CleanReport;
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, set_age, Cache, DAY};
+use std::fs;
+
+/// A flat cache where every version is a directory at the root.
+fn flat_cache(versions: &[(&str, u32)]) -> Cache {
+    let cache = Cache::new();
+    for &(name, days) in versions {
+        let path = cache.root().join(name);
+        fs::create_dir(&path).expect("create version directory");
+        fs::write(path.join("payload.bin"), [0u8; 100]).expect("write payload");
+        set_age(&path, days * DAY);
+    }
+    cache
+}
+
+/// Cleans `cache` for real with `--group-by pattern`, keeping one version.
+fn clean(cache: &Cache, pattern: &str) -> CleanReport {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .group_by(pattern)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+/// Names of the entries left at the root of `cache`, sorted.
+fn surviving(cache: &Cache) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(cache.root())
+        .expect("read cache root")
+        .map(|entry| entry.expect("read entry").file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn groups_a_flat_layout_by_the_captured_package() {
+    let cache = flat_cache(&[
+        ("pkgA-1.0", 3),
+        ("pkgA-1.1", 2),
+        ("pkgA-1.2", 1),
+        ("pkgB-2.0", 2),
+        ("pkgB-2.1", 1),
+        ("README", 5),
+    ]);
+
+    let report = clean(&cache, r"^(.*)-[0-9.]+$");
+
+    assert_eq!(surviving(&cache), names(&["README", "pkgA-1.2", "pkgB-2.1"]));
+    assert_eq!(report.packages_processed, 2);
+    assert_eq!(report.versions_deleted, 3);
+}
+
+#[test]
+fn a_named_capture_picks_the_package() {
+    let cache = flat_cache(&[("v1.0_tool", 2), ("v2.0_tool", 1)]);
+
+    clean(&cache, r"^v[0-9.]+_(?P<package>.+)$");
+
+    assert_eq!(surviving(&cache), names(&["v2.0_tool"]));
+}
+
+#[test]
+fn a_pattern_without_a_capture_group_is_rejected() {
+    let error = CleanerOptions::builder().path(".").group_by("^.*-[0-9.]+$").build().unwrap_err();
+
+    assert!(error.to_string().contains("needs a capture group"), "{}", error);
+}
+
+#[cfg(windows)]
+#[test]
+fn package_names_differing_in_case_share_a_bucket_on_windows() {
+    let cache = flat_cache(&[("Foo-1.0", 3), ("foo-2.0", 2), ("foo-3.0", 1)]);
+
+    let report = clean(&cache, r"^(.*)-[0-9.]+$");
+
+    assert_eq!(surviving(&cache), names(&["foo-3.0"]));
+    assert_eq!(report.packages.len(), 1);
+    assert_eq!(report.packages[0].name, "Foo", "the first spelling seen names the package");
+}
+
+#[cfg(windows)]
+#[test]
+fn keep_overrides_ignore_case_on_windows() {
+    let cache = flat_cache(&[("Foo-1.0", 3), ("foo-2.0", 2), ("foo-3.0", 1)]);
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .keep_override("foo", 2)
+        .group_by(r"^(.*)-[0-9.]+$")
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+
+    // The package is named Foo after its first spelling, but the override still applies
+    Cleaner::new(options).run().expect("clean cache");
+
+    assert_eq!(surviving(&cache), names(&["foo-2.0", "foo-3.0"]));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn package_names_differing_in_case_are_separate_packages() {
+    let cache = flat_cache(&[("Foo-1.0", 3), ("foo-2.0", 2), ("foo-3.0", 1)]);
+
+    let report = clean(&cache, r"^(.*)-[0-9.]+$");
+
+    assert_eq!(surviving(&cache), names(&["Foo-1.0", "foo-3.0"]));
+    assert_eq!(report.packages_processed, 2);
+}
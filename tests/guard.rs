@@ -0,0 +1,82 @@
+mod common;
+
+use cleanpkgcache::cleaner::apply_plan;
+use cleanpkgcache::guard::{canonical_path, relative_to, same_path};
+use cleanpkgcache::plan::check_within_roots;
+use cleanpkgcache::CleanerOptions;
+use common::{names, Cache, DAY};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn trailing_slashes_and_dot_dot_compare_equal_to_the_canonical_path() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", DAY);
+    let root = cache.root();
+
+    let with_slash = PathBuf::from(format!("{}/", root.display()));
+    assert!(same_path(&with_slash, root));
+    assert!(same_path(&root.join("zlib").join(".."), root));
+    assert!(same_path(&root.join("zlib").join(".").join("1.0"), &root.join("zlib/1.0")));
+}
+
+#[test]
+fn missing_paths_are_normalized_below_their_existing_ancestor() {
+    let cache = Cache::new();
+    let root = cache.root();
+
+    let missing = canonical_path(&root.join("gone").join("..").join("new").join("1.0"));
+    assert_eq!(missing, canonical_path(root).join("new").join("1.0"));
+    assert_eq!(relative_to(&root.join("gone/../new/1.0"), root), Some(PathBuf::from("new/1.0")));
+    assert_eq!(relative_to(&root.join("../elsewhere"), root), None);
+}
+
+#[test]
+fn plan_paths_traversing_out_of_the_root_are_refused() {
+    let cache = Cache::new();
+    let root = cache.root().to_path_buf();
+    let roots = [root.clone()];
+
+    assert!(check_within_roots(&root.join("zlib/1.0"), &roots, false).is_ok());
+    assert!(check_within_roots(&root.join("zlib/../../etc/passwd"), &roots, false).is_err());
+    assert!(check_within_roots(&root.join("zlib/1.0/../../.."), &roots, false).is_err());
+    assert!(check_within_roots(&root.join("zlib"), &roots, false).is_err());
+    assert!(check_within_roots(Path::new("/etc/zlib/1.0"), &roots, false).is_err());
+}
+
+#[test]
+fn roots_written_differently_still_match() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", DAY);
+    let root = cache.root();
+    let path = root.join("zlib/1.0");
+
+    let with_slash = PathBuf::from(format!("{}/", root.display()));
+    assert!(check_within_roots(&path, &[with_slash], false).is_ok());
+    assert!(check_within_roots(&path, &[root.join("zlib/..")], false).is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn package_symlinks_leading_out_of_the_root_are_refused() {
+    let cache = Cache::new();
+    let outside = Cache::new();
+    outside.version("victim", "1.0", 2 * DAY);
+    std::os::unix::fs::symlink(outside.root().join("victim"), cache.root().join("zlib")).expect("create symlink");
+
+    // Textually root/zlib/1.0, but it lives in the other cache
+    let planned = cache.root().join("zlib").join("1.0");
+    let plan_file = cache.root().join("plan.txt");
+    fs::write(&plan_file, format!("{}\n", planned.display())).expect("write plan");
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .quiet(true)
+        .build()
+        .expect("valid options");
+    let report = apply_plan(&plan_file, &[cache.root().to_path_buf()], options.clean_options()).expect("apply plan");
+
+    assert_eq!(report.refused, [planned]);
+    assert!(report.deleted.is_empty());
+    assert_eq!(outside.surviving("victim"), names(&["1.0"]));
+}
@@ -0,0 +1,75 @@
+#![cfg(unix)]
+
+mod common;
+
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Writes an executable hook that vetoes every version named `veto`, and
+/// records each path it was asked about, from its argument and from
+/// `CLEANPKGCACHE_VERSION_PATH`, in `calls.log` next to it.
+fn vetoing_hook(dir: &Path) -> PathBuf {
+    let hook = dir.join("hook.sh");
+    let script = format!(
+        "#!/bin/sh\necho \"$1 $CLEANPKGCACHE_VERSION_PATH\" >> '{}'\ncase \"$1\" in\n  */veto) exit 3 ;;\nesac\n",
+        dir.join("calls.log").display()
+    );
+    fs::write(&hook, script).expect("write hook");
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).expect("make hook executable");
+    hook
+}
+
+#[test]
+fn a_failing_hook_skips_that_deletion() {
+    let cache = Cache::new();
+    let hooks = tempfile::tempdir().expect("create hook dir");
+    cache.version("zlib", "veto", 3 * DAY);
+    cache.version("zlib", "old", 2 * DAY);
+    cache.version("zlib", "new", DAY);
+    let hook = vetoing_hook(hooks.path());
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .quiet(true)
+        .pre_delete_hook(hook.display().to_string())
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    assert_eq!(report.versions_deleted, 1);
+    assert_eq!(report.versions_skipped_by_hook, 1);
+    assert_eq!(report.versions_kept, 2);
+    assert_eq!(cache.surviving("zlib"), names(&["new", "veto"]));
+
+    let old = cache.root().join("zlib").join("old");
+    let calls = fs::read_to_string(hooks.path().join("calls.log")).expect("read hook log");
+    assert!(calls.lines().any(|line| line == format!("{} {}", old.display(), old.display())));
+    assert_eq!(calls.lines().count(), 2);
+}
+
+#[test]
+fn hooks_are_not_run_in_a_dry_run() {
+    let cache = Cache::new();
+    let hooks = tempfile::tempdir().expect("create hook dir");
+    cache.version("zlib", "veto", 2 * DAY);
+    cache.version("zlib", "new", DAY);
+    let hook = vetoing_hook(hooks.path());
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .dry_run(true)
+        .quiet(true)
+        .pre_delete_hook(hook.display().to_string())
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("dry run").remove(0);
+
+    assert_eq!(report.versions_deleted, 1);
+    assert_eq!(report.versions_skipped_by_hook, 0);
+    assert!(!hooks.path().join("calls.log").exists());
+}
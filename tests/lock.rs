@@ -0,0 +1,52 @@
+mod common;
+
+use cleanpkgcache::cleaner::{CleanReport, Reason};
+use cleanpkgcache::{Cleaner, CleanerOptions, CleanerOptionsBuilder};
+use common::{names, Cache, DAY};
+use std::fs;
+
+/// `zlib` with three versions, the oldest holding the marker `lock_file`.
+fn cache_with_locked_version(lock_file: &str) -> Cache {
+    let cache = Cache::new();
+    let locked = cache.version("zlib", "1.0", 3 * DAY);
+    fs::write(locked.join(lock_file), "").expect("write lock file");
+    cache.version("zlib", "2.0", 2 * DAY);
+    cache.version("zlib", "3.0", DAY);
+    cache
+}
+
+/// Cleans `cache` for real with `--explain`, keeping one version.
+fn clean(cache: &Cache, configure: impl FnOnce(CleanerOptionsBuilder) -> CleanerOptionsBuilder) -> CleanReport {
+    let builder = CleanerOptions::builder().path(cache.root()).keep(1).explain(true).quiet(true).jobs(1);
+    let options = configure(builder).build().expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+/// The reason `report` gives for keeping `version`.
+fn kept_reason(report: &CleanReport, version: &str) -> Option<Reason> {
+    report.packages[0].kept.iter().find(|kept| kept.name == version).and_then(|kept| kept.reason)
+}
+
+#[test]
+fn a_version_with_a_lock_file_is_kept_and_reported() {
+    for lock_file in [".lock", "in_use.marker"] {
+        let cache = cache_with_locked_version(lock_file);
+
+        let report = clean(&cache, |builder| builder);
+
+        assert_eq!(cache.surviving("zlib"), names(&["1.0", "3.0"]), "{}", lock_file);
+        assert_eq!(report.versions_deleted, 1);
+        assert_eq!(kept_reason(&report, "1.0"), Some(Reason::InUse));
+    }
+}
+
+#[test]
+fn lock_file_names_replace_the_defaults() {
+    let cache = cache_with_locked_version(".lock");
+    fs::write(cache.root().join("zlib").join("2.0").join("build.pid"), "").expect("write lock file");
+
+    let report = clean(&cache, |builder| builder.lock_file_names(["build.pid"]));
+
+    assert_eq!(cache.surviving("zlib"), names(&["2.0", "3.0"]));
+    assert_eq!(kept_reason(&report, "2.0"), Some(Reason::InUse));
+}
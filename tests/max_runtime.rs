@@ -0,0 +1,34 @@
+// The hook that stands in for a slow deletion is a shell command
+#![cfg(unix)]
+
+mod common;
+
+use common::{cleanpkgcache, Cache, DAY};
+
+const PACKAGES: [&str; 5] = ["boost", "curl", "openssl", "zlib", "zstd"];
+
+#[test]
+fn max_runtime_stops_early_with_a_partial_summary() {
+    let cache = Cache::new();
+    for package in PACKAGES {
+        cache.version(package, "1.0", 2 * DAY);
+        cache.version(package, "2.0", DAY);
+    }
+
+    // Each deletion waits on the hook, so only the first packages fit in a second
+    let output = cleanpkgcache()
+        .args(["--keep", "1", "--jobs", "1", "--color", "never", "--max-runtime", "1s"])
+        .args(["--pre-delete-hook", "sleep 0.6; true"])
+        .arg(cache.root())
+        .output()
+        .expect("run cleanpkgcache");
+
+    assert_eq!(output.status.code(), Some(4));
+    let log = String::from_utf8_lossy(&output.stderr);
+    assert!(log.contains("stopped early by --max-runtime"), "{}", log);
+
+    let cleaned = PACKAGES.iter().filter(|package| cache.surviving(package).len() == 1).count();
+    assert!(cleaned > 0 && cleaned < PACKAGES.len(), "{} of {} packages cleaned", cleaned, PACKAGES.len());
+    let summary = String::from_utf8_lossy(&output.stdout);
+    assert!(summary.contains(&format!("Versions deleted: {}", cleaned)), "{}", summary);
+}
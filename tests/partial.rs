@@ -0,0 +1,51 @@
+mod common;
+
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, set_age, Cache, DAY};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Deletes one file of the version named `half` before failing on the next,
+/// fails on `locked` without touching it, and deletes the rest.
+fn fail_midway(path: &Path) -> io::Result<()> {
+    if path.ends_with("half") {
+        fs::remove_file(path.join("payload.bin"))?;
+        return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+    }
+    if path.ends_with("locked") {
+        return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+    }
+    fs::remove_dir_all(path)
+}
+
+#[test]
+fn partial_and_complete_failures_are_counted_apart() {
+    let cache = Cache::new();
+    let half = cache.version("zlib", "half", 4 * DAY);
+    fs::write(half.join("extra.bin"), [0u8; 100]).expect("write second file");
+    set_age(&half, 4 * DAY);
+    cache.version("zlib", "locked", 3 * DAY);
+    cache.version("zlib", "old", 2 * DAY);
+    cache.version("zlib", "new", DAY);
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .keep_going(true)
+        .deleter(fail_midway)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    assert_eq!(cache.surviving("zlib"), names(&["half", "locked", "new"]));
+    assert!(!half.join("payload.bin").exists());
+    assert_eq!(report.versions_deleted, 1);
+    assert_eq!(report.versions_partially_deleted, 1);
+    assert_eq!(report.versions_failed, 1);
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(report.packages[0].partially_deleted[0].size_bytes, 100, "what the failed deletion freed");
+    assert_eq!(report.bytes_freed, 200);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{cache_with_excess, cleanpkgcache, names};
+use std::fs;
+
+/// Runs `cleanpkgcache --keep 1` with `args`, away from any user config or
+/// environment, and returns whether it succeeded and its log output.
+fn run(args: &[&str]) -> (bool, String) {
+    let output = cleanpkgcache()
+        .args(["--keep", "1", "--color", "never"])
+        .args(args)
+        .output()
+        .expect("run cleanpkgcache");
+    (output.status.success(), String::from_utf8(output.stderr).expect("utf-8 output"))
+}
+
+#[test]
+fn cleans_listed_roots_with_inline_ones_and_skips_missing_lines() {
+    let listed = cache_with_excess();
+    let inline = cache_with_excess();
+    let missing = listed.root().join("gone");
+    let list = tempfile::NamedTempFile::new().expect("create path list");
+    fs::write(
+        list.path(),
+        format!("# build caches\n\n  {}  \n{}\n", listed.root().display(), missing.display()),
+    )
+    .expect("write path list");
+
+    let (success, log) = run(&[inline.root().to_str().unwrap(), "--paths-from", list.path().to_str().unwrap()]);
+
+    assert!(success, "{}", log);
+    assert_eq!(listed.surviving("zlib"), names(&["2.0"]));
+    assert_eq!(inline.surviving("zlib"), names(&["2.0"]));
+    assert!(log.contains(&format!("skipping {} from", missing.display())), "{}", log);
+    assert!(log.contains("line 4 (path does not exist)"), "{}", log);
+}
+
+#[test]
+fn list_without_existing_roots_is_an_error() {
+    let list = tempfile::NamedTempFile::new().expect("create path list");
+    fs::write(list.path(), "# nothing yet\n/definitely/not/a/cache\n").expect("write path list");
+
+    let (success, log) = run(&["--paths-from", list.path().to_str().unwrap()]);
+
+    assert!(!success);
+    assert!(log.contains("lists no cache root that exists"), "{}", log);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Fails like a directory owned by another user for every version named
+/// `locked`, and deletes the rest.
+fn deny_locked(path: &Path) -> io::Result<()> {
+    if path.ends_with("locked") {
+        return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+    }
+    fs::remove_dir_all(path)
+}
+
+fn cache_with_locked_version() -> Cache {
+    let cache = Cache::new();
+    cache.version("zlib", "locked", 4 * DAY);
+    cache.version("zlib", "old", 3 * DAY);
+    cache.version("zlib", "new", DAY);
+    cache
+}
+
+#[test]
+fn permission_failures_are_counted_and_keep_going_continues() {
+    let cache = cache_with_locked_version();
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .jobs(1)
+        .keep_going(true)
+        .quiet(true)
+        .deleter(deny_locked)
+        .build()
+        .expect("valid options");
+
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    assert_eq!(cache.surviving("zlib"), names(&["locked", "new"]));
+    assert_eq!(report.versions_deleted, 1);
+    assert_eq!(report.versions_failed, 1);
+    assert_eq!(report.versions_permission_denied, 1);
+    assert_eq!(report.errors.len(), 1);
+}
+
+#[test]
+fn without_keep_going_the_first_permission_failure_stops_the_run() {
+    let cache = cache_with_locked_version();
+    cache.version("zstd", "1.0", 2 * DAY);
+    cache.version("zstd", "2.0", DAY);
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .jobs(1)
+        .quiet(true)
+        .deleter(deny_locked)
+        .build()
+        .expect("valid options");
+
+    let err = Cleaner::new(options).run().expect_err("deletion fails");
+
+    assert!(format!("{:#}", err).contains("locked"));
+    // Packages go by name, so zstd is never reached
+    assert_eq!(cache.surviving("zlib"), names(&["locked", "new"]));
+    assert_eq!(cache.surviving("zstd"), names(&["1.0", "2.0"]));
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use cleanpkgcache::cleaner::RetentionPolicy;
+use cleanpkgcache::{Cleaner, CleanerOptions, CleanerOptionsBuilder};
+use common::{names, Cache, DAY};
+use std::time::SystemTime;
+
+/// `zlib` with versions 1.0 to 4.0 of 100 bytes each, modified 4 to 1 days ago.
+fn four_versions() -> Cache {
+    let cache = Cache::new();
+    for version in 1..=4u32 {
+        cache.version("zlib", &format!("{}.0", version), (5 - version) * DAY);
+    }
+    cache
+}
+
+/// Cleans `cache` for real under `policy` with the options `configure` adds.
+fn clean(cache: &Cache, policy: RetentionPolicy, configure: impl FnOnce(CleanerOptionsBuilder) -> CleanerOptionsBuilder) {
+    let builder = CleanerOptions::builder().path(cache.root()).policy(policy).quiet(true).jobs(1);
+    let options = configure(builder).build().expect("valid options");
+    Cleaner::new(options).run().expect("clean cache");
+}
+
+/// The error building options for `policy` with what `configure` adds.
+fn policy_error(policy: RetentionPolicy, configure: impl FnOnce(CleanerOptionsBuilder) -> CleanerOptionsBuilder) -> String {
+    let builder = CleanerOptions::builder().path(".").policy(policy);
+    configure(builder).build().expect_err("invalid options").to_string()
+}
+
+#[test]
+fn count_policy_keeps_the_newest_versions() {
+    let cache = four_versions();
+
+    clean(&cache, RetentionPolicy::Count, |builder| builder.keep(2));
+
+    assert_eq!(cache.surviving("zlib"), names(&["3.0", "4.0"]));
+}
+
+#[test]
+fn age_policy_keeps_versions_newer_than_the_cutoff() {
+    let cache = four_versions();
+
+    clean(&cache, RetentionPolicy::Age, |builder| builder.delete_older_than(SystemTime::now() - 5 * DAY / 2));
+
+    assert_eq!(cache.surviving("zlib"), names(&["3.0", "4.0"]));
+}
+
+#[test]
+fn size_policy_keeps_the_newest_versions_within_the_budget() {
+    let cache = four_versions();
+
+    clean(&cache, RetentionPolicy::Size, |builder| builder.max_total_size(350));
+
+    assert_eq!(cache.surviving("zlib"), names(&["2.0", "3.0", "4.0"]));
+}
+
+#[test]
+fn each_policy_needs_its_own_setting_and_no_other() {
+    let cutoff = SystemTime::now() - DAY;
+
+    assert_eq!(
+        policy_error(RetentionPolicy::Count, |builder| builder.delete_older_than(cutoff)),
+        "policy(Count) cannot be combined with delete_older_than or max_total_size"
+    );
+    assert_eq!(
+        policy_error(RetentionPolicy::Count, |builder| builder.max_total_size(100)),
+        "policy(Count) cannot be combined with delete_older_than or max_total_size"
+    );
+    assert_eq!(policy_error(RetentionPolicy::Age, |builder| builder), "policy(Age) needs delete_older_than");
+    assert_eq!(
+        policy_error(RetentionPolicy::Age, |builder| builder.delete_older_than(cutoff).max_total_size(100)),
+        "policy(Age) cannot be combined with max_total_size"
+    );
+    assert_eq!(policy_error(RetentionPolicy::Size, |builder| builder), "policy(Size) needs max_total_size");
+    assert_eq!(
+        policy_error(RetentionPolicy::Size, |builder| builder.max_total_size(100).delete_older_than(cutoff)),
+        "policy(Size) cannot be combined with delete_older_than"
+    );
+}
+
+#[test]
+fn policy_errors_on_the_command_line_name_the_flags() {
+    let cache = four_versions();
+
+    let output = common::cleanpkgcache()
+        .args([cache.root().to_str().unwrap(), "--policy", "age", "--max-total-size", "1KiB"])
+        .output()
+        .expect("run cleanpkgcache");
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--policy age needs --delete-older-than"), "{}", stderr);
+    assert_eq!(cache.surviving("zlib").len(), 4);
+}
@@ -0,0 +1,373 @@
+mod common;
+
+use cleanpkgcache::cleaner::RetentionPolicy;
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+use filetime::FileTime;
+use std::time::SystemTime;
+
+/// Cleans `cache` for real, keeping the newest `keep` versions per package.
+fn clean(cache: &Cache, keep: usize) -> cleanpkgcache::cleaner::CleanReport {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(keep)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    let mut reports = Cleaner::new(options).run().expect("clean cache");
+    reports.remove(0)
+}
+
+#[test]
+fn fewer_versions_than_keep_are_all_kept() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.2.13", DAY);
+
+    let report = clean(&cache, 2);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.2.13"]));
+    assert_eq!(report.versions_deleted, 0);
+    assert_eq!(report.versions_kept, 1);
+}
+
+#[test]
+fn keep_larger_than_the_version_count_deletes_nothing() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.2.11", 3 * DAY);
+    cache.version("zlib", "1.2.12", 2 * DAY);
+    cache.version("zlib", "1.2.13", DAY);
+
+    let report = clean(&cache, 10);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.2.11", "1.2.12", "1.2.13"]));
+    assert_eq!(report.versions_deleted, 0);
+    assert!(report.errors.is_empty());
+}
+
+#[test]
+fn exactly_keep_versions_are_all_kept() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.2.12", 2 * DAY);
+    cache.version("zlib", "1.2.13", DAY);
+
+    let report = clean(&cache, 2);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.2.12", "1.2.13"]));
+    assert_eq!(report.versions_deleted, 0);
+    assert_eq!(report.packages_with_excess_versions, 0);
+}
+
+#[test]
+fn versions_beyond_keep_are_deleted_oldest_first() {
+    let cache = Cache::new();
+    // Numeric order wins over mtime: 14.9 is older than 14.10 however recent
+    cache.version("VC", "14.9.0", DAY);
+    cache.version("VC", "14.10.0", 3 * DAY);
+    cache.version("VC", "14.38.1", 2 * DAY);
+    cache.version("VC", "14.40.2", 4 * DAY);
+
+    let report = clean(&cache, 2);
+
+    assert_eq!(cache.surviving("VC"), names(&["14.38.1", "14.40.2"]));
+    assert_eq!(report.versions_deleted, 2);
+    assert_eq!(report.bytes_freed, 200);
+}
+
+#[test]
+fn higher_version_wins_over_a_newer_mtime() {
+    let cache = Cache::new();
+    let older = cache.version("VC", "14.38.33130", DAY);
+    let newer = cache.version("VC", "14.40.33807", DAY);
+    // Touch the lower version last, as a copy or restore from backup would
+    let now = SystemTime::now();
+    filetime::set_file_mtime(&newer, FileTime::from_system_time(now - 30 * DAY)).expect("set mtime");
+    filetime::set_file_mtime(&older, FileTime::from_system_time(now)).expect("set mtime");
+
+    clean(&cache, 1);
+
+    assert_eq!(cache.surviving("VC"), names(&["14.40.33807"]));
+}
+
+#[test]
+fn unnumbered_versions_are_ordered_by_mtime() {
+    let cache = Cache::new();
+    cache.version("nightly", "build-c", 3 * DAY);
+    cache.version("nightly", "build-a", DAY);
+    cache.version("nightly", "build-b", 2 * DAY);
+
+    clean(&cache, 1);
+
+    assert_eq!(cache.surviving("nightly"), names(&["build-a"]));
+}
+
+#[test]
+fn packages_are_cleaned_independently() {
+    let cache = Cache::new();
+    for (i, version) in ["1.0", "2.0", "3.0"].iter().enumerate() {
+        cache.version("a", version, (3 - i as u32) * DAY);
+    }
+    cache.version("b", "1.0", DAY);
+
+    let report = clean(&cache, 2);
+
+    assert_eq!(cache.surviving("a"), names(&["2.0", "3.0"]));
+    assert_eq!(cache.surviving("b"), names(&["1.0"]));
+    assert_eq!(report.packages_processed, 2);
+}
+
+#[test]
+fn dry_run_deletes_nothing() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 3 * DAY);
+    cache.version("zlib", "2.0", 2 * DAY);
+    cache.version("zlib", "3.0", DAY);
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .dry_run(true)
+        .quiet(true)
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.0", "2.0", "3.0"]));
+    assert_eq!(report.versions_deleted, 2);
+    assert_eq!(report.deleted_paths.len(), 2);
+}
+
+#[test]
+fn files_are_ignored_by_default() {
+    let cache = Cache::new();
+    cache.file("zlib", "1.0.zip", 10, 3 * DAY);
+    cache.version("zlib", "2.0", 2 * DAY);
+    cache.version("zlib", "3.0", DAY);
+
+    clean(&cache, 1);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.0.zip", "3.0"]));
+}
+
+#[test]
+fn include_files_mixes_files_and_directories() {
+    let cache = Cache::new();
+    cache.file("zlib", "1.0.zip", 10, DAY);
+    cache.version("zlib", "2.0", 3 * DAY);
+    cache.file("zlib", "3.0.zip", 30, 2 * DAY);
+    cache.version("zlib", "4.0", 4 * DAY);
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(2)
+        .include_files(true)
+        .quiet(true)
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    assert_eq!(cache.surviving("zlib"), names(&["3.0.zip", "4.0"]));
+    assert_eq!(report.versions_deleted, 2);
+    assert_eq!(report.bytes_freed, 10 + 100);
+}
+
+#[test]
+fn reports_list_packages_by_name() {
+    let cache = Cache::new();
+    for package in ["zlib", "curl", "openssl", "boost"] {
+        cache.version(package, "1.0", 2 * DAY);
+        cache.version(package, "2.0", DAY);
+    }
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .dry_run(true)
+        .jobs(4)
+        .quiet(true)
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    let packages: Vec<&str> = report.packages.iter().map(|package| package.name.as_str()).collect();
+    assert_eq!(packages, ["boost", "curl", "openssl", "zlib"]);
+    let deleted: Vec<_> = ["boost", "curl", "openssl", "zlib"]
+        .iter()
+        .map(|package| cache.root().join(package).join("1.0"))
+        .collect();
+    assert_eq!(report.deleted_paths, deleted);
+}
+
+/// Gives `package` the versions `1` to `count`, `count` being the newest.
+fn numbered_versions(cache: &Cache, package: &str, count: u32) {
+    for version in 1..=count {
+        cache.version(package, &version.to_string(), (count + 1 - version) * DAY);
+    }
+}
+
+#[test]
+fn keep_percent_rounds_up_per_package() {
+    let cache = Cache::new();
+    numbered_versions(&cache, "zlib", 8);
+    numbered_versions(&cache, "curl", 9);
+    numbered_versions(&cache, "zstd", 1);
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep_percent(25.0)
+        .quiet(true)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache");
+
+    assert_eq!(cache.surviving("zlib"), names(&["7", "8"]));
+    assert_eq!(cache.surviving("curl"), names(&["7", "8", "9"]));
+    assert_eq!(cache.surviving("zstd"), names(&["1"]));
+}
+
+#[test]
+fn keep_values_parse_as_counts_or_percentages() {
+    use cleanpkgcache::cleaner::{parse_keep, KeepCount};
+
+    assert_eq!(parse_keep("3"), Ok(KeepCount::Count(3)));
+    assert_eq!(parse_keep("25%"), Ok(KeepCount::Percent(25.0)));
+    assert_eq!(parse_keep("12.5%"), Ok(KeepCount::Percent(12.5)));
+    assert!(parse_keep("0").is_err());
+    assert!(parse_keep("0%").is_err());
+    assert!(parse_keep("150%").is_err());
+    assert!(parse_keep("%").is_err());
+    assert_eq!(KeepCount::Percent(25.0).resolve(8), 2);
+    assert_eq!(KeepCount::Percent(25.0).resolve(9), 3);
+}
+
+/// Cleans `cache` for real under `--policy age` with everything older than
+/// a day going, but at least `min_versions` kept.
+fn clean_by_age(cache: &Cache, min_versions: usize) -> cleanpkgcache::cleaner::CleanReport {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .policy(RetentionPolicy::Age)
+        .delete_older_than(SystemTime::now() - DAY / 2)
+        .min_versions(min_versions)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+#[test]
+fn min_versions_keeps_the_newest_despite_an_aggressive_age_policy() {
+    let cache = Cache::new();
+    for (index, version) in ["1.0", "2.0", "3.0", "4.0", "5.0"].iter().enumerate() {
+        cache.version("zlib", version, (10 - index as u32) * DAY);
+    }
+    cache.version("zstd", "1.0", 3 * DAY);
+    cache.version("zstd", "2.0", 2 * DAY);
+
+    let report = clean_by_age(&cache, 3);
+
+    assert_eq!(cache.surviving("zlib"), names(&["3.0", "4.0", "5.0"]));
+    assert_eq!(cache.surviving("zstd"), names(&["1.0", "2.0"]));
+    assert_eq!(report.versions_deleted, 2);
+}
+
+#[test]
+fn min_versions_counts_versions_kept_for_other_reasons() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 4 * DAY);
+    cache.version("zlib", "2.0", 3 * DAY);
+    cache.version("zlib", "3.0", 2 * DAY);
+    // Newer than the cutoff, so kept by the age policy already
+    cache.version("zlib", "4.0", DAY / 4);
+    cache.version("zlib", "5.0", DAY / 8);
+
+    clean_by_age(&cache, 3);
+
+    assert_eq!(cache.surviving("zlib"), names(&["3.0", "4.0", "5.0"]));
+}
+
+#[test]
+fn keep_override_applies_to_its_package_only() {
+    let cache = Cache::new();
+    numbered_versions(&cache, "VC17LTCG", 7);
+    numbered_versions(&cache, "zlib", 7);
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(2)
+        .keep_override("VC17LTCG", 5)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache");
+
+    assert_eq!(cache.surviving("VC17LTCG"), names(&["3", "4", "5", "6", "7"]));
+    assert_eq!(cache.surviving("zlib"), names(&["6", "7"]));
+}
+
+/// Cleans `cache` for real by size, within a budget of `limit` bytes.
+fn clean_by_size(cache: &Cache, limit: u64, allow_empty_packages: bool) -> cleanpkgcache::cleaner::CleanReport {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .policy(RetentionPolicy::Size)
+        .max_total_size(limit)
+        .allow_empty_packages(allow_empty_packages)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+#[test]
+fn a_budget_smaller_than_the_newest_version_still_keeps_it() {
+    let cache = Cache::new();
+    numbered_versions(&cache, "zlib", 3);
+
+    // Each version holds 100 bytes
+    let report = clean_by_size(&cache, 50, false);
+
+    assert_eq!(cache.surviving("zlib"), names(&["3"]));
+    assert_eq!(report.versions_deleted, 2);
+    let budget = report.size_budget.expect("size budget report");
+    assert_eq!(budget.total, 300);
+    assert_eq!(budget.trimmed, 200);
+}
+
+#[test]
+fn allow_empty_packages_lets_the_budget_take_the_newest_version_too() {
+    let cache = Cache::new();
+    numbered_versions(&cache, "zlib", 3);
+
+    clean_by_size(&cache, 50, true);
+
+    assert!(cache.surviving("zlib").is_empty());
+}
+
+#[test]
+fn package_depth_two_groups_scoped_packages() {
+    let cache = Cache::new();
+    for package in ["@babel/core", "@babel/parser", "@types/node"] {
+        cache.version(package, "1.0", 2 * DAY);
+        cache.version(package, "2.0", DAY);
+    }
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .package_depth(2)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    for package in ["@babel/core", "@babel/parser", "@types/node"] {
+        assert_eq!(cache.surviving(package), names(&["2.0"]), "{}", package);
+    }
+    let mut packages: Vec<&str> = report.packages.iter().map(|package| package.name.as_str()).collect();
+    packages.sort();
+    assert_eq!(packages, ["@babel/core", "@babel/parser", "@types/node"]);
+    assert_eq!(report.versions_deleted, 3);
+}
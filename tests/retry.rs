@@ -0,0 +1,80 @@
+mod common;
+
+use cleanpkgcache::cleaner::{CleanReport, Deleter};
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Deleters are plain functions, so each test counts its attempts in its own static
+static BUSY_TWICE_ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+static ALWAYS_BUSY_ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+static DENIED_ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+/// Fails as if the directory were busy on the first two attempts, then deletes it.
+fn busy_twice(path: &Path) -> io::Result<()> {
+    if BUSY_TWICE_ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+        return Err(io::Error::from(io::ErrorKind::ResourceBusy));
+    }
+    fs::remove_dir_all(path)
+}
+
+fn always_busy(_: &Path) -> io::Result<()> {
+    ALWAYS_BUSY_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+    Err(io::Error::from(io::ErrorKind::ResourceBusy))
+}
+
+fn denied(_: &Path) -> io::Result<()> {
+    DENIED_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+    Err(io::Error::from(io::ErrorKind::PermissionDenied))
+}
+
+/// Cleans a cache with one excess `zlib` version through `deleter`,
+/// retrying up to `retries` times.
+fn clean(deleter: Deleter, retries: u32) -> (Cache, CleanReport) {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 2 * DAY);
+    cache.version("zlib", "2.0", DAY);
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .retries(retries)
+        .deleter(deleter)
+        .keep_going(true)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+    (cache, report)
+}
+
+#[test]
+fn a_transient_failure_is_retried_until_it_succeeds() {
+    let (cache, report) = clean(busy_twice, 3);
+
+    assert_eq!(BUSY_TWICE_ATTEMPTS.load(Ordering::SeqCst), 3);
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+    assert_eq!(report.versions_deleted, 1);
+    assert_eq!(report.versions_failed, 0);
+}
+
+#[test]
+fn a_persistent_failure_gives_up_after_the_retries() {
+    let (cache, report) = clean(always_busy, 2);
+
+    assert_eq!(ALWAYS_BUSY_ATTEMPTS.load(Ordering::SeqCst), 3, "one attempt plus two retries");
+    assert_eq!(cache.surviving("zlib"), names(&["1.0", "2.0"]));
+    assert_eq!(report.versions_failed, 1);
+    assert_eq!(report.errors.len(), 1);
+}
+
+#[test]
+fn permission_errors_are_not_retried() {
+    let (_cache, report) = clean(denied, 3);
+
+    assert_eq!(DENIED_ATTEMPTS.load(Ordering::SeqCst), 1);
+    assert_eq!(report.versions_permission_denied, 1);
+}
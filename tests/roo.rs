@@ -0,0 +1,328 @@
+mod common;
+
+use cleanpkgcache::cleaner::OutputFormat;
+use cleanpkgcache::duration::{Cutoff, TimestampZone};
+use cleanpkgcache::roo::{clean_roo_checkpoints, extension_task_paths, parse_extension_id, RooOptions, ROO_EXTENSION_IDS};
+use common::{cleanpkgcache, set_age, Cache, RooTasks, DAY};
+use filetime::FileTime;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn options(tasks: &RooTasks, cutoff: Cutoff) -> RooOptions {
+    options_with_deep_age(tasks, cutoff, false)
+}
+
+fn options_with_deep_age(tasks: &RooTasks, cutoff: Cutoff, deep_age: bool) -> RooOptions {
+    RooOptions {
+        dry_run: false,
+        format: OutputFormat::Json,
+        cutoff,
+        task_dirs: vec![tasks.path().to_path_buf()],
+        subdirs: vec!["checkpoints".to_string()],
+        delete_task: false,
+        deep_age,
+        timestamps: TimestampZone::Utc,
+        protect_newer_than: None,
+        audit: None,
+        report_only_errors: false,
+    }
+}
+
+#[test]
+fn checkpoints_older_than_the_max_age_are_deleted() {
+    let tasks = RooTasks::new();
+    let old = tasks.task("old", SystemTime::now() - 61 * DAY);
+    let recent = tasks.task("recent", SystemTime::now() - 59 * DAY);
+
+    let report = clean_roo_checkpoints(&options(&tasks, Cutoff::Age(60 * DAY))).expect("clean checkpoints");
+
+    assert!(!old.join("checkpoints").exists());
+    assert!(old.exists(), "only the checkpoints folder goes");
+    assert!(recent.join("checkpoints").exists());
+    assert_eq!(report.tasks_inspected, 2);
+    assert_eq!(report.deleted, vec![old.join("checkpoints")]);
+    assert_eq!(report.bytes_freed, "{}".len() as u64);
+}
+
+#[test]
+fn a_task_modified_exactly_at_the_since_date_is_kept() {
+    let since = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let tasks = RooTasks::new();
+    let at_boundary = tasks.task("at-boundary", since);
+    let just_before = tasks.task("just-before", since - Duration::from_secs(1));
+
+    let report = clean_roo_checkpoints(&options(&tasks, Cutoff::Date(since))).expect("clean checkpoints");
+
+    assert!(at_boundary.join("checkpoints").exists());
+    assert!(!just_before.join("checkpoints").exists());
+    assert_eq!(report.deleted, vec![just_before.join("checkpoints")]);
+}
+
+#[test]
+fn by_default_a_stale_task_folder_loses_checkpoints_with_recent_contents() {
+    let tasks = RooTasks::new();
+    // The checkpoint files inside were just written
+    let task = tasks.task("touched-inside", SystemTime::now() - 61 * DAY);
+
+    clean_roo_checkpoints(&options(&tasks, Cutoff::Age(60 * DAY))).expect("clean checkpoints");
+
+    assert!(!task.join("checkpoints").exists());
+}
+
+#[test]
+fn deep_age_keeps_a_task_with_recent_contents() {
+    let tasks = RooTasks::new();
+    let task = tasks.task("touched-inside", SystemTime::now() - 61 * DAY);
+
+    let report = clean_roo_checkpoints(&options_with_deep_age(&tasks, Cutoff::Age(60 * DAY), true))
+        .expect("clean checkpoints");
+
+    assert!(task.join("checkpoints").exists());
+    assert!(report.deleted.is_empty());
+}
+
+#[test]
+fn deep_age_still_cleans_a_task_that_is_old_throughout() {
+    let tasks = RooTasks::new();
+    let task = tasks.task("old", SystemTime::now() - 61 * DAY);
+    set_age(&task.join("checkpoints").join("state.json"), 62 * DAY);
+    set_age(&task.join("checkpoints"), 62 * DAY);
+
+    let report = clean_roo_checkpoints(&options_with_deep_age(&tasks, Cutoff::Age(60 * DAY), true))
+        .expect("clean checkpoints");
+
+    assert_eq!(report.deleted, vec![task.join("checkpoints")]);
+}
+
+#[test]
+fn protect_newer_than_keeps_recent_checkpoints_of_an_old_task() {
+    let tasks = RooTasks::new();
+    let resumed = tasks.task("resumed", SystemTime::now() - 61 * DAY);
+    let stale = tasks.task("stale", SystemTime::now() - 61 * DAY);
+    // Either side of a 7 day threshold, with an hour's slack for the test's own runtime
+    set_age(&resumed.join("checkpoints"), 7 * DAY - Duration::from_secs(3600));
+    set_age(&stale.join("checkpoints"), 7 * DAY + Duration::from_secs(3600));
+    let mut options = options(&tasks, Cutoff::Age(60 * DAY));
+    options.protect_newer_than = Some(7 * DAY);
+
+    let report = clean_roo_checkpoints(&options).expect("clean checkpoints");
+
+    assert!(resumed.join("checkpoints").exists());
+    assert_eq!(report.deleted, vec![stale.join("checkpoints")]);
+}
+
+#[test]
+fn protect_newer_than_keeps_the_whole_task_with_delete_task() {
+    let tasks = RooTasks::new();
+    let resumed = tasks.task("resumed", SystemTime::now() - 61 * DAY);
+    set_age(&resumed.join("checkpoints"), DAY);
+    let mut options = options(&tasks, Cutoff::Age(60 * DAY));
+    options.delete_task = true;
+    options.protect_newer_than = Some(7 * DAY);
+
+    let report = clean_roo_checkpoints(&options).expect("clean checkpoints");
+
+    assert!(resumed.join("checkpoints").exists());
+    assert_eq!(report.tasks_deleted, 0);
+}
+
+#[test]
+fn task_paths_follow_custom_extension_ids() {
+    let storage = Path::new("globalStorage");
+    let ids = [parse_extension_id("saoudrizwan.claude-dev").expect("valid extension ID")];
+
+    assert_eq!(
+        extension_task_paths(storage, &ids),
+        vec![PathBuf::from("globalStorage").join("saoudrizwan.claude-dev").join("tasks")]
+    );
+    assert_eq!(extension_task_paths(storage, &ROO_EXTENSION_IDS).len(), 2);
+    assert!(parse_extension_id("claude-dev").is_err());
+    assert!(parse_extension_id("../saoudrizwan.claude-dev").is_err());
+    assert!(parse_extension_id(".claude-dev").is_err());
+}
+
+/// Runs the `cleanpkgcache` binary with `args`, away from any user config
+/// or environment.
+fn run(args: &[&str]) -> Output {
+    cleanpkgcache()
+        .args(["--color", "never"])
+        .args(args)
+        .output()
+        .expect("run cleanpkgcache")
+}
+
+#[test]
+fn roo_tasks_dir_replaces_the_detected_directories() {
+    let cache = Cache::new();
+    let tasks = RooTasks::new();
+    let old = tasks.task("old", SystemTime::now() - 31 * DAY);
+    let recent = tasks.task("recent", SystemTime::now() - 29 * DAY);
+
+    let output = run(&[
+        cache.root().to_str().unwrap(),
+        "--clean-roo-checkpoints",
+        "--roo-max-age",
+        "30d",
+        "--roo-tasks-dir",
+        tasks.path().to_str().unwrap(),
+    ]);
+
+    assert!(output.status.success());
+    assert!(!old.join("checkpoints").exists());
+    assert!(recent.join("checkpoints").exists());
+}
+
+#[test]
+fn a_missing_roo_tasks_dir_is_reported_with_verbose() {
+    let cache = Cache::new();
+    let tasks = RooTasks::new();
+    let missing = tasks.path().join("missing");
+
+    let output = run(&[
+        cache.root().to_str().unwrap(),
+        "--clean-roo-checkpoints",
+        "--verbose",
+        "--roo-tasks-dir",
+        missing.to_str().unwrap(),
+    ]);
+
+    assert!(output.status.success());
+    let log = String::from_utf8_lossy(&output.stderr);
+    assert!(log.contains(&format!("Skipping {} (path not found)", missing.display())), "{}", log);
+}
+
+#[test]
+fn roo_since_cleans_tasks_last_modified_before_the_date() {
+    let since = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+    let cache = Cache::new();
+    let tasks = RooTasks::new();
+    let before = tasks.task("before", since - Duration::from_secs(1));
+    let on_the_day = tasks.task("on-the-day", since);
+
+    let output = run(&[
+        cache.root().to_str().unwrap(),
+        "--clean-roo-checkpoints",
+        "--roo-since",
+        "2024-01-01",
+        "--roo-tasks-dir",
+        tasks.path().to_str().unwrap(),
+    ]);
+
+    assert!(output.status.success());
+    assert!(!before.join("checkpoints").exists());
+    assert!(on_the_day.join("checkpoints").exists());
+}
+
+#[test]
+fn roo_since_conflicts_with_roo_max_age() {
+    let cache = Cache::new();
+
+    let output = run(&[cache.root().to_str().unwrap(), "--roo-since", "2024-01-01", "--roo-max-age", "30d"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+/// Creates a task like [`RooTasks::task`] that also has the folders `extra`.
+fn task_with_subdirs(tasks: &RooTasks, name: &str, modified: SystemTime, extra: &[&str]) -> PathBuf {
+    let task = tasks.task(name, modified);
+    for subdir in extra {
+        fs::create_dir(task.join(subdir)).expect("create task subdirectory");
+        fs::write(task.join(subdir).join("data.json"), "[]").expect("write task data");
+    }
+    filetime::set_file_mtime(&task, FileTime::from_system_time(modified)).expect("set task mtime");
+    task
+}
+
+#[test]
+fn roo_subdirs_removes_only_the_listed_folders_and_counts_each() {
+    let cache = Cache::new();
+    let tasks = RooTasks::new();
+    let extra = ["api_conversation_history", "ui_messages"];
+    let old = task_with_subdirs(&tasks, "old", SystemTime::now() - 31 * DAY, &extra);
+    let fresh = task_with_subdirs(&tasks, "fresh", SystemTime::now() - DAY, &extra);
+
+    let output = run(&[
+        cache.root().to_str().unwrap(),
+        "--clean-roo-checkpoints",
+        "--roo-max-age",
+        "30d",
+        "--roo-tasks-dir",
+        tasks.path().to_str().unwrap(),
+        "--roo-subdirs",
+        "checkpoints,api_conversation_history",
+    ]);
+
+    assert!(output.status.success());
+    assert!(!old.join("checkpoints").exists());
+    assert!(!old.join("api_conversation_history").exists());
+    assert!(old.join("ui_messages").exists(), "unlisted folders are left alone");
+    for subdir in ["checkpoints", "api_conversation_history", "ui_messages"] {
+        assert!(fresh.join(subdir).exists(), "{} of the fresh task", subdir);
+    }
+    let summary = String::from_utf8_lossy(&output.stdout);
+    assert!(summary.contains("  checkpoints folders deleted: 1\n"), "{}", summary);
+    assert!(summary.contains("  api_conversation_history folders deleted: 1\n"), "{}", summary);
+    assert!(!summary.contains("ui_messages"), "{}", summary);
+}
+
+#[test]
+fn roo_delete_task_summary_counts_task_folders() {
+    let cache = Cache::new();
+    let tasks = RooTasks::new();
+    let old = tasks.task("old", SystemTime::now() - 90 * DAY);
+    tasks.task("recent", SystemTime::now() - DAY);
+    let args = [
+        cache.root().to_str().unwrap(),
+        "--clean-roo-checkpoints",
+        "--roo-delete-task",
+        "--roo-tasks-dir",
+        tasks.path().to_str().unwrap(),
+    ];
+
+    let dry_run = run(&[&args[..], &["--dry-run"]].concat());
+    let summary = String::from_utf8_lossy(&dry_run.stdout);
+    assert!(summary.contains("  Task folders eligible for deletion: 1\n"), "{}", summary);
+    assert!(old.exists());
+
+    let output = run(&args);
+    let summary = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(summary.contains("  Task folders deleted: 1\n"), "{}", summary);
+    assert!(!summary.contains("checkpoints folders"), "{}", summary);
+    assert!(!old.exists());
+}
+
+#[test]
+fn roo_delete_task_conflicts_with_roo_subdirs() {
+    let cache = Cache::new();
+
+    let output = run(&[cache.root().to_str().unwrap(), "--roo-delete-task", "--roo-subdirs", "checkpoints"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn roo_delete_task_only_removes_immediate_children_of_the_tasks_dir() {
+    let tasks = RooTasks::new();
+    let old = SystemTime::now() - 90 * DAY;
+    tasks.task("old", old);
+    let recent = tasks.task("recent", SystemTime::now() - DAY);
+    // An aged folder inside a recent task is not a task of its own
+    let nested = recent.join("checkpoints").join("old-snapshot");
+    fs::create_dir(&nested).expect("create nested folder");
+    filetime::set_file_mtime(&nested, FileTime::from_system_time(old)).expect("set nested mtime");
+    filetime::set_file_mtime(&recent, FileTime::from_system_time(SystemTime::now() - DAY)).expect("set task mtime");
+    filetime::set_file_mtime(tasks.path(), FileTime::from_system_time(old)).expect("set tasks dir mtime");
+    let mut options = options(&tasks, Cutoff::Age(60 * DAY));
+    options.delete_task = true;
+
+    let report = clean_roo_checkpoints(&options).expect("clean checkpoints");
+
+    assert_eq!(report.deleted, vec![tasks.path().join("old")]);
+    assert!(tasks.path().is_dir(), "the tasks directory itself stays");
+    assert!(nested.exists());
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use cleanpkgcache::sidecar::{parse_version_source, VersionSource};
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, set_age, Cache, DAY};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Writes `json` as `metadata.json` in `version`, keeping its age.
+fn sidecar(version: &Path, json: &str, age: Duration) {
+    fs::write(version.join("metadata.json"), json).expect("write sidecar");
+    set_age(version, age);
+}
+
+/// Cleans `cache` for real, keeping the newest version, with `--version-from source`.
+fn clean(cache: &Cache, source: &str) {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .version_from(parse_version_source(source).expect("valid source"))
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache");
+}
+
+#[test]
+fn sidecar_version_overrides_the_directory_name() {
+    let cache = Cache::new();
+    let build_a = cache.version("tool", "build-a", DAY);
+    let build_b = cache.version("tool", "build-b", 2 * DAY);
+    sidecar(&build_a, r#"{"version": "1.9.0"}"#, DAY);
+    sidecar(&build_b, r#"{"version": "1.10.0"}"#, 2 * DAY);
+
+    clean(&cache, "metadata.json:version");
+
+    assert_eq!(cache.surviving("tool"), names(&["build-b"]));
+}
+
+#[test]
+fn sidecar_date_overrides_the_timestamp() {
+    let cache = Cache::new();
+    let old = cache.version("tool", "old", DAY);
+    let new = cache.version("tool", "new", 2 * DAY);
+    sidecar(&old, r#"{"build": {"date": "2020-01-01"}}"#, DAY);
+    sidecar(&new, r#"{"build": {"date": "2024-05-01T10:00:00Z"}}"#, 2 * DAY);
+
+    clean(&cache, "metadata.json:build.date");
+
+    assert_eq!(cache.surviving("tool"), names(&["new"]));
+}
+
+#[test]
+fn versions_without_a_sidecar_fall_back_to_their_name() {
+    let cache = Cache::new();
+    let with = cache.version("tool", "1.0", DAY);
+    cache.version("tool", "2.0", 2 * DAY);
+    cache.version("tool", "3.0", 3 * DAY);
+    sidecar(&with, "not json", DAY);
+
+    clean(&cache, "metadata.json:version");
+
+    assert_eq!(cache.surviving("tool"), names(&["3.0"]));
+}
+
+#[test]
+fn source_must_name_a_plain_file_and_a_field() {
+    assert_eq!(
+        parse_version_source("metadata.json:build.version"),
+        Ok(VersionSource { file: "metadata.json".to_string(), field: "build.version".to_string() })
+    );
+    assert!(parse_version_source("metadata.json").is_err());
+    assert!(parse_version_source("../metadata.json:version").is_err());
+    assert!(parse_version_source("metadata.json:").is_err());
+    assert!(parse_version_source("metadata.json:build..version").is_err());
+}
@@ -0,0 +1,63 @@
+use cleanpkgcache::size::dir_size;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tempfile::TempDir;
+
+/// Creates a chain of `depth` nested directories below `root`, each holding
+/// one file of `len` bytes, and returns the total.
+fn deep_tree(root: &Path, depth: usize, len: usize) -> u64 {
+    let mut dir = root.to_path_buf();
+    for _ in 0..depth {
+        dir.push("d");
+        fs::create_dir(&dir).expect("create nested directory");
+        fs::write(dir.join("f"), vec![0u8; len]).expect("write file");
+    }
+    (depth * len) as u64
+}
+
+/// Creates `width` sibling directories below `root`, each holding `files`
+/// files of `len` bytes, and returns the total.
+fn wide_tree(root: &Path, width: usize, files: usize, len: usize) -> u64 {
+    for index in 0..width {
+        let dir: PathBuf = root.join(format!("w{}", index));
+        fs::create_dir_all(&dir).expect("create directory");
+        for file in 0..files {
+            fs::write(dir.join(format!("f{}", file)), vec![0u8; len]).expect("write file");
+        }
+    }
+    (width * files * len) as u64
+}
+
+#[test]
+fn sizes_deep_and_wide_trees_exactly() {
+    let dir = TempDir::new().expect("create temporary directory");
+    let expected = deep_tree(dir.path(), 300, 7) + wide_tree(dir.path(), 50, 4, 13);
+
+    assert_eq!(dir_size(dir.path()).expect("measure tree"), expected);
+}
+
+#[test]
+fn empty_directory_has_no_size() {
+    let dir = TempDir::new().expect("create temporary directory");
+
+    assert_eq!(dir_size(dir.path()).expect("measure tree"), 0);
+}
+
+/// Run with `cargo test --release --test size -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn benchmark_large_synthetic_tree() {
+    let dir = TempDir::new().expect("create temporary directory");
+    let mut expected = 0;
+    for branch in 0..20 {
+        let root = dir.path().join(format!("b{}", branch));
+        fs::create_dir(&root).expect("create branch");
+        expected += deep_tree(&root, 200, 1) + wide_tree(&root, 100, 10, 1);
+    }
+
+    let start = Instant::now();
+    let size = dir_size(dir.path()).expect("measure tree");
+    println!("measured {} directories in {:?}", 20 * 300, start.elapsed());
+    assert_eq!(size, expected);
+}
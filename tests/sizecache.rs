@@ -0,0 +1,41 @@
+mod common;
+
+use cleanpkgcache::sizecache::SizeCache;
+use common::{set_age, Cache, DAY};
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn cached_sizes_hold_until_the_directory_mtime_changes() {
+    let cache = Cache::new();
+    let version = cache.version("zlib", "1.0", 2 * DAY);
+    let cache_dir = TempDir::new().expect("create size cache directory");
+    let sizes = cache_dir.path().join("cleanpkgcache").join("sizes.json");
+
+    let first = SizeCache::load(&sizes);
+    assert_eq!(first.dir_size(&version).expect("measure version"), 100);
+    first.save().expect("save size cache");
+
+    // Rewriting a file inside leaves the directory's mtime alone
+    fs::write(version.join("payload.bin"), [0u8; 300]).expect("grow payload");
+    let reloaded = SizeCache::load(&sizes);
+    assert_eq!(reloaded.dir_size(&version).expect("measure version"), 100, "served from the cache");
+
+    set_age(&version, DAY);
+    assert_eq!(reloaded.dir_size(&version).expect("measure version"), 300, "measured again");
+}
+
+#[test]
+fn saving_drops_directories_that_are_gone() {
+    let cache = Cache::new();
+    let version = cache.version("zlib", "1.0", DAY);
+    let cache_dir = TempDir::new().expect("create size cache directory");
+    let sizes = cache_dir.path().join("sizes.json");
+
+    let size_cache = SizeCache::load(&sizes);
+    size_cache.dir_size(&version).expect("measure version");
+    fs::remove_dir_all(&version).expect("remove version");
+    size_cache.save().expect("save size cache");
+
+    assert_eq!(fs::read_to_string(&sizes).expect("read size cache"), "{}");
+}
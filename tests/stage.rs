@@ -0,0 +1,68 @@
+mod common;
+
+use cleanpkgcache::cleaner::CleanOptions;
+use cleanpkgcache::stage::purge_staged;
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+
+/// Cleans `cache` for real with `--stage`, keeping the newest `keep` versions.
+fn stage(cache: &Cache, keep: usize) -> cleanpkgcache::cleaner::CleanReport {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(keep)
+        .stage(true)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+#[test]
+fn staging_renames_old_versions_instead_of_deleting_them() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.2.11", 3 * DAY);
+    cache.version("zlib", "1.2.12", 2 * DAY);
+    cache.version("zlib", "1.2.13", DAY);
+
+    let report = stage(&cache, 2);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.2.11.to-delete", "1.2.12", "1.2.13"]));
+    assert_eq!(report.versions_deleted, 1);
+    assert!(cache.root().join("zlib").join("1.2.11.to-delete").join("payload.bin").exists());
+}
+
+#[test]
+fn staged_versions_do_not_count_as_versions_on_the_next_run() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.2.12", 2 * DAY);
+    cache.version("zlib", "1.2.13", DAY);
+    stage(&cache, 1);
+
+    let report = stage(&cache, 1);
+
+    assert_eq!(report.versions_kept, 1);
+    assert_eq!(report.versions_deleted, 0);
+    assert_eq!(cache.surviving("zlib"), names(&["1.2.12.to-delete", "1.2.13"]));
+}
+
+#[test]
+fn purging_removes_only_staged_versions() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.2.12", 2 * DAY);
+    cache.version("zlib", "1.2.13", DAY);
+    cache.version("zstd", "1.5", DAY);
+    stage(&cache, 1);
+
+    let dry_run = CleanOptions { dry_run: true, ..CleanOptions::default() };
+    let preview = purge_staged(cache.root(), &dry_run).expect("preview purge");
+    assert_eq!(preview.purged, vec![cache.root().join("zlib").join("1.2.12.to-delete")]);
+    assert_eq!(cache.surviving("zlib"), names(&["1.2.12.to-delete", "1.2.13"]));
+
+    let report = purge_staged(cache.root(), &CleanOptions::default()).expect("purge");
+
+    assert_eq!(report.purged.len(), 1);
+    assert_eq!(report.bytes_freed, 100);
+    assert_eq!(cache.surviving("zlib"), names(&["1.2.13"]));
+    assert_eq!(cache.surviving("zstd"), names(&["1.5"]));
+}
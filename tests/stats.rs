@@ -0,0 +1,30 @@
+mod common;
+
+use cleanpkgcache::stats::cache_stats;
+use cleanpkgcache::CleanerOptions;
+use common::{names, Cache, DAY};
+
+#[test]
+fn stats_report_sizes_and_reclaimable_space_without_deleting() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 3 * DAY);
+    cache.version("zlib", "2.0", 2 * DAY);
+    cache.version("zlib", "3.0", DAY);
+    cache.version("curl", "8.0", DAY);
+
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .build()
+        .expect("valid options");
+    let stats = cache_stats(cache.root(), options.clean_options()).expect("collect stats");
+
+    assert_eq!(stats.packages, 2);
+    assert_eq!(stats.versions, 4);
+    assert_eq!(stats.average_versions_per_package, 2.0);
+    assert_eq!(stats.total_bytes, 400);
+    assert_eq!(stats.reclaimable_versions, 2);
+    assert_eq!(stats.reclaimable_bytes, 200);
+    assert!(stats.oldest_version < stats.newest_version);
+    assert_eq!(cache.surviving("zlib"), names(&["1.0", "2.0", "3.0"]));
+}
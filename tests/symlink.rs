@@ -0,0 +1,57 @@
+#![cfg(unix)]
+
+mod common;
+
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// A cache whose older `zlib/1.0` is a symlink to a directory outside it.
+/// Returns the directory holding the link's target too, to keep it alive.
+fn cache_with_symlinked_version() -> (Cache, TempDir, PathBuf) {
+    let cache = Cache::new();
+    let elsewhere = TempDir::new().expect("create target directory");
+    let target = elsewhere.path().join("zlib-1.0");
+    fs::create_dir(&target).expect("create link target");
+    fs::write(target.join("payload.bin"), [0u8; 100]).expect("write payload");
+    cache.version("zlib", "2.0", DAY);
+    fs::create_dir_all(cache.root().join("zlib")).expect("create package directory");
+    symlink(&target, cache.root().join("zlib").join("1.0")).expect("create symlink");
+    (cache, elsewhere, target)
+}
+
+fn clean(cache: &Cache, follow_symlinks: bool) {
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .follow_symlinks(follow_symlinks)
+        .quiet(true)
+        .jobs(1)
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+    assert_eq!(report.versions_deleted, 1);
+}
+
+#[test]
+fn deleting_a_symlinked_version_removes_only_the_link() {
+    let (cache, _elsewhere, target) = cache_with_symlinked_version();
+
+    clean(&cache, false);
+
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+    assert!(target.join("payload.bin").exists(), "the link's target is left alone");
+}
+
+#[test]
+fn follow_symlinks_deletes_the_target_as_well() {
+    let (cache, _elsewhere, target) = cache_with_symlinked_version();
+
+    clean(&cache, true);
+
+    assert_eq!(cache.surviving("zlib"), names(&["2.0"]));
+    assert!(!target.exists());
+}
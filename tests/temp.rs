@@ -0,0 +1,48 @@
+mod common;
+
+use cleanpkgcache::cleaner::CleanReport;
+use cleanpkgcache::{Cleaner, CleanerOptions, CleanerOptionsBuilder};
+use common::{names, Cache, DAY};
+
+/// `zlib` with real versions, some with names close to the temp patterns,
+/// and the newer leftovers of interrupted installs.
+fn cache_with_leftovers() -> Cache {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 6 * DAY);
+    cache.version("zlib", "2.0-tmpfs", 5 * DAY);
+    cache.version("zlib", "2.1-partial", 4 * DAY);
+    cache.version("zlib", "3.0", 3 * DAY);
+    cache.version("zlib", "3.1.tmp", 2 * DAY);
+    cache.version("zlib", "3.2~", DAY);
+    cache.version("zlib", ".partial-3.3", DAY / 2);
+    cache
+}
+
+/// Cleans `cache` for real with `--clean-temp`, keeping every real version.
+fn clean(cache: &Cache, configure: impl FnOnce(CleanerOptionsBuilder) -> CleanerOptionsBuilder) -> CleanReport {
+    let builder = CleanerOptions::builder().path(cache.root()).keep(4).clean_temp(true).quiet(true).jobs(1);
+    let options = configure(builder).build().expect("valid options");
+    Cleaner::new(options).run().expect("clean cache").remove(0)
+}
+
+#[test]
+fn temp_dirs_are_removed_and_dont_count_as_versions() {
+    let cache = cache_with_leftovers();
+
+    let report = clean(&cache, |builder| builder);
+
+    assert_eq!(cache.surviving("zlib"), names(&["1.0", "2.0-tmpfs", "2.1-partial", "3.0"]));
+    assert_eq!(report.temp_dirs_removed, 3);
+    assert_eq!(report.bytes_freed_by_temp, 300);
+    assert_eq!(report.versions_kept, 4);
+}
+
+#[test]
+fn temp_patterns_replace_the_defaults() {
+    let cache = cache_with_leftovers();
+
+    let report = clean(&cache, |builder| builder.keep(10).temp_pattern("*-partial"));
+
+    assert_eq!(report.temp_dirs_removed, 1);
+    assert_eq!(cache.surviving("zlib"), names(&[".partial-3.3", "1.0", "2.0-tmpfs", "3.0", "3.1.tmp", "3.2~"]));
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use cleanpkgcache::template::SummaryTemplate;
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{Cache, DAY};
+
+#[test]
+fn placeholders_are_filled_in_from_the_report() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 2 * DAY);
+    cache.version("zlib", "2.0", DAY);
+    let options = CleanerOptions::builder()
+        .path(cache.root())
+        .keep(1)
+        .dry_run(true)
+        .quiet(true)
+        .build()
+        .expect("valid options");
+    let report = Cleaner::new(options).run().expect("clean cache").remove(0);
+
+    let template = SummaryTemplate::parse("{mode}: {would-delete} of {packages} ({freed-bytes} B) {{ok}}").unwrap();
+
+    assert_eq!(template.render(&report, true), "dry-run: 1 of 1 (100 B) {ok}");
+}
+
+#[test]
+fn unknown_placeholders_and_stray_braces_are_refused() {
+    assert!(SummaryTemplate::parse("{deleted} {nope}").unwrap_err().contains("unknown placeholder '{nope}'"));
+    assert!(SummaryTemplate::parse("{deleted").is_err());
+    assert!(SummaryTemplate::parse("deleted}").is_err());
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{cleanpkgcache, Cache};
+use filetime::FileTime;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// `vendor` holding unnumbered versions that were all copied in at the same instant.
+fn cache_with_tied_versions() -> Cache {
+    let cache = Cache::new();
+    let copied = FileTime::from_system_time(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    for name in ["beta", "gamma", "alpha"] {
+        let path = cache.version("vendor", name, Duration::ZERO);
+        filetime::set_file_mtime(&path, copied).expect("set mtime");
+    }
+    cache
+}
+
+/// Runs `cleanpkgcache --keep 1` on `cache`, away from any user config or
+/// environment, and returns the surviving versions and the log output.
+fn clean(cache: &Cache) -> (Vec<String>, String) {
+    let output = cleanpkgcache()
+        .args(["--keep", "1", "--color", "never"])
+        .arg(cache.root())
+        .output()
+        .expect("run cleanpkgcache");
+    assert!(output.status.success());
+    (cache.surviving("vendor"), String::from_utf8(output.stderr).expect("utf-8 output"))
+}
+
+#[test]
+fn identical_timestamps_keep_the_same_version_every_run() {
+    for _ in 0..3 {
+        let cache = cache_with_tied_versions();
+
+        let (surviving, log) = clean(&cache);
+
+        assert_eq!(surviving, ["gamma"], "the name that sorts last wins the tie");
+        assert!(
+            log.contains("vendor: gamma and beta have the same version and mtime, keeping gamma because its name sorts later"),
+            "{}",
+            log
+        );
+    }
+}
+
+#[test]
+fn no_warning_when_the_timestamps_differ() {
+    let cache = Cache::new();
+    cache.version("vendor", "alpha", Duration::from_secs(60));
+    cache.version("vendor", "beta", Duration::from_secs(120));
+
+    let (surviving, log) = clean(&cache);
+
+    assert_eq!(surviving, ["alpha"]);
+    assert!(!log.contains("have the same version"), "{}", log);
+}
@@ -0,0 +1,36 @@
+use cleanpkgcache::duration::{format_timestamp, TimestampZone};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn utc_timestamps_are_rfc3339_to_the_second() {
+    let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+
+    assert_eq!(format_timestamp(time, TimestampZone::Utc), "2023-11-14T22:13:20Z");
+}
+
+#[test]
+fn local_timestamps_carry_their_offset() {
+    let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+    let formatted = format_timestamp(time, TimestampZone::Local);
+
+    // 2023-11-14T22:13:20+00:00, in whatever zone the tests run in
+    let bytes = formatted.as_bytes();
+    assert_eq!(bytes.len(), 25, "{}", formatted);
+    for (index, byte) in bytes.iter().enumerate() {
+        let expected = match index {
+            4 | 7 => b'-',
+            10 => b'T',
+            13 | 16 | 22 => b':',
+            19 => {
+                assert!(*byte == b'+' || *byte == b'-', "{}", formatted);
+                continue;
+            }
+            _ => {
+                assert!(byte.is_ascii_digit(), "{}", formatted);
+                continue;
+            }
+        };
+        assert_eq!(*byte, expected, "{}", formatted);
+    }
+}
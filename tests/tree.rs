@@ -0,0 +1,53 @@
+mod common;
+
+use common::{cleanpkgcache, Cache, DAY};
+
+/// Runs `cleanpkgcache --dry-run --tree` on `cache` with extra `args`, away
+/// from any user config, and returns what it printed. `--color never` draws
+/// the tree in ASCII even though the locale is UTF-8.
+fn tree(cache: &Cache, args: &[&str]) -> String {
+    let output = cleanpkgcache()
+        .arg(cache.root())
+        .args(["--dry-run", "--tree", "--keep", "1", "--color", "never"])
+        .args(args)
+        .env("LANG", "en_US.UTF-8")
+        .env_remove("LC_ALL")
+        .env_remove("LC_CTYPE")
+        .output()
+        .expect("run cleanpkgcache");
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).expect("utf-8 output")
+}
+
+#[test]
+fn tree_marks_each_version_under_its_package() {
+    let cache = Cache::new();
+    cache.version("zlib", "1.0", 2 * DAY);
+    cache.version("zlib", "2.0", DAY);
+    cache.version("zstd", "1.5", DAY);
+    let root = cache.root().display().to_string();
+
+    let output = tree(&cache, &[]);
+
+    let expected = [
+        root.as_str(),
+        "+- zlib/",
+        "|  +- 2.0 (KEEP 100 B)",
+        "|  `- 1.0 (DELETE 100 B)",
+        "`- zstd/",
+        "   `- 1.5 (KEEP 100 B)",
+    ];
+    assert!(output.starts_with(&(expected.join("\n") + "\n")), "{}", output);
+}
+
+#[test]
+fn tree_nests_deeper_packages() {
+    let cache = Cache::new();
+    cache.version("zlib/x64", "1.0", DAY);
+    let root = cache.root().display().to_string();
+
+    let output = tree(&cache, &["--package-depth", "2"]);
+
+    let expected = [root.as_str(), "`- zlib/", "   `- x64/", "      `- 1.0 (KEEP 100 B)"];
+    assert!(output.starts_with(&(expected.join("\n") + "\n")), "{}", output);
+}
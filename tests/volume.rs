@@ -0,0 +1,37 @@
+mod common;
+
+use cleanpkgcache::{Cleaner, CleanerOptions};
+use common::{names, Cache, DAY};
+
+#[test]
+fn jobs_per_volume_cleans_every_root_and_reports_them_in_order() {
+    let first = Cache::new();
+    let second = Cache::new();
+    for (cache, pkg) in [(&first, "zlib"), (&second, "curl")] {
+        cache.version(pkg, "1.0", 3 * DAY);
+        cache.version(pkg, "2.0", 2 * DAY);
+        cache.version(pkg, "3.0", DAY);
+    }
+
+    let options = CleanerOptions::builder()
+        .path(second.root())
+        .path(first.root())
+        .keep(1)
+        .jobs_per_volume(2)
+        .build()
+        .expect("valid options");
+    let reports = Cleaner::new(options).run().expect("clean both roots");
+
+    let paths: Vec<_> = reports.iter().map(|report| report.path.as_path()).collect();
+    assert_eq!(paths, [second.root(), first.root()]);
+    assert!(reports.iter().all(|report| report.versions_deleted == 2));
+    assert_eq!(first.surviving("zlib"), names(&["3.0"]));
+    assert_eq!(second.surviving("curl"), names(&["3.0"]));
+}
+
+#[test]
+fn jobs_per_volume_must_be_at_least_one() {
+    let cache = Cache::new();
+    let result = CleanerOptions::builder().path(cache.root()).jobs_per_volume(0).build();
+    assert!(result.is_err());
+}